@@ -0,0 +1 @@
+// Stand-in for a vendored copy of the lottie-player web component
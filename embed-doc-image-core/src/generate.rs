@@ -0,0 +1,65 @@
+//! Generating images in build scripts.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Writes an image generated by a build script to `OUT_DIR`, where
+/// `embed_doc_image::embed_image_from_out_dir!` embeds it from.
+///
+/// A proc macro cannot call functions of the crate it is used in, so images generated by the
+/// crate itself, such as plots of the functions it implements, have to be generated by its build
+/// script. This calls `generate` and writes the image it returns to `name`, a path relative to
+/// `OUT_DIR`, creating parent directories as needed. Returns the path of the written image.
+/// Fails if `OUT_DIR` is not set, i.e. if called outside a build script, or if the image cannot
+/// be written.
+///
+/// ```rust,no_run
+/// // build.rs
+/// fn render_plot() -> Vec<u8> {
+///     // Render the plot, e.g. with code of the crate included with `#[path = ".."] mod ..;`
+/// #   Vec::new()
+/// }
+///
+/// fn main() {
+///     embed_doc_image_core::write_generated_image("plot.png", render_plot).unwrap();
+/// }
+/// ```
+pub fn write_generated_image(
+    name: impl AsRef<Path>,
+    generate: impl FnOnce() -> Vec<u8>,
+) -> io::Result<PathBuf> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "`OUT_DIR` is not set. Generated images must be written by a build script.",
+        )
+    })?;
+    write_image(Path::new(&out_dir), name.as_ref(), &generate())
+}
+
+fn write_image(out_dir: &Path, name: &Path, bytes: &[u8]) -> io::Result<PathBuf> {
+    let path = out_dir.join(name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_images_to_subdirectories() {
+        let out_dir = std::env::temp_dir().join(format!(
+            "embed-doc-image-core-{}-writes_images_to_subdirectories",
+            std::process::id()
+        ));
+        let result = write_image(&out_dir, Path::new("plots/sine.svg"), b"<svg/>");
+        let written = result.as_ref().ok().map(std::fs::read);
+        std::fs::remove_dir_all(&out_dir).unwrap();
+        assert_eq!(result.unwrap(), out_dir.join("plots").join("sine.svg"));
+        assert_eq!(written.unwrap().unwrap(), b"<svg/>");
+    }
+}
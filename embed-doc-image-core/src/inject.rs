@@ -0,0 +1,249 @@
+//! Embedding images in doc attributes from other proc macros.
+
+use crate::mime_type_for_extension;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An error that prevents an image from being embedded.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EmbedDocImageError {
+    /// The MIME type of the image could not be determined from the extension of its path.
+    UnrecognizedExtension { path: PathBuf },
+    /// The image could not be read.
+    Io { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for EmbedDocImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmbedDocImageError::UnrecognizedExtension { path } => write!(
+                f,
+                "Unrecognized image extension for {}, unable to infer correct MIME type",
+                path.display()
+            ),
+            EmbedDocImageError::Io { path, source } => {
+                write!(f, "Failed to load image at {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmbedDocImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmbedDocImageError::UnrecognizedExtension { .. } => None,
+            EmbedDocImageError::Io { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Embeds an image in the doc attributes of an item, making it available to the docs under the
+/// given label, e.g. as `![Alt text][label]`.
+///
+/// This appends the same doc attributes as `#[embed_doc_image(label, path)]`: a blank line, if
+/// needed to separate the image data from the docs, followed by a Markdown reference definition
+/// holding the `base64`-encoded image. As with the macros, a relative `path` is resolved against
+/// the root of the crate being compiled, i.e. `CARGO_MANIFEST_DIR`. The image is embedded as it
+/// is: unlike the macros, this does not strip metadata from PNG images or apply the
+/// `EMBED_DOC_IMAGE_*` settings, and it embeds the image in all builds, not only in `rustdoc`.
+///
+/// ```rust
+/// use embed_doc_image_core::inject_image_doc;
+/// use std::path::Path;
+///
+/// let mut attrs: Vec<syn::Attribute> = vec![syn::parse_quote!(#[doc = " ![Corro][corro]"])];
+/// let path = Path::new("../embed-doc-image-showcase/images/corro.svg");
+/// inject_image_doc(&mut attrs, "corro", path).unwrap();
+/// assert_eq!(attrs.len(), 3);
+/// ```
+pub fn inject_image_doc(
+    attrs: &mut Vec<syn::Attribute>,
+    label: &str,
+    path: &Path,
+) -> Result<(), EmbedDocImageError> {
+    let path = match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(root_dir) => Path::new(&root_dir).join(path),
+        None => path.to_path_buf(),
+    };
+    let mime = path
+        .extension()
+        .and_then(|ext| mime_type_for_extension(&ext.to_string_lossy()));
+    let Some(mime) = mime else {
+        return Err(EmbedDocImageError::UnrecognizedExtension { path });
+    };
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(source) => return Err(EmbedDocImageError::Io { path, source }),
+    };
+    let doc = format!(
+        " [{}]: data:{};base64,{}",
+        label,
+        mime,
+        base64::encode(bytes)
+    );
+    append_image_doc(attrs, &doc);
+    Ok(())
+}
+
+/// Appends a doc string holding image data, i.e. one or more Markdown reference definitions, to
+/// the doc attributes of an item.
+///
+/// A blank doc line is inserted first, unless the docs already end with one or with image data,
+/// since the Markdown parser only treats the definitions as such when they are separated from
+/// the preceding paragraph.
+pub fn append_image_doc(attrs: &mut Vec<syn::Attribute>, doc: &str) {
+    append_doc_separator(attrs);
+    attrs.push(syn::parse_quote! {
+        #[doc = #doc]
+    });
+}
+
+/// Appends a blank doc line to the doc attributes of an item if one is needed before appending
+/// image data, see [`needs_blank_doc_line`].
+///
+/// This is useful for proc macros that append image data in attributes other than plain `doc`
+/// attributes, e.g. in `#[cfg_attr(.., doc = "..")]`, which [`append_image_doc`] cannot produce.
+pub fn append_doc_separator(attrs: &mut Vec<syn::Attribute>) {
+    if needs_blank_doc_line(attrs) {
+        attrs.push(syn::parse_quote! {
+            #[doc = ""]
+        });
+    }
+}
+
+/// Determines whether a blank doc line must be inserted before appending image data to `attrs`.
+///
+/// This is not necessary if the last doc line is already blank, or if it is image data
+/// from a previous (stacked) invocation of `embed_doc_image`, in which case we are already
+/// inside the "bibliography". Avoiding redundant blank lines keeps the paragraph structure of
+/// the Markdown intact. The contents of doc attributes that are not string literals, such as
+/// `#[doc = include_str!("..")]`, are unknown, so a blank line is inserted after them.
+pub fn needs_blank_doc_line(attrs: &[syn::Attribute]) -> bool {
+    let last_doc = attrs
+        .iter()
+        .rev()
+        .find(|attr| attr.path.is_ident("doc"))
+        .and_then(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(s),
+                ..
+            })) => Some(s.value()),
+            _ => None,
+        });
+    match last_doc {
+        Some(doc) => {
+            let doc = doc.trim();
+            let is_image_data = doc.starts_with('[') && doc.contains("]: data:");
+            !(doc.is_empty() || is_image_data)
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc_attrs(docs: &[&str]) -> Vec<syn::Attribute> {
+        docs.iter()
+            .map(|doc| syn::parse_quote!(#[doc = #doc]))
+            .collect()
+    }
+
+    fn docs(attrs: &[syn::Attribute]) -> Vec<String> {
+        attrs
+            .iter()
+            .filter_map(|attr| match attr.parse_meta() {
+                Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                    lit: syn::Lit::Str(s),
+                    ..
+                })) => Some(s.value()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn separates_docs_from_image_data() {
+        assert!(needs_blank_doc_line(&[]));
+        assert!(needs_blank_doc_line(&doc_attrs(&[" Some docs."])));
+        assert!(!needs_blank_doc_line(&doc_attrs(&[" Some docs.", ""])));
+        assert!(!needs_blank_doc_line(&doc_attrs(&[
+            " Some docs.",
+            "",
+            " [a]: data:image/png;base64,cG5n",
+        ])));
+        // Only doc attributes count
+        let mut attrs = doc_attrs(&[" Some docs.", ""]);
+        attrs.push(syn::parse_quote!(#[inline]));
+        assert!(!needs_blank_doc_line(&attrs));
+    }
+
+    #[test]
+    fn separates_docs_of_unknown_content_from_image_data() {
+        let mut attrs = doc_attrs(&[" Some docs.", ""]);
+        attrs.push(syn::parse_quote!(#[doc = include_str!("README.md")]));
+        assert!(needs_blank_doc_line(&attrs));
+    }
+
+    #[test]
+    fn appends_image_data_after_a_single_blank_line() {
+        let mut attrs = doc_attrs(&[" ![A][a] ![B][b]"]);
+        append_image_doc(&mut attrs, " [a]: data:image/png;base64,YQ==");
+        append_image_doc(&mut attrs, " [b]: data:image/png;base64,Yg==");
+        assert_eq!(
+            docs(&attrs),
+            [
+                " ![A][a] ![B][b]",
+                "",
+                " [a]: data:image/png;base64,YQ==",
+                " [b]: data:image/png;base64,Yg==",
+            ]
+        );
+    }
+
+    #[test]
+    fn appends_a_separator_only_when_needed() {
+        let mut attrs = doc_attrs(&[" Some docs."]);
+        append_doc_separator(&mut attrs);
+        append_doc_separator(&mut attrs);
+        assert_eq!(docs(&attrs), [" Some docs.", ""]);
+    }
+
+    #[test]
+    fn injects_image_data() {
+        let path = std::env::temp_dir().join(format!(
+            "embed-doc-image-core-{}-injects_image_data.gif",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"GIF89a").unwrap();
+        let mut attrs = doc_attrs(&[" ![Image][image]"]);
+        let result = inject_image_doc(&mut attrs, "image", &path);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+        assert_eq!(
+            docs(&attrs),
+            [
+                " ![Image][image]",
+                "",
+                " [image]: data:image/gif;base64,R0lGODlh"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut attrs = Vec::new();
+        let err = inject_image_doc(&mut attrs, "a", Path::new("image.exe")).unwrap_err();
+        assert!(matches!(
+            err,
+            EmbedDocImageError::UnrecognizedExtension { .. }
+        ));
+        let err = inject_image_doc(&mut attrs, "a", Path::new("missing.png")).unwrap_err();
+        assert!(matches!(err, EmbedDocImageError::Io { .. }));
+        assert!(err.to_string().starts_with("Failed to load image at "));
+        assert!(attrs.is_empty());
+    }
+}
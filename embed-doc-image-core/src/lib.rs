@@ -0,0 +1,74 @@
+//! Functionality shared by [`embed-doc-image`](https://docs.rs/embed-doc-image) and proc macros
+//! that build on it.
+//!
+//! `embed-doc-image` is a proc macro crate, which cannot export anything but macros, so the
+//! parts that other crates may want to reuse live in this separate crate. With the
+//! `proc-macro-api` feature, [`inject_image_doc`] embeds an image in the doc attributes of an
+//! item, which allows derive macros and other proc macros to embed images in the documentation
+//! of the code they generate, just like `#[embed_doc_image]` does. Build scripts can use
+//! [`write_generated_image`] to generate images for `embed_image_from_out_dir!`.
+
+mod generate;
+#[cfg(feature = "proc-macro-api")]
+mod inject;
+
+pub use generate::write_generated_image;
+
+#[cfg(feature = "proc-macro-api")]
+pub use inject::{
+    EmbedDocImageError, append_doc_separator, append_image_doc, inject_image_doc,
+    needs_blank_doc_line,
+};
+
+/// Returns the MIME type for a file extension, ignoring case, or `None` if the extension is not
+/// recognized.
+///
+/// ```rust
+/// use embed_doc_image_core::mime_type_for_extension;
+///
+/// assert_eq!(mime_type_for_extension("PNG"), Some("image/png"));
+/// assert_eq!(mime_type_for_extension("exe"), None);
+/// ```
+pub fn mime_type_for_extension(extension: &str) -> Option<&'static str> {
+    let extension = extension.to_ascii_lowercase();
+
+    // TODO: Consider using the mime_guess crate? The below list does seem kinda exhaustive for
+    // doc purposes though?
+
+    // Matches taken haphazardly from
+    // https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types/Common_types
+    let mime = match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "gif" => "image/gif",
+        "tif" | "tiff" => "image/tiff",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/vnd.microsoft.icon",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_mime_types_from_extensions() {
+        assert_eq!(mime_type_for_extension("png"), Some("image/png"));
+        assert_eq!(mime_type_for_extension("JPG"), Some("image/jpeg"));
+        assert_eq!(mime_type_for_extension("svg"), Some("image/svg+xml"));
+        assert_eq!(mime_type_for_extension("exe"), None);
+        // Only dedicated macros embed these, with MIME types of their own
+        assert_eq!(mime_type_for_extension("json"), None);
+        assert_eq!(mime_type_for_extension("js"), None);
+        assert_eq!(mime_type_for_extension("pdf"), None);
+        assert_eq!(mime_type_for_extension("css"), None);
+        assert_eq!(mime_type_for_extension("woff2"), None);
+        assert_eq!(mime_type_for_extension("webm"), None);
+        assert_eq!(mime_type_for_extension(""), None);
+    }
+}
@@ -9,6 +9,7 @@ doc = ::embed_doc_image::embed_image!("ferris", "images/rustacean-orig-noshadow-
 doc = ::embed_doc_image::embed_image!("ferris-gesture", "images/rustacean-flat-gesture-tiny.png"),
 doc = ::embed_doc_image::embed_image!("dancing-ferris", "images/dancing-ferris-tiny.gif"),
 doc = ::embed_doc_image::embed_image!("corro", "images/corro.svg")))]
+#![cfg_attr(feature = "nightly", feature(decl_macro))]
 #![cfg_attr(
     not(feature = "doc-images"),
     doc = "**Doc images not enabled**. Compile with feature `doc-images` and Rust version >= 1.54 \
@@ -44,7 +45,10 @@ doc = ::embed_doc_image::embed_image!("corro", "images/corro.svg")))]
 //!
 //! ![Dancing Ferris][dancing-ferris]
 //!
-use embed_doc_image::embed_doc_image;
+use embed_doc_image::{
+    embed_doc_image, embed_doc_image_auto, embed_doc_images, embed_field_doc_images,
+    embed_image_figure_number as figure, embed_struct_field_images,
+};
 
 /// Test that images render in function docs.
 ///
@@ -88,6 +92,13 @@ macro_rules! macro_docs_work {
     () => {};
 }
 
+// Declarative macros 2.0 are unstable syntax, which is rejected even when configured away,
+// so they live in a separate module that is only loaded on nightly
+#[cfg(feature = "nightly")]
+mod nightly;
+#[cfg(feature = "nightly")]
+pub use nightly::macro2_docs_work;
+
 /// Test that images render in struct docs.
 ///
 /// ![Original Ferris][ferris] ![Ferris makes gesture][ferris-gesture]
@@ -101,6 +112,84 @@ macro_rules! macro_docs_work {
 #[embed_doc_image("corro", "images/corro.svg")]
 pub struct StructDocsWork {}
 
+/// Test that stacked attributes separate the docs from the images by a single blank line.
+///
+/// ![Original Ferris][ferris] ![Corro][corro] ![Dancing Ferris][dancing-ferris]
+#[embed_doc_image("ferris", "images/rustacean-orig-noshadow-tiny.png")]
+#[embed_doc_image("corro", "images/corro.svg")]
+#[embed_doc_image("dancing-ferris", "images/dancing-ferris-tiny.gif")]
+pub struct StackedDocsWork;
+
+/// Test that images render in the docs of structs with const generic parameters.
+///
+/// ![Original Ferris][ferris] ![Corro][corro]
+#[embed_doc_image("ferris", "images/rustacean-orig-noshadow-tiny.png")]
+#[embed_doc_image("corro", "images/corro.svg")]
+pub struct ConstGenericStructDocsWork<const N: usize, const M: usize> {
+    pub data: [[f64; M]; N],
+}
+
+/// Test that images render in the docs of structs with type parameters.
+///
+/// ![Original Ferris][ferris] ![Corro][corro]
+#[embed_doc_image("ferris", "images/rustacean-orig-noshadow-tiny.png")]
+#[embed_doc_image("corro", "images/corro.svg")]
+pub struct GenericStructDocsWork<T: Clone, const N: usize> {
+    pub data: [T; N],
+}
+
+/// Test that images declared on struct fields render in struct docs.
+///
+/// ![Original Ferris][field-ferris] ![Corro][field-corro]
+#[embed_field_doc_images]
+pub struct FieldImageDocsWork {
+    #[doc_image(
+        label = "field-ferris",
+        path = "images/rustacean-orig-noshadow-tiny.png"
+    )]
+    pub ferris: (),
+    #[doc_image(label = "field-corro", path = "images/corro.svg")]
+    pub corro: (),
+}
+
+/// Test that several images embedded with a single attribute render.
+///
+/// ![Original Ferris][batch-ferris] ![Corro][batch-corro]
+#[embed_doc_images(
+    ("batch-ferris", "images/rustacean-orig-noshadow-tiny.png"),
+    ("batch-corro", "images/corro.svg")
+)]
+pub fn batch_docs_work() {}
+
+/// Test that images render in the docs of struct fields.
+#[embed_struct_field_images]
+pub struct FieldDocsWork {
+    /// ![Original Ferris][ferris]
+    #[field_doc_image("ferris", "images/rustacean-orig-noshadow-tiny.png")]
+    pub ferris: (),
+    /// ![Corro][corro]
+    #[field_doc_image("corro", "images/corro.svg")]
+    pub corro: (),
+}
+
+/// Test that images found by label render in function docs.
+///
+/// ![Corro][corro] ![Dancing Ferris][dancing-ferris-tiny]
+#[embed_doc_image_auto("images")]
+pub fn auto_discovered_images_work() {}
+
+/// Test that numbered figures render in function docs.
+#[figure(
+    "figure-ferris",
+    "images/rustacean-orig-noshadow-tiny.png",
+    caption = "Ferris"
+)]
+pub fn figure_docs_work() {}
+
+/// Test that figures are numbered sequentially, see also [Figure 1](#figure-ferris).
+#[figure("figure-corro", "images/corro.svg", caption = "Corro")]
+pub fn second_figure_docs_work() {}
+
 /// Test that images render in trait docs.
 ///
 /// ![Original Ferris][ferris] ![Ferris makes gesture][ferris-gesture]
@@ -126,3 +215,63 @@ pub trait TraitDocsWork {}
 #[embed_doc_image("dancing-ferris", "images/dancing-ferris-tiny.gif")]
 #[embed_doc_image("corro", "images/corro.svg")]
 pub type TypeAliasDocsWork = f64;
+
+/// Test that images render in the docs of generic type aliases.
+///
+/// ![Original Ferris][ferris] ![Corro][corro]
+#[embed_doc_image("ferris", "images/rustacean-orig-noshadow-tiny.png")]
+#[embed_doc_image("corro", "images/corro.svg")]
+pub type GenericTypeAliasDocsWork<T> = Vec<Option<T>>;
+
+/// Test that `embed_doc_image` works on type aliases that are not public.
+///
+/// ![Corro][corro]
+#[embed_doc_image("corro", "images/corro.svg")]
+#[allow(dead_code)]
+pub(crate) type CrateTypeAliasDocsWork = f64;
+
+/// Test that other attributes of a type alias are preserved.
+///
+/// ![Corro][corro]
+#[embed_doc_image("corro", "images/corro.svg")]
+#[deprecated(note = "only here to test that attributes are preserved")]
+pub type DeprecatedTypeAliasDocsWork = f64;
+
+// If `embed_doc_image` dropped the `cfg` attribute, this would fail to compile
+#[embed_doc_image("corro", "images/corro.svg")]
+#[cfg(any())]
+pub type ConfiguredAwayTypeAlias = DoesNotExist;
+
+/// Test that images render in the docs of type aliases inside modules.
+pub mod type_aliases {
+    use embed_doc_image::embed_doc_image;
+
+    /// Test that images render in the docs of type aliases inside modules.
+    ///
+    /// ![Corro][corro]
+    #[embed_doc_image("corro", "images/corro.svg")]
+    pub type NestedTypeAliasDocsWork = f64;
+}
+
+/// Test that images embedded from a directory render.
+///
+/// ![Corro][showcase-0] ![Dancing Ferris][showcase-1]
+///
+/// ![Ferris makes gesture][showcase-2] ![Original Ferris][showcase-3]
+#[cfg_attr(
+    feature = "doc-images",
+    doc = ::embed_doc_image::embed_doc_images_from_dir!("images", prefix = "showcase")
+)]
+pub struct DirectoryDocsWork {}
+
+#[cfg(test)]
+mod tests {
+    use embed_doc_image::embed_doc_image;
+
+    /// Test that images can be embedded conditionally in test-only documentation.
+    ///
+    /// ![Corro][test-diagram]
+    #[cfg_attr(test, embed_doc_image("test-diagram", "images/corro.svg"))]
+    #[test]
+    fn cfg_attr_test_docs_work() {}
+}
@@ -0,0 +1,14 @@
+use embed_doc_image::embed_doc_image;
+
+/// Test that images render in declarative macro 2.0 docs.
+///
+/// ![Original Ferris][ferris] ![Ferris makes gesture][ferris-gesture]
+///
+/// Some more docs.
+///
+/// ![Corro][corro] ![Dancing Ferris][dancing-ferris]
+#[embed_doc_image("ferris", "images/rustacean-orig-noshadow-tiny.png")]
+#[embed_doc_image("ferris-gesture", "images/rustacean-flat-gesture-tiny.png")]
+#[embed_doc_image("dancing-ferris", "images/dancing-ferris-tiny.gif")]
+#[embed_doc_image("corro", "images/corro.svg")]
+pub macro macro2_docs_work() {}
@@ -0,0 +1,124 @@
+//! Checks that the data URIs produced by `embed-doc-image` decode to valid images.
+//!
+//! Doc attributes are not accessible at runtime, so we expand the macros as string constants
+//! instead. This verifies the same doc strings that end up in the documentation.
+
+use embed_doc_image::{
+    embed_doc_image_include_dir, embed_doc_image_include_dir_labels, embed_doc_images_from_dir,
+    embed_image, embed_image_svg_optimize, embed_image_table, embed_image_with_alt,
+};
+use embed_doc_image_test_helper::extract_embedded_images;
+
+/// Extracts the MIME type and decoded data of every `base64` data URI in a doc string.
+fn data_uris(doc: &str) -> Vec<(&str, Vec<u8>)> {
+    doc.split("data:")
+        .skip(1)
+        .map(|uri| {
+            let (mime, rest) = uri
+                .split_once(";base64,")
+                .expect("data URI is not base64-encoded");
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '+' || c == '/' || c == '='))
+                .unwrap_or(rest.len());
+            let data = base64::decode(&rest[..end]).expect("invalid base64 data");
+            (mime, data)
+        })
+        .collect()
+}
+
+/// Asserts that the doc string contains the expected number of data URIs, all of which are
+/// valid images.
+fn assert_valid_images(doc: &str, expected_count: usize) {
+    let uris = data_uris(doc);
+    assert_eq!(uris.len(), expected_count, "unexpected number of images");
+    for (mime, data) in uris {
+        match mime {
+            "image/svg+xml" => {
+                let svg = std::str::from_utf8(&data).expect("SVG is not valid UTF-8");
+                assert!(svg.contains("<svg"), "SVG has no <svg> element");
+            }
+            _ => {
+                let format = image::ImageFormat::from_mime_type(mime)
+                    .unwrap_or_else(|| panic!("unexpected MIME type {}", mime));
+                image::load_from_memory_with_format(&data, format)
+                    .unwrap_or_else(|err| panic!("invalid {} image: {}", mime, err));
+            }
+        }
+    }
+}
+
+#[test]
+fn embed_image_produces_valid_images() {
+    const PNG: &str = embed_image!("ferris", "images/rustacean-orig-noshadow-tiny.png");
+    const GIF: &str = embed_image!("dancing-ferris", "images/dancing-ferris-tiny.gif");
+    const SVG: &str = embed_image!("corro", "images/corro.svg");
+    for doc in [PNG, GIF, SVG] {
+        assert!(doc.trim_start().starts_with('['));
+        assert_valid_images(doc, 1);
+    }
+}
+
+#[test]
+fn embed_image_accepts_multiple_images() {
+    const DOC: &str = embed_image!(
+        "ferris", "images/rustacean-orig-noshadow-tiny.png";
+        "corro", "images/corro.svg";
+    );
+    let labels: Vec<_> = extract_embedded_images(DOC)
+        .into_iter()
+        .map(|info| info.label)
+        .collect();
+    assert_eq!(labels, ["ferris", "corro"]);
+    assert_valid_images(DOC, 2);
+}
+
+#[test]
+fn html_macros_produce_valid_images() {
+    const WITH_ALT: &str = embed_image_with_alt!(
+        "ferris",
+        "images/rustacean-flat-gesture-tiny.png",
+        alt = "Ferris making a gesture"
+    );
+    const TABLE: &str = embed_image_table!([
+        ("ferris", "images/rustacean-orig-noshadow-tiny.png"),
+        ("corro", "images/corro.svg")
+    ]);
+    assert_valid_images(WITH_ALT, 1);
+    assert_valid_images(TABLE, 2);
+}
+
+#[test]
+fn embed_doc_images_from_dir_produces_valid_images() {
+    const DIR: &str = embed_doc_images_from_dir!("images", prefix = "showcase");
+    assert_valid_images(DIR, 4);
+}
+
+#[test]
+fn embed_doc_image_include_dir_labels_images_by_file_name() {
+    const DIR: &str = embed_doc_image_include_dir!("images", label_prefix = "showcase");
+    const LABELS: &[&str] =
+        embed_doc_image_include_dir_labels!("images", label_prefix = "showcase");
+    assert_eq!(
+        LABELS,
+        [
+            "showcase-corro",
+            "showcase-dancing-ferris-tiny",
+            "showcase-rustacean-flat-gesture-tiny",
+            "showcase-rustacean-orig-noshadow-tiny"
+        ]
+    );
+    let embedded_labels: Vec<_> = extract_embedded_images(DIR)
+        .into_iter()
+        .map(|info| info.label)
+        .collect();
+    assert_eq!(embedded_labels, LABELS);
+    assert_valid_images(DIR, 4);
+}
+
+#[test]
+fn embed_image_svg_optimize_produces_smaller_valid_image() {
+    const ORIGINAL: &str = embed_image!("corro", "images/corro.svg");
+    const OPTIMIZED: &str = embed_image_svg_optimize!("corro", "images/corro.svg");
+    assert_valid_images(OPTIMIZED, 1);
+    assert!(OPTIMIZED.len() <= ORIGINAL.len());
+}
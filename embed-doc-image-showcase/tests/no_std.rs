@@ -0,0 +1,23 @@
+//! Checks that the code generated by `embed-doc-image` compiles in `no_std` crates.
+//!
+//! The test harness itself needs `std`, so it is linked explicitly, but nothing from it is in
+//! scope for the generated code.
+
+#![no_std]
+#![deny(clippy::std_instead_of_core, clippy::std_instead_of_alloc)]
+
+extern crate std;
+
+use embed_doc_image::{embed_doc_image, embed_image};
+
+/// ![Ferris][ferris]
+#[embed_doc_image("ferris", "images/rustacean-orig-noshadow-tiny.png")]
+pub struct Documented;
+
+const FERRIS: &str = embed_image!("ferris", "images/rustacean-orig-noshadow-tiny.png");
+
+#[test]
+fn macros_expand_without_std() {
+    assert!(FERRIS.contains("data:image/png;base64,"));
+    let _ = Documented;
+}
@@ -0,0 +1,70 @@
+//! Checks the doc attributes produced by stacked `#[embed_doc_image]` attributes.
+//!
+//! Doc attributes are not accessible at runtime, so we expand the showcase crate with
+//! `-Zunpretty=expanded` and inspect the attributes of `StackedDocsWork`. The expansion uses
+//! its own target directory, since `RUSTC_BOOTSTRAP` invalidates the regular build.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Expands the macros in the showcase crate and returns the expanded source.
+fn expand_showcase() -> String {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(env!("CARGO"))
+        .args(["rustc", "--quiet", "--lib", "--manifest-path"])
+        .arg(manifest_dir.join("Cargo.toml"))
+        .args(["--", "-Zunpretty=expanded"])
+        .env("RUSTC_BOOTSTRAP", "1")
+        .env("EMBED_DOC_IMAGE_ALWAYS_EMBED", "true")
+        .env("CARGO_TARGET_DIR", manifest_dir.join("../target/expand"))
+        .output()
+        .expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "failed to expand the showcase crate:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("expanded source is not UTF-8")
+}
+
+/// Returns the values of the doc attributes directly preceding the given item.
+fn doc_lines(source: &str, item: &str) -> Vec<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    let end = lines
+        .iter()
+        .position(|line| line.trim() == item)
+        .unwrap_or_else(|| panic!("`{}` not found in the expanded source", item));
+    // Long attributes are wrapped, with the string literal on the following line
+    let start = lines[..end]
+        .iter()
+        .rposition(|line| !(line.starts_with("#[doc") || line.starts_with('"')))
+        .map_or(0, |index| index + 1);
+    lines[start..end]
+        .join(" ")
+        .split("#[doc =")
+        .skip(1)
+        .map(|attr| {
+            let literal = attr.trim().trim_end_matches(']');
+            literal.trim_matches('"').to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn stacked_attributes_add_a_single_blank_line() {
+    let docs = doc_lines(&expand_showcase(), "pub struct StackedDocsWork;");
+    let is_image_data = |line: &String| line.starts_with(" [") && line.contains("]: data:");
+    let first_image = docs
+        .iter()
+        .position(is_image_data)
+        .expect("no image data in the docs");
+
+    assert_eq!(docs.len() - first_image, 3, "unexpected lines: {:#?}", docs);
+    assert!(docs[first_image..].iter().all(is_image_data));
+    assert_eq!(docs[first_image - 1], "", "no blank line before the images");
+    assert_ne!(
+        docs[first_image - 2],
+        "",
+        "more than one blank line before the images"
+    );
+}
@@ -0,0 +1,113 @@
+//! Utilities for testing crates that embed images in documentation with
+//! [`embed-doc-image`](https://docs.rs/embed-doc-image).
+//!
+//! `embed-doc-image` is a proc macro crate, which cannot export anything but macros, so these
+//! utilities live in this separate crate. Doc attributes are not accessible at runtime, but the
+//! macros that produce doc strings can also be expanded as string constants, which can then be
+//! inspected with the functions in this crate. Outside of `rustdoc`, `embed_image!` only embeds
+//! images if `EMBED_DOC_IMAGE_ALWAYS_EMBED` is set to `true`, e.g. in the `[env]` section of
+//! `.cargo/config.toml`. This also applies to doctests.
+//!
+//! ```rust
+//! use embed_doc_image::embed_image;
+//! use embed_doc_image_test_helper::{decode_embedded_image, extract_embedded_images};
+//!
+//! const DOC: &str = embed_image!("corro", "../embed-doc-image-showcase/images/corro.svg");
+//!
+//! let images = extract_embedded_images(DOC);
+//! assert_eq!(images.len(), 1);
+//! assert_eq!(images[0].label, "corro");
+//! assert_eq!(images[0].mime, "image/svg+xml");
+//! assert!(!decode_embedded_image(&images[0]).unwrap().is_empty());
+//! ```
+
+/// An image embedded in a doc string as a Markdown reference definition, i.e.
+/// `[label]: data:mime;base64,data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedImageInfo {
+    pub label: String,
+    pub mime: String,
+    pub base64_data: String,
+}
+
+/// Parses a single line of Markdown as a reference definition with a `base64` data URI.
+fn parse_definition(line: &str) -> Option<EmbeddedImageInfo> {
+    let rest = line.trim().strip_prefix('[')?;
+    let (label, rest) = rest.split_once("]:")?;
+    let rest = rest.trim_start().strip_prefix("data:")?;
+    let (mime, data) = rest.split_once(";base64,")?;
+    let data = data.split_whitespace().next().unwrap_or("");
+    Some(EmbeddedImageInfo {
+        label: label.to_string(),
+        mime: mime.to_string(),
+        base64_data: data.to_string(),
+    })
+}
+
+/// Extracts all images embedded in a doc string, in the order in which they appear.
+///
+/// Only images embedded as Markdown reference definitions are found, as produced by e.g.
+/// `embed_image!` and `#[embed_doc_image]`. Images embedded directly in HTML tags have no label
+/// and are skipped.
+pub fn extract_embedded_images(doc_string: &str) -> Vec<EmbeddedImageInfo> {
+    doc_string.lines().filter_map(parse_definition).collect()
+}
+
+/// Decodes the data of an embedded image.
+pub fn decode_embedded_image(info: &EmbeddedImageInfo) -> Result<Vec<u8>, base64::DecodeError> {
+    base64::decode(&info.base64_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(label: &str, mime: &str, base64_data: &str) -> EmbeddedImageInfo {
+        EmbeddedImageInfo {
+            label: label.to_string(),
+            mime: mime.to_string(),
+            base64_data: base64_data.to_string(),
+        }
+    }
+
+    #[test]
+    fn extracts_multiple_definitions_in_order() {
+        let doc = "![A][a] and ![B][b]\n \n [a]: data:image/png;base64,iVBO\n \
+                   [b]: data:image/svg+xml;base64,PHN2Zy8+\n [a]: data:image/png;base64,AAAA";
+        assert_eq!(
+            extract_embedded_images(doc),
+            vec![
+                info("a", "image/png", "iVBO"),
+                info("b", "image/svg+xml", "PHN2Zy8+"),
+                // Markdown uses the first definition of a label, but all of them are reported
+                info("a", "image/png", "AAAA"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_definitions_without_data_uris() {
+        let doc = "[docs]: https://docs.rs\n[local]: images/a.png\n[plain]: data:image/png,abc\n";
+        assert!(extract_embedded_images(doc).is_empty());
+    }
+
+    #[test]
+    fn keeps_escaped_brackets_in_labels() {
+        let doc = r"[a\]b]: data:image/png;base64,AAAA";
+        assert_eq!(
+            extract_embedded_images(doc),
+            vec![info(r"a\]b", "image/png", "AAAA")]
+        );
+    }
+
+    #[test]
+    fn fails_to_decode_malformed_base64() {
+        let images = extract_embedded_images("[a]: data:image/png;base64,A*==");
+        assert_eq!(images.len(), 1);
+        assert!(decode_embedded_image(&images[0]).is_err());
+        assert_eq!(
+            decode_embedded_image(&info("a", "image/png", "YWJj")).unwrap(),
+            b"abc"
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Rendering of simple bar and line charts as SVG.
+
+use crate::svg::escape;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChartKind {
+    Bar,
+    Line,
+}
+
+/// Options controlling the appearance of a chart.
+#[derive(Debug, Clone)]
+pub(crate) struct ChartOptions {
+    pub kind: ChartKind,
+    /// Fill color of bars, or stroke color of lines.
+    pub color: String,
+    pub x_label: Option<String>,
+    pub y_label: Option<String>,
+    /// Whether to draw horizontal grid lines at each tick.
+    pub grid: bool,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        ChartOptions {
+            kind: ChartKind::Bar,
+            color: "#4e79a7".to_string(),
+            x_label: None,
+            y_label: None,
+            grid: true,
+            width: 480,
+            height: 300,
+        }
+    }
+}
+
+const FONT_SIZE: f64 = 12.0;
+const MARGIN_LEFT: f64 = 64.0;
+const MARGIN_RIGHT: f64 = 16.0;
+const MARGIN_TOP: f64 = 16.0;
+const MARGIN_BOTTOM: f64 = 48.0;
+
+/// Returns a "nice" step size (1, 2 or 5 times a power of ten) for dividing `range` into
+/// roughly five intervals.
+fn tick_step(range: f64) -> f64 {
+    let raw = range / 5.0;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let normalized = raw / magnitude;
+    let nice = if normalized <= 1.0 {
+        1.0
+    } else if normalized <= 2.0 {
+        2.0
+    } else if normalized <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+/// Formats a tick value with just enough decimals for the given step size.
+fn format_tick(value: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+/// Renders the data as a bar or line chart, returning the SVG document.
+///
+/// The value axis always includes zero. Non-finite values are drawn as zero.
+pub(crate) fn render_chart(data: &[(&str, f64)], options: &ChartOptions) -> String {
+    let values: Vec<f64> = data
+        .iter()
+        .map(|(_, value)| if value.is_finite() { *value } else { 0.0 })
+        .collect();
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    let min = values.iter().cloned().fold(0.0, f64::min);
+    let step = if max > min { tick_step(max - min) } else { 1.0 };
+    let y_min = (min / step).floor() * step;
+    let y_max = ((max / step).ceil() * step).max(y_min + step);
+
+    let (width, height) = (f64::from(options.width), f64::from(options.height));
+    let plot_width = (width - MARGIN_LEFT - MARGIN_RIGHT).max(1.0);
+    let plot_height = (height - MARGIN_TOP - MARGIN_BOTTOM).max(1.0);
+    let x_axis = MARGIN_TOP + plot_height;
+    let y_of = |value: f64| x_axis - (value - y_min) / (y_max - y_min) * plot_height;
+    let band = plot_width / data.len().max(1) as f64;
+    let x_center = |index: usize| MARGIN_LEFT + band * (index as f64 + 0.5);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"{f}\">\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>",
+        w = options.width,
+        h = options.height,
+        f = FONT_SIZE
+    );
+
+    // Ticks and grid lines
+    let tick_count = ((y_max - y_min) / step).round() as usize;
+    for i in 0..=tick_count {
+        let value = y_min + step * i as f64;
+        let y = y_of(value);
+        if options.grid {
+            svg.push_str(&format!(
+                "<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"#ddd\"/>",
+                MARGIN_LEFT,
+                MARGIN_LEFT + plot_width,
+                y = y
+            ));
+        }
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" dominant-baseline=\"central\">\
+             {}</text>",
+            MARGIN_LEFT - 6.0,
+            y,
+            format_tick(value, step)
+        ));
+    }
+
+    // Data
+    match options.kind {
+        ChartKind::Bar => {
+            let bar_width = band * 0.7;
+            for (index, value) in values.iter().enumerate() {
+                let (top, bottom) = (y_of(value.max(0.0)), y_of(value.min(0.0)));
+                svg.push_str(&format!(
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+                     fill=\"{}\"/>",
+                    x_center(index) - bar_width / 2.0,
+                    top,
+                    bar_width,
+                    bottom - top,
+                    escape(&options.color)
+                ));
+            }
+        }
+        ChartKind::Line => {
+            let points: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| format!("{:.1},{:.1}", x_center(index), y_of(*value)))
+                .collect();
+            svg.push_str(&format!(
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+                points.join(" "),
+                escape(&options.color)
+            ));
+            for (index, value) in values.iter().enumerate() {
+                svg.push_str(&format!(
+                    "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"/>",
+                    x_center(index),
+                    y_of(*value),
+                    escape(&options.color)
+                ));
+            }
+        }
+    }
+
+    // Axes and labels
+    svg.push_str(&format!(
+        "<line x1=\"{l:.1}\" y1=\"{t:.1}\" x2=\"{l:.1}\" y2=\"{b:.1}\" stroke=\"black\"/>\
+         <line x1=\"{l:.1}\" y1=\"{z:.1}\" x2=\"{r:.1}\" y2=\"{z:.1}\" stroke=\"black\"/>",
+        l = MARGIN_LEFT,
+        r = MARGIN_LEFT + plot_width,
+        t = MARGIN_TOP,
+        b = x_axis,
+        z = y_of(0.0)
+    ));
+    for (index, (name, _)) in data.iter().enumerate() {
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            x_center(index),
+            x_axis + FONT_SIZE + 4.0,
+            escape(name)
+        ));
+    }
+    if let Some(x_label) = &options.x_label {
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            MARGIN_LEFT + plot_width / 2.0,
+            height - 6.0,
+            escape(x_label)
+        ));
+    }
+    if let Some(y_label) = &options.y_label {
+        let (x, y) = (FONT_SIZE, MARGIN_TOP + plot_height / 2.0);
+        svg.push_str(&format!(
+            "<text x=\"{x:.1}\" y=\"{y:.1}\" text-anchor=\"middle\" \
+             transform=\"rotate(-90 {x:.1} {y:.1})\">{}</text>",
+            escape(y_label),
+            x = x,
+            y = y
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tick_steps_are_nice() {
+        assert_eq!(tick_step(10.0), 2.0);
+        assert_eq!(tick_step(100.0), 20.0);
+        assert_eq!(tick_step(7.0), 2.0);
+        assert_eq!(tick_step(3.0), 1.0);
+        assert_eq!(tick_step(40.0), 10.0);
+        assert!((tick_step(0.5) - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn ticks_have_enough_decimals() {
+        assert_eq!(format_tick(20.0, 5.0), "20");
+        assert_eq!(format_tick(0.25, 0.05), "0.25");
+        assert_eq!(format_tick(0.3, 0.1), "0.3");
+    }
+
+    #[test]
+    fn bar_charts_have_one_bar_per_value() {
+        let data = [("a", 1.0), ("b", 3.0), ("c", 2.0)];
+        let svg = render_chart(&data, &ChartOptions::default());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"480\""));
+        assert!(svg.ends_with("</svg>"));
+        // The background and one rectangle per bar
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert_eq!(svg.matches("fill=\"#4e79a7\"").count(), 3);
+        for label in [">a<", ">b<", ">c<"] {
+            assert!(svg.contains(label), "missing label {}", label);
+        }
+    }
+
+    #[test]
+    fn line_charts_have_one_point_per_value() {
+        let options = ChartOptions {
+            kind: ChartKind::Line,
+            ..ChartOptions::default()
+        };
+        let svg = render_chart(&[("a", 1.0), ("b", 2.0)], &options);
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert_eq!(svg.matches("<circle").count(), 2);
+        // Only the background is a rectangle
+        assert_eq!(svg.matches("<rect").count(), 1);
+    }
+
+    #[test]
+    fn value_axis_includes_zero() {
+        let options = ChartOptions {
+            grid: false,
+            ..ChartOptions::default()
+        };
+        let svg = render_chart(&[("a", 5.0), ("b", 10.0)], &options);
+        assert!(svg.contains(">0</text>"));
+        assert!(svg.contains(">10</text>"));
+        let svg = render_chart(&[("a", -4.0), ("b", 2.0)], &options);
+        assert!(svg.contains(">-4</text>"));
+        assert!(svg.contains(">2</text>"));
+    }
+
+    #[test]
+    fn non_finite_values_are_drawn_as_zero() {
+        let data = [("a", f64::NAN), ("b", f64::INFINITY)];
+        let svg = render_chart(&data, &ChartOptions::default());
+        assert!(!svg.contains("NaN"));
+        assert!(!svg.contains("inf"));
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let options = ChartOptions {
+            color: "\"red".to_string(),
+            x_label: Some("x < y".to_string()),
+            y_label: Some("a & b".to_string()),
+            ..ChartOptions::default()
+        };
+        let svg = render_chart(&[("<script>", 1.0)], &options);
+        assert!(!svg.contains("<script>"));
+        assert!(svg.contains("&lt;script&gt;"));
+        assert!(svg.contains("x &lt; y"));
+        assert!(svg.contains("a &amp; b"));
+        assert!(svg.contains("fill=\"&quot;red\""));
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn charts_are_well_formed() {
+        let data = [("a", 1.5), ("b", -2.0), ("c", 0.0)];
+        for kind in [ChartKind::Bar, ChartKind::Line] {
+            let options = ChartOptions {
+                kind,
+                x_label: Some("x".to_string()),
+                y_label: Some("y".to_string()),
+                ..ChartOptions::default()
+            };
+            let svg = render_chart(&data, &options);
+            assert_eq!(crate::svg::validate(svg.as_bytes()), Ok(()));
+        }
+        let svg = render_chart(&[], &ChartOptions::default());
+        assert_eq!(crate::svg::validate(svg.as_bytes()), Ok(()));
+    }
+}
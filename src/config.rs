@@ -0,0 +1,267 @@
+//! Configuration through environment variables.
+//!
+//! Proc macros cannot read `Cargo.toml` metadata reliably, and hermetic build environments
+//! prefer environment variables anyway. The variables are read once per compiler process, when
+//! the configuration is first needed.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Environment variable holding an optional hard limit on the encoded size of images, in MiB.
+const HARD_LIMIT_MB_VAR: &str = "EMBED_DOC_IMAGE_HARD_LIMIT_MB";
+/// Environment variable holding an optional hard limit on the encoded size of images, in bytes.
+pub(crate) const MAX_BYTES_VAR: &str = "EMBED_DOC_IMAGE_MAX_BYTES";
+const SANITIZE_SVG_VAR: &str = "EMBED_DOC_IMAGE_SANITIZE_SVG";
+pub(crate) const LABEL_PATTERN_VAR: &str = "EMBED_DOC_IMAGE_LABEL_PATTERN";
+const STRIP_EXIF_VAR: &str = "EMBED_DOC_IMAGE_STRIP_EXIF";
+const STRIP_PNG_GAMMA_VAR: &str = "EMBED_DOC_IMAGE_STRIP_PNG_GAMMA";
+const JPEG_QUALITY_VAR: &str = "EMBED_DOC_IMAGE_JPEG_QUALITY";
+const PNG_LEVEL_VAR: &str = "EMBED_DOC_IMAGE_PNG_LEVEL";
+const DEBUG_VAR: &str = "EMBED_DOC_IMAGE_DEBUG";
+const SVG_PRECISION_VAR: &str = "EMBED_DOC_IMAGE_SVG_PRECISION";
+const ALWAYS_EMBED_VAR: &str = "EMBED_DOC_IMAGE_ALWAYS_EMBED";
+const PLANTUML_SERVER_VAR: &str = "EMBED_DOC_IMAGE_PLANTUML_SERVER";
+const PLANTUML_TIMEOUT_VAR: &str = "EMBED_DOC_IMAGE_PLANTUML_TIMEOUT";
+
+/// Settings that apply to all macros, see the crate documentation for details.
+#[derive(Debug, Clone)]
+pub(crate) struct EmbedDocImageConfig {
+    /// The hard limit on the encoded size of images in bytes, if any, and the variable that
+    /// set it.
+    pub hard_limit: Option<(u64, &'static str)>,
+    /// Whether to remove `<script>` elements from SVG images.
+    pub sanitize_svg: bool,
+    /// A pattern that all labels must match, where `*` matches any sequence of characters and
+    /// `?` matches any single character.
+    pub label_pattern: Option<String>,
+    /// Whether to strip metadata, including EXIF data, from PNG images.
+    pub strip_exif: bool,
+    /// Whether to strip gamma and color space information from PNG images.
+    pub strip_png_gamma: bool,
+    /// Quality of JPEG images encoded by the crate, from 1 to 100.
+    pub jpeg_quality: u8,
+    /// Compression level of PNG images encoded by the crate, from 0 (fastest) to 9 (smallest).
+    pub png_level: u8,
+    /// Whether `embed_image_debug_output!` writes the embedded images to disk.
+    pub debug: bool,
+    /// The number of decimal places to round the coordinates in SVG images to, if any.
+    pub svg_precision: Option<u8>,
+    /// Whether `embed_image!` and `#[embed_doc_image]` embed images in builds other than
+    /// documentation builds.
+    pub always_embed: bool,
+    /// The base URL of the PlantUML server used by `embed_image_from_plantuml!`.
+    pub plantuml_server: String,
+    /// How long `embed_image_from_plantuml!` waits for the PlantUML server.
+    pub plantuml_timeout: Duration,
+}
+
+impl Default for EmbedDocImageConfig {
+    fn default() -> Self {
+        EmbedDocImageConfig {
+            hard_limit: None,
+            sanitize_svg: false,
+            label_pattern: None,
+            strip_exif: true,
+            strip_png_gamma: false,
+            jpeg_quality: 90,
+            png_level: 6,
+            debug: false,
+            svg_precision: None,
+            always_embed: false,
+            plantuml_server: "https://www.plantuml.com/plantuml".to_string(),
+            plantuml_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Reads an environment variable, ignoring surrounding whitespace.
+fn var(name: &str) -> Option<String> {
+    std::env::var_os(name).map(|value| value.to_string_lossy().trim().to_string())
+}
+
+fn invalid(name: &str, value: &str, expected: &str) -> ! {
+    panic!(
+        "Invalid value `{}` for {}, expected {}",
+        value, name, expected
+    )
+}
+
+fn parse_bool(name: &str, value: &str) -> bool {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => true,
+        "0" | "false" | "no" | "off" => false,
+        _ => invalid(name, value, "`true` or `false`"),
+    }
+}
+
+fn parse_in_range(name: &str, value: &str, min: u8, max: u8) -> u8 {
+    value
+        .parse()
+        .ok()
+        .filter(|number| (min..=max).contains(number))
+        .unwrap_or_else(|| invalid(name, value, &format!("a number from {} to {}", min, max)))
+}
+
+impl EmbedDocImageConfig {
+    /// Returns the configuration given by the environment variables of this process.
+    pub(crate) fn from_env() -> &'static EmbedDocImageConfig {
+        static CONFIG: OnceLock<EmbedDocImageConfig> = OnceLock::new();
+        CONFIG.get_or_init(Self::read_env)
+    }
+
+    fn read_env() -> EmbedDocImageConfig {
+        let mut config = EmbedDocImageConfig::default();
+        let hard_limit_mb = var(HARD_LIMIT_MB_VAR).map(|value| {
+            let mib: f64 = value
+                .parse()
+                .ok()
+                .filter(|mib: &f64| *mib >= 0.0)
+                .unwrap_or_else(|| invalid(HARD_LIMIT_MB_VAR, &value, "a non-negative number"));
+            ((mib * 1024.0 * 1024.0) as u64, HARD_LIMIT_MB_VAR)
+        });
+        let max_bytes = var(MAX_BYTES_VAR).map(|value| {
+            let bytes = value
+                .parse()
+                .unwrap_or_else(|_| invalid(MAX_BYTES_VAR, &value, "a number of bytes"));
+            (bytes, MAX_BYTES_VAR)
+        });
+        // If both limits are given, the stricter one applies
+        config.hard_limit = match (hard_limit_mb, max_bytes) {
+            (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+            (a, b) => a.or(b),
+        };
+        if let Some(value) = var(SANITIZE_SVG_VAR) {
+            config.sanitize_svg = parse_bool(SANITIZE_SVG_VAR, &value);
+        }
+        config.label_pattern = var(LABEL_PATTERN_VAR).filter(|pattern| !pattern.is_empty());
+        if let Some(value) = var(STRIP_EXIF_VAR) {
+            config.strip_exif = parse_bool(STRIP_EXIF_VAR, &value);
+        }
+        if let Some(value) = var(STRIP_PNG_GAMMA_VAR) {
+            config.strip_png_gamma = parse_bool(STRIP_PNG_GAMMA_VAR, &value);
+        }
+        if let Some(value) = var(JPEG_QUALITY_VAR) {
+            config.jpeg_quality = parse_in_range(JPEG_QUALITY_VAR, &value, 1, 100);
+        }
+        if let Some(value) = var(PNG_LEVEL_VAR) {
+            config.png_level = parse_in_range(PNG_LEVEL_VAR, &value, 0, 9);
+        }
+        if let Some(value) = var(DEBUG_VAR) {
+            config.debug = parse_bool(DEBUG_VAR, &value);
+        }
+        config.svg_precision =
+            var(SVG_PRECISION_VAR).map(|value| parse_in_range(SVG_PRECISION_VAR, &value, 0, 10));
+        if let Some(value) = var(ALWAYS_EMBED_VAR) {
+            config.always_embed = parse_bool(ALWAYS_EMBED_VAR, &value);
+        }
+        if let Some(value) = var(PLANTUML_SERVER_VAR).filter(|value| !value.is_empty()) {
+            config.plantuml_server = value.trim_end_matches('/').to_string();
+        }
+        if let Some(value) = var(PLANTUML_TIMEOUT_VAR) {
+            let seconds = value
+                .parse()
+                .ok()
+                .filter(|seconds: &f64| seconds.is_finite() && *seconds > 0.0)
+                .unwrap_or_else(|| {
+                    invalid(PLANTUML_TIMEOUT_VAR, &value, "a positive number of seconds")
+                });
+            config.plantuml_timeout = Duration::from_secs_f64(seconds);
+        }
+        config
+    }
+
+    /// Whether the label matches the label pattern, if any.
+    pub(crate) fn label_matches(&self, label: &str) -> bool {
+        match &self.label_pattern {
+            Some(pattern) => {
+                let pattern: Vec<char> = pattern.chars().collect();
+                let label: Vec<char> = label.chars().collect();
+                wildcard_match(&pattern, &label)
+            }
+            None => true,
+        }
+    }
+}
+
+/// Matches text against a pattern in which `*` matches any sequence of characters and `?` any
+/// single character.
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && wildcard_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && wildcard_match(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_booleans() {
+        for value in ["1", "true", "TRUE", "yes", "on"] {
+            assert!(parse_bool("VAR", value));
+        }
+        for value in ["0", "false", "No", "off"] {
+            assert!(!parse_bool("VAR", value));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid value `maybe` for VAR, expected `true` or `false`")]
+    fn rejects_invalid_booleans() {
+        parse_bool("VAR", "maybe");
+    }
+
+    #[test]
+    fn parses_numbers_in_range() {
+        assert_eq!(parse_in_range("VAR", "0", 0, 9), 0);
+        assert_eq!(parse_in_range("VAR", "9", 0, 9), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid value `10` for VAR, expected a number from 0 to 9")]
+    fn rejects_numbers_out_of_range() {
+        parse_in_range("VAR", "10", 0, 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid value `-1` for VAR")]
+    fn rejects_negative_numbers() {
+        parse_in_range("VAR", "-1", 0, 9);
+    }
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        wildcard_match(&pattern, &text)
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("fig-*", "fig-1"));
+        assert!(matches("fig-*", "fig-"));
+        assert!(matches("*-logo", "ferris-logo"));
+        assert!(matches("fig-?", "fig-a"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(matches("ß?", "ßü"));
+        assert!(!matches("fig-?", "fig-10"));
+        assert!(!matches("fig-*", "figure-1"));
+        assert!(!matches("", "a"));
+        assert!(!matches("a*b", "ac"));
+    }
+
+    #[test]
+    fn labels_match_without_pattern() {
+        let config = EmbedDocImageConfig::default();
+        assert!(config.label_matches("anything"));
+        let config = EmbedDocImageConfig {
+            label_pattern: Some("fig-*".to_string()),
+            ..EmbedDocImageConfig::default()
+        };
+        assert!(config.label_matches("fig-1"));
+        assert!(!config.label_matches("ferris"));
+    }
+}
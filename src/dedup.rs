@@ -0,0 +1,181 @@
+//! Reuse of the `base64` encoding of images that are embedded more than once.
+//!
+//! The same image (or identical copies of it) is often embedded under several labels, e.g. in
+//! the docs of several items. The encoded data still appears once per label in the output, but
+//! we only encode it once per compiler process. Large images are not cached, see
+//! [`MAX_CACHED_LEN`], and the cache as a whole is bounded by [`MAX_CACHE_SIZE`].
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// Images are looked up by their size and CRC32 checksum, which quickly rules out images that
+/// differ. A match is confirmed by comparing the images byte by byte.
+type Key = (u64, u32);
+
+/// Size in bytes above which images are encoded without being cached.
+///
+/// Keeping the encoding of a large image around for the rest of the compilation costs more
+/// memory than encoding it again would cost time, and large images are best streamed from disk
+/// instead of being read in full.
+pub(crate) const MAX_CACHED_LEN: u64 = 1024 * 1024;
+
+/// Total size in bytes of the images and encodings kept in the cache.
+///
+/// The compiler process may be long-lived, e.g. rust-analyzer's proc macro server, which expands
+/// the macros of every crate in the workspace, so the oldest entries are evicted to stay below
+/// this size.
+const MAX_CACHE_SIZE: usize = 32 * 1024 * 1024;
+
+struct Entry {
+    bytes: Vec<u8>,
+    encoded: String,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        self.bytes.len() + self.encoded.len()
+    }
+}
+
+struct Cache {
+    entries: HashMap<Key, Entry>,
+    /// Keys in the order they were inserted, oldest first.
+    order: VecDeque<Key>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Cache {
+    fn new(max_size: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            size: 0,
+            max_size,
+        }
+    }
+
+    fn get(&self, key: &Key, bytes: &[u8]) -> Option<&str> {
+        self.entries
+            .get(key)
+            .filter(|entry| entry.bytes == bytes)
+            .map(|entry| entry.encoded.as_str())
+    }
+
+    fn insert(&mut self, key: Key, entry: Entry) {
+        // On a checksum collision, keep the image that was cached first
+        if self.entries.contains_key(&key) || entry.size() > self.max_size {
+            return;
+        }
+        while self.size + entry.size() > self.max_size {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.size -= evicted.size();
+            }
+        }
+        self.size += entry.size();
+        self.order.push_back(key);
+        self.entries.insert(key, entry);
+    }
+}
+
+fn cache() -> MutexGuard<'static, Cache> {
+    static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(Cache::new(MAX_CACHE_SIZE)));
+    // Entries are inserted and evicted along with their size in a single step, so the cache
+    // remains consistent even if a macro panicked
+    cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Encodes the image as `base64`, reusing a previous encoding of identical content.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    if bytes.len() as u64 > MAX_CACHED_LEN {
+        return base64::encode(bytes);
+    }
+    let key = (bytes.len() as u64, crc32fast::hash(bytes));
+    if let Some(encoded) = cache().get(&key, bytes) {
+        return encoded.to_string();
+    }
+    let encoded = base64::encode(bytes);
+    cache().insert(
+        key,
+        Entry {
+            bytes: bytes.to_vec(),
+            encoded: encoded.clone(),
+        },
+    );
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(bytes: &[u8]) -> Key {
+        (bytes.len() as u64, crc32fast::hash(bytes))
+    }
+
+    fn entry(bytes: &[u8], encoded: &str) -> Entry {
+        Entry {
+            bytes: bytes.to_vec(),
+            encoded: encoded.to_string(),
+        }
+    }
+
+    #[test]
+    fn encodes_identical_images_once() {
+        // The cache is shared by all tests, so use content that no other test encodes
+        let image = b"dedup::tests::encodes_identical_images_once".repeat(3);
+        let encoded = encode(&image);
+        assert_eq!(encoded, base64::encode(&image));
+        assert_eq!(cache().get(&key(&image), &image), Some(encoded.as_str()));
+        assert_eq!(encode(&image.clone()), encoded);
+    }
+
+    #[test]
+    fn distinguishes_images_of_the_same_size() {
+        let a = b"dedup::tests::distinguishes::aaaaaa";
+        let b = b"dedup::tests::distinguishes::bbbbbb";
+        assert_eq!(encode(a), base64::encode(a));
+        assert_eq!(encode(b), base64::encode(b));
+    }
+
+    #[test]
+    fn confirms_matches_byte_by_byte() {
+        // Pretend that another image with the same size and checksum was cached before
+        let image = b"dedup::tests::confirms_matches_byte_by_byte";
+        let other = b"dedup::tests::confirms_matches_byte_by_BYTE";
+        cache().insert(key(image), entry(other, "other"));
+        assert_eq!(encode(image), base64::encode(image));
+    }
+
+    #[test]
+    fn does_not_cache_large_images() {
+        let image = vec![b'd'; MAX_CACHED_LEN as usize + 1];
+        assert_eq!(encode(&image), base64::encode(&image));
+        assert!(cache().get(&key(&image), &image).is_none());
+    }
+
+    #[test]
+    fn evicts_the_oldest_entries() {
+        let mut cache = Cache::new(24);
+        cache.insert(key(b"a"), entry(b"a", "YQ=="));
+        cache.insert(key(b"bb"), entry(b"bb", "YmI="));
+        cache.insert(key(b"ccc"), entry(b"ccc", "Y2Nj"));
+        assert_eq!(cache.size, 18);
+        cache.insert(key(b"dddd"), entry(b"dddd", "ZGRkZA=="));
+        assert_eq!(cache.size, 19);
+        assert!(cache.get(&key(b"a"), b"a").is_none());
+        assert!(cache.get(&key(b"bb"), b"bb").is_none());
+        assert_eq!(cache.get(&key(b"ccc"), b"ccc"), Some("Y2Nj"));
+        assert_eq!(cache.get(&key(b"dddd"), b"dddd"), Some("ZGRkZA=="));
+        // Entries larger than the cache are not cached at all
+        let large = [b'e'; 21];
+        cache.insert(key(&large), entry(&large, "ZWVl"));
+        assert_eq!(cache.size, 19);
+    }
+}
@@ -0,0 +1,329 @@
+//! Determining the pixel dimensions of images from their headers.
+//!
+//! We only need the dimensions, so rather than decoding images we read just enough of each
+//! format's header to find them.
+
+use crate::png;
+
+fn be_u16(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 2)?;
+    Some(u32::from(u16::from_be_bytes([b[0], b[1]])))
+}
+
+fn le_u16(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 2)?;
+    Some(u32::from(u16::from_le_bytes([b[0], b[1]])))
+}
+
+fn be_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn le_u24(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 3)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], 0]))
+}
+
+fn le_i32(bytes: &[u8], offset: usize) -> Option<i32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn png_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !png::is_png(bytes) || bytes.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some((be_u32(bytes, 16)?, be_u32(bytes, 20)?))
+}
+
+fn gif_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !bytes.starts_with(b"GIF87a") && !bytes.starts_with(b"GIF89a") {
+        return None;
+    }
+    Some((le_u16(bytes, 6)?, le_u16(bytes, 8)?))
+}
+
+fn bmp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !bytes.starts_with(b"BM") {
+        return None;
+    }
+    // The height is negative for top-down bitmaps
+    let width = le_i32(bytes, 18)?.unsigned_abs();
+    let height = le_i32(bytes, 22)?.unsigned_abs();
+    Some((width, height))
+}
+
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !bytes.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+    // Walk the markers until we find a start-of-frame marker
+    let mut offset = 2;
+    loop {
+        if *bytes.get(offset)? != 0xff {
+            return None;
+        }
+        let marker = *bytes.get(offset + 1)?;
+        match marker {
+            // Padding
+            0xff => offset += 1,
+            // Markers without a payload
+            0x01 | 0xd0..=0xd7 => offset += 2,
+            // Start of frame (excluding DHT, JPG and DAC, which share the range)
+            0xc0..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                let height = be_u16(bytes, offset + 5)?;
+                let width = be_u16(bytes, offset + 7)?;
+                return Some((width, height));
+            }
+            _ => offset += 2 + be_u16(bytes, offset + 2)? as usize,
+        }
+    }
+}
+
+fn webp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    match bytes.get(12..16)? {
+        b"VP8 " => {
+            let width = le_u16(bytes, 26)? & 0x3fff;
+            let height = le_u16(bytes, 28)? & 0x3fff;
+            Some((width, height))
+        }
+        b"VP8L" => {
+            let b = bytes.get(21..25)?;
+            let bits = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+        }
+        b"VP8X" => Some((le_u24(bytes, 24)? + 1, le_u24(bytes, 27)? + 1)),
+        _ => None,
+    }
+}
+
+fn ico_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !bytes.starts_with(&[0, 0, 1, 0]) || le_u16(bytes, 4)? == 0 {
+        return None;
+    }
+    // Report the first icon in the file. A stored size of 0 means 256 pixels.
+    let size = |b: u8| if b == 0 { 256 } else { u32::from(b) };
+    Some((size(*bytes.get(6)?), size(*bytes.get(7)?)))
+}
+
+/// Extracts the value of an attribute from the root `<svg>` element.
+fn svg_root_attribute<'a>(svg: &'a str, name: &str) -> Option<&'a str> {
+    let start = svg.find("<svg")?;
+    let end = start + svg[start..].find('>')?;
+    let tag = &svg[start..end];
+    let pattern = format!(" {}=", name);
+    let value_start = tag
+        .find(&pattern)
+        .or_else(|| tag.find(&format!("\n{}=", name)))?
+        + pattern.len();
+    let quote = tag[value_start..].chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let value = &tag[value_start + 1..];
+    Some(&value[..value.find(quote)?])
+}
+
+/// Parses an SVG length in pixels, e.g. `120` or `120px`. Relative units are not supported.
+fn parse_svg_length(length: &str) -> Option<u32> {
+    let length = length.trim();
+    let number = length.strip_suffix("px").unwrap_or(length);
+    let value: f64 = number.parse().ok()?;
+    if value.is_finite() && value >= 0.0 {
+        Some(value.round() as u32)
+    } else {
+        None
+    }
+}
+
+fn svg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let svg = std::str::from_utf8(bytes).ok()?;
+    let width = svg_root_attribute(svg, "width").and_then(parse_svg_length);
+    let height = svg_root_attribute(svg, "height").and_then(parse_svg_length);
+    if let (Some(width), Some(height)) = (width, height) {
+        return Some((width, height));
+    }
+
+    // Fall back to the size of the view box
+    let view_box: Vec<f64> = svg_root_attribute(svg, "viewBox")?
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match view_box.as_slice() {
+        [_, _, width, height] if *width >= 0.0 && *height >= 0.0 => {
+            Some((width.round() as u32, height.round() as u32))
+        }
+        _ => None,
+    }
+}
+
+/// Determines the width and height of an image in pixels.
+///
+/// Returns `None` if the format is not supported or the dimensions could not be determined,
+/// e.g. for SVG files with relative sizes.
+pub(crate) fn image_dimensions(mime: &str, bytes: &[u8]) -> Option<(u32, u32)> {
+    match mime {
+        "image/png" => png_dimensions(bytes),
+        "image/gif" => gif_dimensions(bytes),
+        "image/bmp" => bmp_dimensions(bytes),
+        "image/jpeg" => jpeg_dimensions(bytes),
+        "image/webp" => webp_dimensions(bytes),
+        "image/vnd.microsoft.icon" => ico_dimensions(bytes),
+        "image/svg+xml" => svg_dimensions(bytes),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png() -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 1, 44, 0, 0, 0, 200]);
+        png
+    }
+
+    fn jpeg() -> Vec<u8> {
+        let mut jpeg = vec![0xff, 0xd8];
+        // An APP0 segment, padding and a DHT segment before the start of frame
+        jpeg.extend_from_slice(&[0xff, 0xe0, 0, 4, 0, 0, 0xff, 0xff]);
+        jpeg.extend_from_slice(&[0xff, 0xc4, 0, 2]);
+        jpeg.extend_from_slice(&[0xff, 0xc0, 0, 11, 8, 0, 200, 1, 44]);
+        jpeg
+    }
+
+    /// The headers of images of 300 by 200 pixels in every supported format, with their MIME
+    /// types. Each header ends right after the dimensions.
+    fn images() -> Vec<(&'static str, Vec<u8>)> {
+        let mut bmp = b"BM".to_vec();
+        bmp.resize(18, 0);
+        bmp.extend_from_slice(&300i32.to_le_bytes());
+        bmp.extend_from_slice(&(-200i32).to_le_bytes());
+
+        let mut vp8x = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+        vp8x.extend_from_slice(&[43, 1, 0, 199, 0, 0]);
+        let mut vp8l = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2f".to_vec();
+        vp8l.extend_from_slice(&(299u32 | (199 << 14)).to_le_bytes());
+        let mut vp8 = b"RIFF\0\0\0\0WEBPVP8 \0\0\0\0\0\0\0\x9d\x01\x2a".to_vec();
+        vp8.extend_from_slice(&[44, 1, 200, 0]);
+
+        vec![
+            ("image/png", png()),
+            ("image/gif", b"GIF89a\x2c\x01\xc8\x00".to_vec()),
+            ("image/bmp", bmp),
+            ("image/jpeg", jpeg()),
+            ("image/webp", vp8x),
+            ("image/webp", vp8l),
+            ("image/webp", vp8),
+        ]
+    }
+
+    #[test]
+    fn reads_dimensions_from_headers() {
+        for (mime, bytes) in images() {
+            assert_eq!(
+                image_dimensions(mime, &bytes),
+                Some((300, 200)),
+                "{} {:?}",
+                mime,
+                bytes
+            );
+        }
+        assert_eq!(
+            image_dimensions("image/vnd.microsoft.icon", b"\0\0\x01\0\x01\0\x20\0"),
+            Some((32, 256))
+        );
+    }
+
+    #[test]
+    fn truncated_headers_have_no_dimensions() {
+        for (mime, bytes) in images() {
+            for len in 0..bytes.len() {
+                assert_eq!(
+                    image_dimensions(mime, &bytes[..len]),
+                    None,
+                    "{} truncated to {} bytes",
+                    mime,
+                    len
+                );
+            }
+        }
+        let ico = b"\0\0\x01\0\x01\0\x20\0";
+        for len in 0..ico.len() {
+            assert_eq!(
+                image_dimensions("image/vnd.microsoft.icon", &ico[..len]),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_headers_have_no_dimensions() {
+        // Wrong signatures
+        for (mime, mut bytes) in images() {
+            bytes[1] ^= 0xff;
+            assert_eq!(image_dimensions(mime, &bytes), None, "{}", mime);
+        }
+        // A PNG file that does not start with `IHDR`
+        let mut png = png();
+        png[12..16].copy_from_slice(b"IDAT");
+        assert_eq!(image_dimensions("image/png", &png), None);
+        // Segment lengths that point past the end of a JPEG file, or into the middle of one
+        let mut jpeg = jpeg();
+        jpeg[4..6].copy_from_slice(&[0xff, 0xff]);
+        assert_eq!(image_dimensions("image/jpeg", &jpeg), None);
+        let mut jpeg = self::jpeg();
+        jpeg[5] = 3;
+        assert_eq!(image_dimensions("image/jpeg", &jpeg), None);
+        // An unknown WebP chunk, and an icon file without icons
+        let webp = b"RIFF\0\0\0\0WEBPALPH\0\0\0\0\0\0\0\0\0\0\0\0";
+        assert_eq!(image_dimensions("image/webp", webp), None);
+        assert_eq!(
+            image_dimensions("image/vnd.microsoft.icon", b"\0\0\x01\0\0\0\x20\0"),
+            None
+        );
+        assert_eq!(image_dimensions("image/tiff", b"II*\0"), None);
+    }
+
+    #[test]
+    fn reads_svg_dimensions() {
+        let svg = |svg: &str| image_dimensions("image/svg+xml", svg.as_bytes());
+        assert_eq!(svg(r#"<svg width="300" height='200px'>"#), Some((300, 200)));
+        assert_eq!(
+            svg("<?xml version=\"1.0\"?>\n<svg\nwidth=\"10.6\" height=\"20\"/>"),
+            Some((11, 20))
+        );
+        assert_eq!(svg(r#"<svg viewBox="0 0 300,200">"#), Some((300, 200)));
+        assert_eq!(
+            svg(r#"<svg width="100%" height="100%" viewBox="0 0 30 20">"#),
+            Some((30, 20))
+        );
+        assert_eq!(
+            svg(r#"<svg stroke-width="2" width="3em" viewBox="0 0 30 20">"#),
+            Some((30, 20))
+        );
+    }
+
+    #[test]
+    fn malformed_svgs_have_no_dimensions() {
+        let svg = |svg: &str| image_dimensions("image/svg+xml", svg.as_bytes());
+        assert_eq!(svg(r#"<svg width="100%" height="100%">"#), None);
+        assert_eq!(svg(r#"<svg viewBox="0 0 -1 20">"#), None);
+        assert_eq!(svg(r#"<svg viewBox="0 0 30">"#), None);
+        assert_eq!(svg(r#"<svg width=300 height=200>"#), None);
+        assert_eq!(svg(r#"<svg width="300" height="200"#), None);
+        assert_eq!(svg(r#"<svg width="300"#), None);
+        assert_eq!(svg(r#"<svg width="NaN" height="inf">"#), None);
+        assert_eq!(
+            image_dimensions("image/svg+xml", b"<svg width=\"\xff\">"),
+            None
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Fetching remote images at build time, with a simple file-based cache.
+
+use crate::{magic, tools};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// An environment variable which is mixed into the cache key, so that setting it to a new value
+/// (e.g. the current date in CI) forces all remote images to be fetched anew.
+pub(crate) const CACHE_EPOCH_VAR: &str = "EMBED_DOC_IMAGE_FETCH_EPOCH";
+
+/// Parses a duration such as `30s`, `15m`, `1h` or `7d`.
+pub(crate) fn parse_duration(duration: &str) -> Option<Duration> {
+    let duration = duration.trim();
+    let unit_start = duration.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = duration.split_at(unit_start);
+    let amount: u64 = amount.parse().ok()?;
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(amount.checked_mul(seconds_per_unit)?))
+}
+
+/// Returns the directory in which fetched images are cached.
+fn cache_dir(manifest_dir: &Path) -> PathBuf {
+    tools::target_dir(manifest_dir)
+        .join("embed-doc-image")
+        .join("fetch")
+}
+
+fn cache_key(url: &str, ttl: Duration) -> String {
+    // Round the TTL to whole hours, so that e.g. `60m` and `1h` share cache entries
+    let ttl_hours = (ttl.as_secs() + 30 * 60) / (60 * 60);
+    let epoch = std::env::var_os(CACHE_EPOCH_VAR).unwrap_or_default();
+    crate::sha256_hex(format!("{}\n{}\n{}", url, ttl_hours, epoch.to_string_lossy()).as_bytes())
+}
+
+fn is_fresh(path: &Path, ttl: Duration) -> bool {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .filter(|age| *age < ttl)
+        .is_some()
+}
+
+/// Determines the MIME type of an image downloaded from `url`, for which the server reported
+/// the MIME type `reported`.
+///
+/// The MIME type ends up in a data URI, so we do not take the server's word for it: if the
+/// format of the image is recognized from its contents, that decides. Otherwise the reported
+/// MIME type must be a well-formed `image/*` type. Anything else, such as an HTML error page
+/// served with status 200, is rejected.
+fn checked_mime_type(url: &str, reported: &str, bytes: &[u8]) -> Result<String, String> {
+    if let Some(sniffed) = magic::sniff_mime_type(bytes) {
+        if sniffed.starts_with("image/") {
+            return Ok(sniffed.to_string());
+        }
+        return Err(format!(
+            "{} is not an image: the server reported `{}`, but the content is `{}`",
+            url, reported, sniffed
+        ));
+    }
+    let is_token = |part: &str| {
+        !part.is_empty()
+            && part
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$&^_.+-".contains(&b))
+    };
+    match reported.split_once('/') {
+        Some(("image", subtype)) if is_token(subtype) => Ok(reported.to_string()),
+        _ if reported.is_empty() => Err(format!(
+            "No content type reported for {}, and the image format is not recognized",
+            url
+        )),
+        _ => Err(format!(
+            "{} is not an image: the server reported `{}`",
+            url, reported
+        )),
+    }
+}
+
+/// Fetches the image at `url` with `curl`, or reads it from the cache if it was fetched less
+/// than `ttl` ago. Returns the MIME type of the image, see [`checked_mime_type`], along with the
+/// image data.
+pub(crate) fn fetch_cached(
+    manifest_dir: &Path,
+    url: &str,
+    ttl: Duration,
+) -> Result<(String, Vec<u8>), String> {
+    let dir = cache_dir(manifest_dir);
+    let key = cache_key(url, ttl);
+    let data_path = dir.join(&key);
+    let mime_path = dir.join(format!("{}.mime", key));
+
+    if !is_fresh(&data_path, ttl) || !mime_path.is_file() {
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+        let download_path = dir.join(format!("{}.download", key));
+        let content_type = tools::run(
+            "curl",
+            [
+                OsStr::new("--silent"),
+                OsStr::new("--show-error"),
+                OsStr::new("--fail"),
+                OsStr::new("--location"),
+                OsStr::new("--output"),
+                download_path.as_os_str(),
+                OsStr::new("--write-out"),
+                OsStr::new("%{content_type}"),
+                OsStr::new(url),
+            ],
+        )?;
+        // Strip parameters such as `; charset=utf-8`
+        let content_type = String::from_utf8_lossy(&content_type);
+        let reported = content_type.split(';').next().unwrap_or("").trim();
+        let checked = std::fs::read(&download_path)
+            .map_err(|err| format!("Failed to read {}: {}", download_path.display(), err))
+            .and_then(|bytes| checked_mime_type(url, reported, &bytes));
+        let mime = match checked {
+            Ok(mime) => mime,
+            Err(err) => {
+                let _ = std::fs::remove_file(&download_path);
+                return Err(err);
+            }
+        };
+        std::fs::write(&mime_path, mime)
+            .and_then(|_| std::fs::rename(&download_path, &data_path))
+            .map_err(|err| format!("Failed to cache {}: {}", url, err))?;
+    }
+
+    let read_cached =
+        |path: &Path| std::fs::read(path).map_err(|err| format!("Failed to read cache: {}", err));
+    let mime = String::from_utf8_lossy(&read_cached(&mime_path)?).into_owned();
+    Ok((mime, read_cached(&data_path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("15m"), Some(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration(" 1h "), Some(Duration::from_secs(60 * 60)));
+        assert_eq!(
+            parse_duration("7d"),
+            Some(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+        assert_eq!(parse_duration("0s"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for duration in ["", "1", "h", "1w", "1.5h", "-1h", "1 h", "1hh"] {
+            assert_eq!(parse_duration(duration), None, "{:?}", duration);
+        }
+        assert_eq!(parse_duration(&format!("{}d", u64::MAX)), None);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn shares_cache_keys_between_equal_ttls() {
+        let url = "https://example.com/badge.svg";
+        let key = |ttl| cache_key(url, parse_duration(ttl).unwrap());
+        assert_eq!(key("60m"), key("1h"));
+        assert_eq!(key("3600s"), key("1h"));
+        assert_ne!(key("1h"), key("2h"));
+        assert_ne!(
+            key("1h"),
+            cache_key("https://example.com/other.svg", Duration::from_secs(3600))
+        );
+    }
+
+    #[test]
+    fn checks_freshness() {
+        let path = tools::temp_path("checks_freshness", "png");
+        assert!(!is_fresh(&path, Duration::from_secs(60)));
+        std::fs::write(&path, b"").unwrap();
+        let fresh = is_fresh(&path, Duration::from_secs(60));
+        let stale = is_fresh(&path, Duration::ZERO);
+        std::fs::remove_file(&path).unwrap();
+        assert!(fresh);
+        assert!(!stale);
+    }
+
+    const URL: &str = "https://example.com/badge";
+    const SVG: &[u8] = b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>";
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR";
+
+    #[test]
+    fn trusts_the_content_over_the_reported_mime_type() {
+        assert_eq!(
+            checked_mime_type(URL, "image/svg+xml", SVG).unwrap(),
+            "image/svg+xml"
+        );
+        assert_eq!(
+            checked_mime_type(URL, "image/jpeg", PNG).unwrap(),
+            "image/png"
+        );
+        assert_eq!(
+            checked_mime_type(URL, "application/octet-stream", PNG).unwrap(),
+            "image/png"
+        );
+        assert_eq!(checked_mime_type(URL, "", SVG).unwrap(), "image/svg+xml");
+    }
+
+    #[test]
+    fn accepts_unrecognized_images_with_image_mime_types() {
+        assert_eq!(
+            checked_mime_type(URL, "image/x-icns", b"icns").unwrap(),
+            "image/x-icns"
+        );
+    }
+
+    #[test]
+    fn rejects_content_that_is_not_an_image() {
+        let html = b"<!DOCTYPE html><html><body>Not found</body></html>";
+        assert_eq!(
+            checked_mime_type(URL, "text/html", html).unwrap_err(),
+            "https://example.com/badge is not an image: the server reported `text/html`"
+        );
+        assert!(checked_mime_type(URL, "", html).is_err());
+        assert!(checked_mime_type(URL, "image/png", b"%PDF-1.7\n").is_err());
+        // The MIME type ends up in a data URI, so it must be well-formed
+        for mime in [
+            "image/",
+            "image",
+            "image/png\"",
+            "image/a b",
+            "image/png,<svg",
+        ] {
+            assert!(checked_mime_type(URL, mime, b"").is_err(), "{:?}", mime);
+        }
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn reads_fresh_images_from_the_cache() {
+        let manifest_dir = tools::temp_path("reads_fresh_images_from_the_cache", "d");
+        let ttl = Duration::from_secs(60 * 60);
+        let url = "https://example.com/cached.png";
+        let dir = cache_dir(&manifest_dir);
+        let key = cache_key(url, ttl);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(&key), PNG).unwrap();
+        std::fs::write(dir.join(format!("{}.mime", key)), "image/png").unwrap();
+        let fetched = fetch_cached(&manifest_dir, url, ttl);
+        std::fs::remove_dir_all(&manifest_dir).unwrap();
+        assert_eq!(fetched.unwrap(), ("image/png".to_string(), PNG.to_vec()));
+    }
+}
@@ -0,0 +1,112 @@
+//! Sequential numbering of figures.
+//!
+//! The compiler expands the macros of a crate in a single process, in source order within each
+//! file, so a counter that lives for the duration of the process numbers figures in the order in
+//! which they appear. Numbering restarts for every source file, i.e. for every module that lives
+//! in its own file. A macro cannot tell which module it is expanded in, so inline `mod` blocks
+//! share the numbering of their file.
+//!
+//! Proc macro servers (e.g. in IDEs) keep running while the code is edited and expand the same
+//! macros again and again. Where the position of a figure in its file is known, a figure that
+//! does not come after the previous one in the same file therefore restarts the numbering.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// Figures are counted per crate and source file.
+type Key = (String, String);
+
+/// The location of a macro invocation.
+pub(crate) struct CallSite {
+    file: String,
+    /// The line and column of the invocation, if known.
+    position: Option<(usize, usize)>,
+}
+
+#[derive(Default)]
+struct Counter {
+    last_position: Option<(usize, usize)>,
+    count: usize,
+}
+
+/// Returns the location of the current macro invocation.
+#[rustversion::since(1.88)]
+#[allow(clippy::incompatible_msrv)] // Only compiled by compilers that support it
+pub(crate) fn call_site() -> CallSite {
+    let span = proc_macro::Span::call_site();
+    CallSite {
+        file: span.file(),
+        position: Some((span.line(), span.column())),
+    }
+}
+
+/// Returns an unknown location, since older compilers cannot tell where a macro is expanded. All
+/// figures of a crate are then numbered together.
+#[rustversion::before(1.88)]
+pub(crate) fn call_site() -> CallSite {
+    CallSite {
+        file: String::new(),
+        position: None,
+    }
+}
+
+/// Returns the number of the figure at the given location, starting from 1 in every file.
+pub(crate) fn next_number(call_site: CallSite) -> usize {
+    static COUNTERS: OnceLock<Mutex<HashMap<Key, Counter>>> = OnceLock::new();
+    // Proc macro servers may also expand the macros of several crates in one process
+    let krate = std::env::var("CARGO_CRATE_NAME").unwrap_or_default();
+    let mut counters = COUNTERS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let counter = counters.entry((krate, call_site.file)).or_default();
+    if call_site.position.is_some() && call_site.position <= counter.last_position {
+        // The file is being expanded again
+        counter.count = 0;
+    }
+    counter.last_position = call_site.position;
+    counter.count += 1;
+    counter.count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(file: &str, line: usize) -> CallSite {
+        CallSite {
+            file: file.to_string(),
+            position: Some((line, 0)),
+        }
+    }
+
+    #[test]
+    fn numbers_figures_per_file() {
+        // The counters are shared by all tests, so use files that no other test numbers
+        let (a, b) = ("figures/tests/a.rs", "figures/tests/b.rs");
+        assert_eq!(next_number(at(a, 1)), 1);
+        assert_eq!(next_number(at(a, 5)), 2);
+        assert_eq!(next_number(at(b, 3)), 1);
+        assert_eq!(next_number(at(a, 9)), 3);
+    }
+
+    #[test]
+    fn restarts_numbering_when_a_file_is_expanded_again() {
+        let file = "figures/tests/restart.rs";
+        assert_eq!(next_number(at(file, 1)), 1);
+        assert_eq!(next_number(at(file, 5)), 2);
+        assert_eq!(next_number(at(file, 1)), 1);
+        assert_eq!(next_number(at(file, 5)), 2);
+    }
+
+    #[test]
+    fn numbers_figures_at_unknown_positions_sequentially() {
+        let unknown = || CallSite {
+            file: "figures/tests/unknown.rs".to_string(),
+            position: None,
+        };
+        assert_eq!(next_number(unknown()), 1);
+        assert_eq!(next_number(unknown()), 2);
+        assert_eq!(next_number(unknown()), 3);
+    }
+}
@@ -76,6 +76,22 @@
 //! And that's it! If you run `cargo doc`, you should hopefully be able to see your images
 //! in the documentation for `foobar`, and it should also work on `docs.rs` without trouble.
 //!
+//! The attribute can also be applied conditionally with `cfg_attr`, e.g. to embed images only in
+//! the documentation of test functions. It does not matter whether it comes before or after other
+//! attributes such as `#[test]`:
+//!
+//! ```rust,no_run
+//! #[cfg(test)]
+//! mod tests {
+//!     use embed_doc_image::embed_doc_image;
+//!
+//!     /// ![Test fixture][test-diagram]
+//!     #[cfg_attr(test, embed_doc_image("test-diagram", "images/test.png"))]
+//!     #[test]
+//!     fn fixture_works() {}
+//! }
+//! ```
+//!
 //! ## Embedding images in inner attribute documentation
 //!
 //! The ability for macros to do *anything* with *inner attributes* is very limited. In fact,
@@ -108,6 +124,10 @@
 //! //! ![Alt text goes here][myimagelabel] ![A Foobaring][foobaring]
 //! ```
 //!
+//! Instead of repeating `doc = ...` for each image, several label/path pairs can also be passed
+//! to a single invocation of `embed_image!`, separated by semicolons:
+//! `embed_image!("myimagelabel", "images/foo.png"; "foobaring", "assets/foobaring.png")`.
+//!
 //! Sadly there is currently no way to detect Rust versions in `cfg_attr`. Therefore we must
 //! rely on a feature flag for toggling proper image embedding. We'll need the following in our
 //! `Cargo.toml`:
@@ -127,12 +147,84 @@
 //! - `docs.rs` will correctly render our documentation with images.
 //! - Locally:
 //!   - for Rust >= 1.54 with `--features doc-images`, the local documentation will
-//!       correctly render images.
+//!     correctly render images.
 //!   - for Rust < 1.54: the local documentation will be missing some images, and will
-//!   contain a warning with instructions on how to enable proper image embedding.
+//!     contain a warning with instructions on how to enable proper image embedding.
 //!   - we can also use e.g. `cargo +nightly doc --features doc-images` to produce correct
 //!     documentation with a nightly compiler.
 //!
+//! # Image size limits
+//!
+//! Embedding an image increases its size by a third due to the `base64` encoding, and some
+//! browsers struggle with data URIs larger than about 2 MiB. Very long string literals also slow
+//! down compilation. A warning is therefore printed for every image whose encoded size exceeds
+//! 2 MiB, or 1 MiB for animated WebP images, which are also costly to play back. In addition, a
+//! hard limit can be set with the `EMBED_DOC_IMAGE_HARD_LIMIT_MB` environment variable, e.g.
+//! `EMBED_DOC_IMAGE_HARD_LIMIT_MB=5`, in which case compilation fails for images whose encoded
+//! size exceeds the given number of MiB. `EMBED_DOC_IMAGE_MAX_BYTES` sets the same limit in
+//! bytes. If both are set, the stricter limit applies.
+//!
+//! Large images are better compressed (e.g. with `oxipng` or `svgo`), scaled down, or hosted
+//! externally and linked by URL.
+//!
+//! # Configuration
+//!
+//! Besides the size limits above, the following environment variables are read during
+//! compilation. They apply to all macros in the crate being compiled.
+//!
+//! - `EMBED_DOC_IMAGE_SANITIZE_SVG`: if `true`, `<script>` elements are removed from SVG
+//!   images. Browsers do not run scripts in images, but they do in SVGs embedded with
+//!   `embed_image_object!`. Defaults to `false`.
+//! - `EMBED_DOC_IMAGE_LABEL_PATTERN`: a pattern that all image labels must match, where `*`
+//!   matches any sequence of characters and `?` any single character, e.g. `fig-*`. This helps
+//!   keep labels consistent across a large crate.
+//! - `EMBED_DOC_IMAGE_STRIP_EXIF`: if `false`, textual and EXIF metadata are kept in PNG images.
+//!   Defaults to `true`.
+//! - `EMBED_DOC_IMAGE_STRIP_PNG_GAMMA`: if `true`, gamma and color space information is removed
+//!   from all PNG images, as with `embed_image_strip_gamma!`. Defaults to `false`.
+//! - `EMBED_DOC_IMAGE_JPEG_QUALITY`: the quality from 1 to 100 of JPEG images that are
+//!   re-encoded by macros such as `embed_image_shrink_to_fit!`. Defaults to 90.
+//! - `EMBED_DOC_IMAGE_PNG_LEVEL`: the compression level from 0 (fastest) to 9 (smallest) of PNG
+//!   images generated by macros that require the `image` feature. Defaults to 6.
+//! - `EMBED_DOC_IMAGE_DEBUG`: if `true`, `embed_image_debug_output!` writes the images it embeds
+//!   to the target directory for inspection. Defaults to `false`.
+//! - `EMBED_DOC_IMAGE_SVG_PRECISION`: the number of decimal places from 0 to 10 to round the
+//!   coordinates in all SVG images to, as with `embed_image_shrink_svg_precision!`. By default,
+//!   SVG images are embedded unchanged. Rounding requires the `svg` feature, and setting this
+//!   variable without it is a compile error.
+//! - `EMBED_DOC_IMAGE_ALWAYS_EMBED`: if `true`, the macros embed images in every build. By
+//!   default, they only do so when the documentation is built, i.e. under `rustdoc`, and
+//!   otherwise only check that the images exist, without reading, rendering or fetching them.
+//!   Function-like macros then expand to an empty string, and attributes leave the item
+//!   unchanged. Set this when inspecting the output of the macros in tests and doctests, which
+//!   are not compiled by `rustdoc` either. It is also needed if other crates re-export your items
+//!   with `#[doc(inline)]`, since their documentation is then taken from a regular build of your
+//!   crate. Some macros still do part of their work in every build:
+//!   `embed_image_max_file_age!`, `embed_image_assert_format!`, `embed_image_max_dimensions!`,
+//!   `embed_image_icon!` and `embed_image_resize_and_compare!` check the images, and
+//!   `embed_image_debug_output!` writes them to disk, while macros that produce no documentation,
+//!   such as `embed_image_hash_label!`, `embed_doc_image_include_dir_labels!` and
+//!   `embed_image_print_size!`, are not affected at all.
+//! - `EMBED_DOC_IMAGE_PLANTUML_SERVER` and `EMBED_DOC_IMAGE_PLANTUML_TIMEOUT`: the URL of the
+//!   PlantUML server used by `embed_image_from_plantuml!`, and how many seconds to wait for it.
+//!   Default to `https://www.plantuml.com/plantuml` and 10 seconds.
+//!
+//! Boolean variables accept `true`, `false`, `1`, `0`, `yes`, `no`, `on` and `off`. Invalid
+//! values are reported as compile errors.
+//!
+//! Cargo does not know that the macros read these variables, so changing one does not cause
+//! the crate to be rebuilt. After changing a variable, touch a source file of the crate, or run
+//! `cargo clean -p <crate>`, to rebuild it with the new configuration.
+//!
+//! # Use in `no_std` crates
+//!
+//! All macros run at compile time only, on the host, so the crate works the same way for
+//! `no_std` crates. The generated code consists of string literals, `#[doc]` attributes and
+//! unnamed constants that include image files with `::core::include_bytes!`, so that the
+//! compiler tracks them. It never refers to `std` or `alloc`, and is therefore unaffected by lints
+//! such as `clippy::std_instead_of_core`. The crate has no runtime component, so nothing from it
+//! ends up in the compiled crate.
+//!
 //!
 //! # How it works
 //!
@@ -155,9 +247,10 @@
 //! inline in the Markdown as `base64` encoded binary blobs in the following way:
 //!
 //! ```rust
-//! ![Alt text][myimagelabel]
-//!
-//! [myimagelabel]: data:image/png;base64,BaSe64EnCoDeDdAtA
+//! /// ![Alt text][myimagelabel]
+//! ///
+//! /// [myimagelabel]: data:image/png;base64,BaSe64EnCoDeDdAtA
+//! fn foobar() {}
 //! ```
 //!
 //! Basically we can use the "reference" feature of Markdown links/images to provide the URL
@@ -195,10 +288,36 @@
 //!
 //!
 
+mod chart;
+mod config;
+mod dedup;
+mod dimensions;
+mod fetch;
+mod figures;
+mod magic;
+mod metadata;
+mod options;
+mod packages;
+mod plantuml;
+mod png;
+mod qr;
+mod raster;
+mod svg;
+mod symlink;
+mod tools;
+mod webp;
+
+use base64::write::EncoderStringWriter;
+use config::EmbedDocImageConfig;
+use embed_doc_image_core::{append_doc_separator, append_image_doc, mime_type_for_extension};
+use options::Options;
 use proc_macro::TokenStream;
-use quote::{quote, ToTokens};
-use std::fs::read;
+use quote::{ToTokens, quote};
+use std::fmt;
+use std::fs::{File, read};
+use std::io::{self, Read, Seek, Write};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use syn::parse;
 use syn::parse::{Parse, ParseStream};
 use syn::{
@@ -207,12 +326,18 @@ use syn::{
     ItemUse,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct ImageDescription {
     label: String,
     path: PathBuf,
 }
 
+impl fmt::Display for ImageDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "label='{}', path='{}'", self.label, self.path.display())
+    }
+}
+
 impl Parse for ImageDescription {
     fn parse(input: ParseStream) -> parse::Result<Self> {
         let label = input.parse::<syn::LitStr>()?;
@@ -225,116 +350,7116 @@ impl Parse for ImageDescription {
     }
 }
 
-fn encode_base64_image_from_path(path: &Path) -> String {
-    let bytes = read(path).unwrap_or_else(|_| panic!("Failed to load image at {}", path.display()));
-    base64::encode(bytes)
+/// A list of `("label", "path")` pairs, as given to `#[embed_doc_images(...)]`.
+#[derive(Debug)]
+struct ImageDescriptionList {
+    images: Vec<ImageDescription>,
+}
+
+impl Parse for ImageDescriptionList {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let mut images: Vec<ImageDescription> = Vec::new();
+        while !input.is_empty() {
+            if !input.peek(syn::token::Paren) {
+                return Err(input.error("Expected a `(\"label\", \"path\")` pair."));
+            }
+            let content;
+            syn::parenthesized!(content in input);
+            let label = content.fork().parse::<syn::LitStr>()?;
+            let image = content.parse::<ImageDescription>()?;
+            // Two definitions of the same reference would leave one of the images unused, and
+            // the Markdown would silently show the wrong one
+            if images.iter().any(|other| other.label == image.label) {
+                return Err(syn::Error::new(
+                    label.span(),
+                    format!("The label `{}` is given more than once.", image.label),
+                ));
+            }
+            images.push(image);
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+        if images.is_empty() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Expected at least one `(\"label\", \"path\")` pair. To embed the images declared \
+                 on the fields of a struct, use `embed_field_doc_images`.",
+            ));
+        }
+        Ok(ImageDescriptionList { images })
+    }
+}
+
+/// A directory of images, as given to `embed_doc_images_from_dir!`.
+#[derive(Debug)]
+struct DirDescription {
+    path: PathBuf,
+    prefix: String,
+    recursive: bool,
+}
+
+impl Parse for DirDescription {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let path = input.parse::<syn::LitStr>()?;
+        let mut options = input.parse::<Options>()?;
+        let prefix = options.take_str("prefix")?.ok_or_else(|| {
+            syn::Error::new(
+                path.span(),
+                "Missing label prefix, e.g. `prefix = \"step\"`.",
+            )
+        })?;
+        let recursive = options.take_bool("recursive")?.unwrap_or(false);
+        options.finish()?;
+        Ok(DirDescription {
+            path: PathBuf::from(path.value()),
+            prefix: prefix.value(),
+            recursive,
+        })
+    }
+}
+
+/// An image description followed by macro-specific options, e.g.
+/// `"label", "path.png", alt = "Alt text"`.
+struct ImageWithOptions {
+    image: ImageDescription,
+    options: Options,
+}
+
+impl Parse for ImageWithOptions {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let image = input.parse::<ImageDescription>()?;
+        let options = input.parse::<Options>()?;
+        Ok(ImageWithOptions { image, options })
+    }
+}
+
+/// A label followed by macro-specific options, e.g. `"label", path = "path.png"`.
+struct LabelWithOptions {
+    label: syn::LitStr,
+    options: Options,
 }
 
-fn determine_mime_type(extension: &str) -> String {
-    let extension = extension.to_ascii_lowercase();
+impl Parse for LabelWithOptions {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let label = input.parse::<syn::LitStr>()?;
+        let options = input.parse::<Options>()?;
+        Ok(LabelWithOptions { label, options })
+    }
+}
 
-    // TODO: Consider using the mime_guess crate? The below list does seem kinda exhaustive for
-    // doc purposes though?
+/// An image label with alternative paths selected by `cfg` predicates, e.g.
+/// `"label", cfg(unix) => "unix.png", default => "other.png"`.
+struct ConditionalImages {
+    label: String,
+    branches: Vec<(proc_macro2::TokenStream, PathBuf)>,
+    default: Option<PathBuf>,
+}
 
-    // Matches taken haphazardly from
-    // https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types/Common_types
-    match extension.as_str() {
-        "jpg" | "jpeg" => "image/jpeg",
-        "png" => "image/png",
-        "bmp" => "image/bmp",
-        "svg" => "image/svg+xml",
-        "gif" => "image/gif",
-        "tif" | "tiff" => "image/tiff",
-        "webp" => "image/webp",
-        "ico" => "image/vnd.microsoft.icon",
-        _ => panic!("Unrecognized image extension, unable to infer correct MIME type"),
+impl Parse for ConditionalImages {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let label = input.parse::<syn::LitStr>()?.value();
+        let mut branches = Vec::new();
+        let mut default = None;
+        while !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            if input.peek(syn::Token![default]) {
+                let keyword = input.parse::<syn::Token![default]>()?;
+                if default.is_some() {
+                    return Err(syn::Error::new(
+                        keyword.span,
+                        "Only one default image may be given.",
+                    ));
+                }
+                input.parse::<syn::Token![=>]>()?;
+                default = Some(PathBuf::from(input.parse::<syn::LitStr>()?.value()));
+            } else {
+                let cfg = input.parse::<syn::Ident>()?;
+                if cfg != "cfg" {
+                    return Err(syn::Error::new(
+                        cfg.span(),
+                        "Expected `cfg(...) => \"path\"` or `default => \"path\"`.",
+                    ));
+                }
+                let predicate;
+                syn::parenthesized!(predicate in input);
+                let predicate = predicate.parse::<proc_macro2::TokenStream>()?;
+                input.parse::<syn::Token![=>]>()?;
+                let path = PathBuf::from(input.parse::<syn::LitStr>()?.value());
+                branches.push((predicate, path));
+            }
+        }
+        Ok(ConditionalImages {
+            label,
+            branches,
+            default,
+        })
     }
-    .to_string()
 }
 
-fn produce_doc_string_for_image(image_desc: &ImageDescription) -> String {
-    let root_dir = std::env::var("CARGO_MANIFEST_DIR")
-        .expect("Failed to retrieve value of CARGO_MANOFEST_DIR.");
-    let root_dir = Path::new(&root_dir);
-    let encoded = encode_base64_image_from_path(&root_dir.join(&image_desc.path));
-    let ext = image_desc.path.extension().unwrap_or_else(|| {
+/// An image label with alternative paths selected by the value of an environment variable, e.g.
+/// `"label", env = "DEPLOY_ENV", "dev" => "dev.png", default => "other.png"`.
+struct EnvConditionalImages {
+    label: String,
+    var: syn::LitStr,
+    branches: Vec<(String, PathBuf)>,
+    default: Option<PathBuf>,
+}
+
+impl Parse for EnvConditionalImages {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let label = input.parse::<syn::LitStr>()?.value();
+        input.parse::<syn::Token![,]>()?;
+        let key = input.parse::<syn::Ident>()?;
+        if key != "env" {
+            return Err(syn::Error::new(
+                key.span(),
+                "Expected `env = \"VARIABLE\"` after the label.",
+            ));
+        }
+        input.parse::<syn::Token![=]>()?;
+        let var = input.parse::<syn::LitStr>()?;
+        let mut branches: Vec<(String, PathBuf)> = Vec::new();
+        let mut default = None;
+        while !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            if input.peek(syn::Token![default]) {
+                let keyword = input.parse::<syn::Token![default]>()?;
+                if default.is_some() {
+                    return Err(syn::Error::new(
+                        keyword.span,
+                        "Only one default image may be given.",
+                    ));
+                }
+                input.parse::<syn::Token![=>]>()?;
+                default = Some(PathBuf::from(input.parse::<syn::LitStr>()?.value()));
+            } else {
+                let value = input.parse::<syn::LitStr>().map_err(|err| {
+                    syn::Error::new(
+                        err.span(),
+                        "Expected `\"value\" => \"path\"` or `default => \"path\"`.",
+                    )
+                })?;
+                if branches
+                    .iter()
+                    .any(|(existing, _)| *existing == value.value())
+                {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        format!("The value `{}` was given more than once.", value.value()),
+                    ));
+                }
+                input.parse::<syn::Token![=>]>()?;
+                let path = PathBuf::from(input.parse::<syn::LitStr>()?.value());
+                branches.push((value.value(), path));
+            }
+        }
+        Ok(EnvConditionalImages {
+            label,
+            var,
+            branches,
+            default,
+        })
+    }
+}
+
+/// Parses a rectangle such as `(x=10, y=20, w=100, h=50)`.
+fn parse_rect(input: ParseStream) -> parse::Result<[u32; 4]> {
+    let content;
+    let paren = syn::parenthesized!(content in input);
+    let mut rect = [None; 4];
+    let fields = content.parse_terminated::<_, syn::Token![,]>(|input: ParseStream| {
+        let key = input.parse::<syn::Ident>()?;
+        input.parse::<syn::Token![=]>()?;
+        let value = input.parse::<syn::LitInt>()?.base10_parse::<u32>()?;
+        Ok((key, value))
+    })?;
+    for (key, value) in fields {
+        let index = ["x", "y", "w", "h"]
+            .iter()
+            .position(|name| key == name)
+            .ok_or_else(|| syn::Error::new(key.span(), "Expected one of `x`, `y`, `w` and `h`."))?;
+        rect[index] = Some(value);
+    }
+    match rect {
+        [Some(x), Some(y), Some(w), Some(h)] => Ok([x, y, w, h]),
+        _ => Err(syn::Error::new(
+            paren.span,
+            "Rectangles must specify all of `x`, `y`, `w` and `h`.",
+        )),
+    }
+}
+
+/// A rectangular hotspot of an image map, e.g. `((x=10, y=20, w=100, h=50), href="crate::foo")`.
+struct MapArea {
+    rect: [u32; 4],
+    href: syn::LitStr,
+}
+
+impl Parse for MapArea {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+        let rect = parse_rect(&content)?;
+        content.parse::<syn::Token![,]>()?;
+        let key = content.parse::<syn::Ident>()?;
+        if key != "href" {
+            return Err(syn::Error::new(key.span(), "Expected `href`."));
+        }
+        content.parse::<syn::Token![=]>()?;
+        let href = content.parse::<syn::LitStr>()?;
+        Ok(MapArea { rect, href })
+    }
+}
+
+/// An image followed by an image map and further options, e.g.
+/// `"label", "path.png", map = [((x=0, y=0, w=10, h=10), href="crate::foo")]`.
+struct ImageWithMap {
+    image: ImageDescription,
+    areas: Vec<MapArea>,
+    options: Options,
+}
+
+impl Parse for ImageWithMap {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let image = input.parse::<ImageDescription>()?;
+        input.parse::<syn::Token![,]>()?;
+        let key = input.parse::<syn::Ident>()?;
+        if key != "map" {
+            return Err(syn::Error::new(key.span(), "Expected `map = [...]`."));
+        }
+        input.parse::<syn::Token![=]>()?;
+        let content;
+        syn::bracketed!(content in input);
+        let areas = content
+            .parse_terminated::<_, syn::Token![,]>(MapArea::parse)?
+            .into_iter()
+            .collect();
+        let options = input.parse::<Options>()?;
+        Ok(ImageWithMap {
+            image,
+            areas,
+            options,
+        })
+    }
+}
+
+/// A bracketed list of images followed by macro-specific options, e.g.
+/// `[("label1", "path1.png"), ("label2", "path2.png")], cols = 2`.
+struct ImageListWithOptions {
+    images: Vec<ImageDescription>,
+    options: Options,
+}
+
+impl Parse for ImageListWithOptions {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let content;
+        syn::bracketed!(content in input);
+        let images = content
+            .parse_terminated::<_, syn::Token![,]>(|tuple: ParseStream| {
+                let content;
+                syn::parenthesized!(content in tuple);
+                content.parse::<ImageDescription>()
+            })?
+            .into_iter()
+            .collect();
+        let options = input.parse::<Options>()?;
+        Ok(ImageListWithOptions { images, options })
+    }
+}
+
+/// A color palette followed by further options, e.g.
+/// `"palette", colors = [("#ff5733", "Danger"), ("#33a1ff", "Primary")], swatch_size = 40`.
+struct PaletteWithOptions {
+    label: syn::LitStr,
+    colors: Vec<(syn::LitStr, syn::LitStr)>,
+    options: Options,
+}
+
+impl Parse for PaletteWithOptions {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let label = input.parse::<syn::LitStr>()?;
+        input.parse::<syn::Token![,]>()?;
+        let key = input.parse::<syn::Ident>()?;
+        if key != "colors" {
+            return Err(syn::Error::new(key.span(), "Expected `colors = [...]`."));
+        }
+        input.parse::<syn::Token![=]>()?;
+        let content;
+        syn::bracketed!(content in input);
+        let colors = content
+            .parse_terminated::<_, syn::Token![,]>(|tuple: ParseStream| {
+                let content;
+                syn::parenthesized!(content in tuple);
+                let color = content.parse::<syn::LitStr>()?;
+                content.parse::<syn::Token![,]>()?;
+                let name = content.parse::<syn::LitStr>()?;
+                Ok((color, name))
+            })?
+            .into_iter()
+            .collect();
+        let options = input.parse::<Options>()?;
+        Ok(PaletteWithOptions {
+            label,
+            colors,
+            options,
+        })
+    }
+}
+
+/// Chart data followed by further options, e.g.
+/// `"perf", kind = "bar", data = [("Vec", 12.5), ("HashMap", 15.1)], y_label = "ns/op"`.
+///
+/// The `data` entry may appear anywhere among the options.
+struct ChartWithOptions {
+    label: syn::LitStr,
+    data: Vec<(String, f64)>,
+    options: Options,
+}
+
+/// Parses a data point such as `("Vec", 12.5)` or `("Delta", -3)`.
+fn parse_data_point(input: ParseStream) -> parse::Result<(String, f64)> {
+    let content;
+    syn::parenthesized!(content in input);
+    let name = content.parse::<syn::LitStr>()?;
+    content.parse::<syn::Token![,]>()?;
+    let negative = content.parse::<Option<syn::Token![-]>>()?.is_some();
+    let value: f64 = match content.parse::<syn::Lit>()? {
+        syn::Lit::Float(lit) => lit.base10_parse()?,
+        syn::Lit::Int(lit) => lit.base10_parse()?,
+        lit => return Err(syn::Error::new(lit.span(), "Expected a number.")),
+    };
+    Ok((name.value(), if negative { -value } else { value }))
+}
+
+impl Parse for ChartWithOptions {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let label = input.parse::<syn::LitStr>()?;
+        let (data, options) = Options::parse_with(input, "data", |input| {
+            let content;
+            syn::bracketed!(content in input);
+            let points = content.parse_terminated::<_, syn::Token![,]>(parse_data_point)?;
+            Ok(points.into_iter().collect())
+        })?;
+        let data = data.ok_or_else(|| {
+            syn::Error::new(
+                label.span(),
+                "Missing chart data, e.g. `data = [(\"a\", 1.0), (\"b\", 2.0)]`.",
+            )
+        })?;
+        Ok(ChartWithOptions {
+            label,
+            data,
+            options,
+        })
+    }
+}
+
+/// An image followed by options, one of which may be a region such as
+/// `region = (x=0, y=0, w=10, h=10)`.
+struct ImageWithRegion {
+    image: ImageDescription,
+    region: Option<[u32; 4]>,
+    options: Options,
+}
+
+impl Parse for ImageWithRegion {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        ImageWithRegion::parse_with_key(input, "region")
+    }
+}
+
+impl ImageWithRegion {
+    /// Parses an image and options, where the option `key` is the region.
+    fn parse_with_key(input: ParseStream, key: &str) -> parse::Result<Self> {
+        let image = input.parse::<ImageDescription>()?;
+        let (region, options) = Options::parse_with(input, key, parse_rect)?;
+        Ok(ImageWithRegion {
+            image,
+            region,
+            options,
+        })
+    }
+}
+
+/// A label followed by options, one of which may be `expected_dimensions = (width, height)`.
+struct LabelWithDimensions {
+    label: syn::LitStr,
+    dimensions: Option<(u32, u32)>,
+    options: Options,
+}
+
+impl Parse for LabelWithDimensions {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let label = input.parse::<syn::LitStr>()?;
+        let (dimensions, options) = Options::parse_with(input, "expected_dimensions", |input| {
+            let content;
+            syn::parenthesized!(content in input);
+            let width = content.parse::<syn::LitInt>()?.base10_parse()?;
+            content.parse::<syn::Token![,]>()?;
+            let height = content.parse::<syn::LitInt>()?.base10_parse()?;
+            Ok((width, height))
+        })?;
+        Ok(LabelWithDimensions {
+            label,
+            dimensions,
+            options,
+        })
+    }
+}
+
+/// A labeled list of images, as given to e.g. `embed_image_gallery!`, e.g.
+/// `"gallery", ["a.png", "b.png"], cols = 2`.
+struct GalleryWithOptions {
+    label: syn::LitStr,
+    paths: Vec<PathBuf>,
+    options: Options,
+}
+
+impl Parse for GalleryWithOptions {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let label = input.parse::<syn::LitStr>()?;
+        input.parse::<syn::Token![,]>()?;
+        let content;
+        syn::bracketed!(content in input);
+        let paths = content
+            .parse_terminated::<syn::LitStr, syn::Token![,]>(<syn::LitStr as Parse>::parse)?
+            .into_iter()
+            .map(|path| PathBuf::from(path.value()))
+            .collect();
+        let options = input.parse::<Options>()?;
+        Ok(GalleryWithOptions {
+            label,
+            paths,
+            options,
+        })
+    }
+}
+
+/// A label followed by options, one of which may be a list of paths such as
+/// `pages = ["a.png", "b.png"]`.
+struct LabelWithPaths {
+    label: syn::LitStr,
+    paths: Option<Vec<PathBuf>>,
+    options: Options,
+}
+
+impl LabelWithPaths {
+    /// Parses a label and options, where the option `key` is the list of paths.
+    fn parse_with_key(input: ParseStream, key: &str) -> parse::Result<Self> {
+        let label = input.parse::<syn::LitStr>()?;
+        let (paths, options) = Options::parse_with(input, key, |input| {
+            let content;
+            syn::bracketed!(content in input);
+            let paths = content
+                .parse_terminated::<syn::LitStr, syn::Token![,]>(<syn::LitStr as Parse>::parse)?;
+            Ok(paths
+                .into_iter()
+                .map(|path| PathBuf::from(path.value()))
+                .collect())
+        })?;
+        Ok(LabelWithPaths {
+            label,
+            paths,
+            options,
+        })
+    }
+}
+
+/// Size of the chunks in which images are read when encoding them directly from file.
+///
+/// This is a multiple of 3, so that every chunk maps to a whole number of `base64` characters.
+const ENCODE_CHUNK_SIZE: usize = 48 * 1024;
+
+/// Reads an image and encodes it as `base64`.
+///
+/// Images larger than [`dedup::MAX_CACHED_LEN`] are read and encoded in chunks, so that we never
+/// hold both the full raw image and its encoded representation in memory at the same time. Such
+/// images are not deduplicated, since that would require keeping their encoding in memory for
+/// the rest of the compilation. Smaller images are read in full and encoded by [`dedup::encode`].
+/// PNG and SVG images do not get here, since they are processed before embedding, which needs
+/// the whole file in memory (see [`produce_doc_string_for_image`]).
+fn encode_base64_image(image_desc: &ImageDescription) -> String {
+    let path = resolve_path(&image_desc.path);
+    let encode = || -> io::Result<String> {
+        let mut file = File::open(&path)?;
+        let raw_len = file.metadata()?.len();
+        let mut header = Vec::with_capacity(SIZE_CHECK_HEADER_LEN);
+        (&mut file)
+            .take(SIZE_CHECK_HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        file.seek(io::SeekFrom::Start(0))?;
+        check_encoded_size(image_desc, raw_len, &header);
+        if raw_len <= dedup::MAX_CACHED_LEN {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            return Ok(dedup::encode(&bytes));
+        }
+        let mut encoded = String::with_capacity((raw_len * 4 / 3 + 4) as usize);
+        let mut encoder = EncoderStringWriter::from(&mut encoded, base64::STANDARD);
+        let mut buffer = vec![0; ENCODE_CHUNK_SIZE];
+        loop {
+            let n = file.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            encoder.write_all(&buffer[..n])?;
+        }
+        encoder.into_inner();
+        Ok(encoded)
+    };
+    encode().unwrap_or_else(|_| {
+        panic!(
+            "Failed to load image ({}) at {}",
+            image_desc,
+            path.display()
+        )
+    })
+}
+
+/// Encoded size above which we warn that an image may be too large to display reliably.
+const SIZE_WARNING_THRESHOLD: u64 = 2 * 1024 * 1024;
+
+/// Lower warning threshold for animations, which are costly to decode and play back.
+const ANIMATION_SIZE_WARNING_THRESHOLD: u64 = 1024 * 1024;
+
+/// Size above which we warn that a video slows down loading the documentation.
+const VIDEO_SIZE_WARNING_THRESHOLD: u64 = 1024 * 1024;
+
+/// Number of bytes at the start of an image that [`check_encoded_size`] needs.
+const SIZE_CHECK_HEADER_LEN: usize = 256;
+
+/// Checks the size that an image of `raw_len` bytes will have once `base64`-encoded against the
+/// size limits described in the crate documentation.
+///
+/// `header` must contain the start of the image (at least [`SIZE_CHECK_HEADER_LEN`] bytes, if
+/// available), which is used to detect animations.
+fn check_encoded_size(image_desc: &ImageDescription, raw_len: u64, header: &[u8]) {
+    let encoded_len = raw_len.div_ceil(3) * 4;
+    let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+    if let Some((limit, var)) = EmbedDocImageConfig::from_env().hard_limit {
+        if encoded_len > limit {
+            // Describe sizes in the unit that the limit was given in
+            let size = |bytes: u64| {
+                if var == config::MAX_BYTES_VAR {
+                    format!("{} bytes", bytes)
+                } else {
+                    format!("{:.1} MiB", mib(bytes))
+                }
+            };
+            panic!(
+                "Image ({}) is {} when encoded, which exceeds the limit of {} set by {}. \
+                 Consider compressing or scaling down the image, or hosting it externally.",
+                image_desc,
+                size(encoded_len),
+                size(limit),
+                var
+            );
+        }
+    }
+    let animated = webp::is_animated(header);
+    let threshold = if animated {
+        ANIMATION_SIZE_WARNING_THRESHOLD
+    } else {
+        SIZE_WARNING_THRESHOLD
+    };
+    if encoded_len > threshold {
+        emit_warning(&format!(
+            "{} ({}) is {:.1} MiB when encoded, which some browsers may fail to display. \
+             Consider compressing or scaling down the image, or hosting it externally.",
+            if animated { "animation" } else { "image" },
+            image_desc,
+            mib(encoded_len)
+        ));
+    }
+}
+
+fn is_svg_path(path: &Path) -> bool {
+    path.extension()
+        .filter(|ext| ext.eq_ignore_ascii_case("svg"))
+        .is_some()
+}
+
+fn read_image_raw(image_desc: &ImageDescription) -> Vec<u8> {
+    let path = resolve_path(&image_desc.path);
+    let bytes = read(&path).unwrap_or_else(|_| {
         panic!(
-            "No extension for file {}. Unable to determine MIME type.",
-            image_desc.path.display()
+            "Failed to load image ({}) at {}",
+            image_desc,
+            path.display()
         )
     });
-    let mime = determine_mime_type(&ext.to_string_lossy());
-    let doc_string = format!(
-        " [{label}]: data:{mime};base64,{encoded}",
-        label = &image_desc.label,
-        mime = mime,
-        encoded = &encoded
-    );
-    doc_string
+    check_encoded_size(image_desc, bytes.len() as u64, &bytes);
+    bytes
 }
 
-/// Produces a doc string for inclusion in Markdown documentation.
+/// Whether the macros should embed their images, or only check that they exist, see
+/// [`embeds_images`].
 ///
-/// Please see the crate-level documentation for usage instructions.
-#[proc_macro]
-pub fn embed_image(item: TokenStream) -> TokenStream {
-    let image_desc = syn::parse_macro_input!(item as ImageDescription);
-    let doc_string = produce_doc_string_for_image(&image_desc);
+/// Images only end up in the documentation, so reading and encoding them in other builds is
+/// wasted time. Proc macros cannot see whether `cfg(doc)` is set for the crate being compiled,
+/// but it is set exactly when the compiler running the macro is `rustdoc`. If the compiler cannot
+/// be determined, the images are embedded to be safe.
+fn should_embed() -> bool {
+    static IS_RUSTDOC: OnceLock<bool> = OnceLock::new();
+    let is_rustdoc = *IS_RUSTDOC.get_or_init(|| {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.file_stem()?.to_string_lossy().starts_with("rustdoc")))
+            .unwrap_or(true)
+    });
+    is_rustdoc || EmbedDocImageConfig::from_env().always_embed
+}
 
-    // Ensure that the "image table" at the end is separated from the rest of the documentation,
-    // otherwise the markdown parser will not treat them as a "lookup table" for the image data
-    let s = format!("\n \n {}", doc_string);
-    let tokens = quote! {
-        #s
+/// Fails like [`read_image_raw`] if the image does not exist, so that builds that do not embed
+/// images still report broken paths.
+fn check_image_exists(image_desc: &ImageDescription) {
+    let path = resolve_path(&image_desc.path);
+    if !path.is_file() {
+        panic!(
+            "Failed to load image ({}) at {}",
+            image_desc,
+            path.display()
+        );
+    }
+}
+
+/// Checks that the given images exist if images are not embedded in the current build, see
+/// [`should_embed`], and returns whether they are.
+///
+/// Macros that embed images call this once their options have been parsed, and expand to an
+/// empty string, or leave the annotated item unchanged, if it returns `false`.
+fn embeds_images<'a>(images: impl IntoIterator<Item = &'a ImageDescription>) -> bool {
+    if should_embed() {
+        return true;
+    }
+    images.into_iter().for_each(check_image_exists);
+    false
+}
+
+/// Reads an image, stripping metadata that does not contribute to the image content.
+///
+/// Currently this only affects PNG files, see [`png::strip_metadata`]. SVG files are checked
+/// for well-formedness, unless the `svg` feature is disabled, and rounded and sanitized as
+/// configured.
+fn read_image(image_desc: &ImageDescription) -> Vec<u8> {
+    prepare_image(image_desc, read_image_raw(image_desc))
+}
+
+/// Checks SVG images for well-formedness and strips metadata from PNG images, as for all images
+/// read from disk. SVG images are also rounded and sanitized if configured to do so.
+fn prepare_image(image_desc: &ImageDescription, bytes: Vec<u8>) -> Vec<u8> {
+    let config = EmbedDocImageConfig::from_env();
+    if is_svg_path(&image_desc.path) {
+        if let Err(err) = svg::validate(&bytes) {
+            panic!("Malformed SVG image ({}): {}", image_desc, err);
+        }
+        let bytes = match config.svg_precision {
+            Some(digits) => svg::round_numbers(&bytes, digits).unwrap_or_else(|err| {
+                panic!(
+                    "Failed to round numbers in SVG image ({}): {}",
+                    image_desc, err
+                )
+            }),
+            None => bytes,
+        };
+        if config.sanitize_svg {
+            let svg = String::from_utf8(bytes)
+                .unwrap_or_else(|_| panic!("SVG file for image ({}) is not UTF-8", image_desc));
+            return svg::strip_elements(&svg, "script").into_bytes();
+        }
+        return bytes;
+    }
+    let bytes = if png::is_png(&bytes) && config.strip_exif {
+        png::strip_metadata(&bytes).unwrap_or(bytes)
+    } else {
+        bytes
     };
-    tokens.into()
+    if png::is_png(&bytes) && config.strip_png_gamma {
+        png::strip_gamma(&bytes).unwrap_or(bytes)
+    } else {
+        bytes
+    }
 }
 
-/// Produces a doc string for inclusion in Markdown documentation.
+fn determine_mime_type(image_desc: &ImageDescription, extension: &str) -> String {
+    let mime = mime_type_for_extension(extension).unwrap_or_else(|| {
+        panic!(
+            "Unrecognized image extension for image ({}), unable to infer correct MIME type",
+            image_desc
+        )
+    });
+    normalize_mime_type(mime)
+}
+
+/// Parses a MIME type with the `mime` crate, which checks its syntax and brings it into
+/// canonical form, e.g. with parameters such as `charset=utf-8` formatted consistently.
+#[cfg(feature = "mime-types")]
+fn normalize_mime_type(mime: &str) -> String {
+    let parsed: mime::Mime = mime
+        .parse()
+        .unwrap_or_else(|err| panic!("Invalid MIME type `{}`: {}", mime, err));
+    parsed.to_string()
+}
+
+#[cfg(not(feature = "mime-types"))]
+fn normalize_mime_type(mime: &str) -> String {
+    mime.to_string()
+}
+
+fn crate_root_dir() -> PathBuf {
+    // Use `var_os`, since the crate may well live in a directory whose path is not valid UTF-8
+    let root_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .expect("Failed to retrieve value of CARGO_MANIFEST_DIR.");
+    PathBuf::from(root_dir)
+}
+
+/// Resolves a path relative to the crate root.
 ///
-/// Please see the crate-level documentation for usage instructions.
-#[proc_macro_attribute]
-pub fn embed_doc_image(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let image_desc = syn::parse_macro_input!(attr as ImageDescription);
-    let doc_string = produce_doc_string_for_image(&image_desc);
+/// The path is canonicalized if it exists. On Windows, this normalizes paths on network drives
+/// to plain UNC paths (`\\server\share\...`) rather than extended-length paths
+/// (`\\?\UNC\server\share\...`), which display correctly in error messages and are understood
+/// by all tools that consume the paths we print. If canonicalization fails (e.g. because the
+/// file does not exist), the joined path is returned as-is so that we can report it. The
+/// exception is a cycle or broken chain of symbolic links, which we report right away with the
+/// links involved.
+fn resolve_path(path: &Path) -> PathBuf {
+    let path = crate_root_dir().join(path);
+    dunce::canonicalize(&path).unwrap_or_else(|_| {
+        if let Err(err) = symlink::check(&path) {
+            panic!("Failed to resolve {}: {}", path.display(), err);
+        }
+        path
+    })
+}
 
-    // Then inject a doc string that "resolves" the image reference and supplies the
-    // base64-encoded data inline
-    let mut input: syn::Item = syn::parse_macro_input!(item);
-    match input {
-        Item::Const(ItemConst { ref mut attrs, .. })
-        | Item::Enum(ItemEnum { ref mut attrs, .. })
-        | Item::ExternCrate(ItemExternCrate { ref mut attrs, .. })
-        | Item::Fn(ItemFn { ref mut attrs, .. })
-        | Item::ForeignMod(ItemForeignMod { ref mut attrs, .. })
-        | Item::Impl(ItemImpl { ref mut attrs, .. })
-        | Item::Macro(ItemMacro { ref mut attrs, .. })
-        | Item::Macro2(ItemMacro2 { ref mut attrs, .. })
-        | Item::Mod(ItemMod { ref mut attrs, .. })
-        | Item::Static(ItemStatic { ref mut attrs, .. })
-        | Item::Struct(ItemStruct { ref mut attrs, .. })
-        | Item::Trait(ItemTrait { ref mut attrs, .. })
-        | Item::TraitAlias(ItemTraitAlias { ref mut attrs, .. })
-        | Item::Type(ItemType { ref mut attrs, .. })
-        | Item::Union(ItemUnion { ref mut attrs, .. })
-        | Item::Use(ItemUse { ref mut attrs, .. }) => {
-            let str = doc_string;
-            // Insert an empty doc line to ensure that we get a blank line between the
-            // docs and the "bibliography" containing the actual image data.
-            // Otherwise the markdown parser will mess up our output.
-            attrs.push(syn::parse_quote! {
-                #[doc = ""]
-            });
-            attrs.push(syn::parse_quote! {
-                #[doc = #str]
-            });
-            input.into_token_stream()
+/// Prints a warning during compilation.
+///
+/// Proc macros cannot emit proper compiler warnings on stable Rust, so this is printed to stderr.
+fn emit_warning(message: &str) {
+    eprintln!("warning: embed-doc-image: {}", message);
+}
+
+/// Collects the paths, relative to the crate root, of all files in `dir` with a recognized image
+/// extension.
+fn collect_image_files(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) {
+    let full_dir = resolve_path(dir);
+    let entries = std::fs::read_dir(&full_dir)
+        .unwrap_or_else(|_| panic!("Failed to read image directory {}", full_dir.display()));
+    for entry in entries {
+        let entry = entry
+            .unwrap_or_else(|_| panic!("Failed to read image directory {}", full_dir.display()));
+        let path = dir.join(entry.file_name());
+        let file_type = entry.file_type().unwrap_or_else(|_| {
+            panic!(
+                "Failed to determine file type of {}",
+                entry.path().display()
+            )
+        });
+        if file_type.is_dir() {
+            if recursive {
+                collect_image_files(&path, recursive, files);
+            }
+        } else if path
+            .extension()
+            .and_then(|ext| mime_type_for_extension(&ext.to_string_lossy()))
+            .filter(|mime| mime.starts_with("image/"))
+            .is_some()
+        {
+            files.push(path);
         }
-        _ => syn::Error::new_spanned(
-            input,
-            "Unsupported item. Cannot apply attribute to the given item.",
+    }
+}
+
+fn image_mime_type(image_desc: &ImageDescription) -> String {
+    let ext = image_desc.path.extension().unwrap_or_else(|| {
+        panic!(
+            "No extension for image ({}). Unable to determine MIME type.",
+            image_desc
         )
-        .to_compile_error(),
+    });
+    determine_mime_type(image_desc, &ext.to_string_lossy())
+}
+
+fn data_uri(mime: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, dedup::encode(bytes))
+}
+
+/// Checks that a label matches the pattern set by `EMBED_DOC_IMAGE_LABEL_PATTERN`, if any.
+fn check_label(label: &str) {
+    let config = EmbedDocImageConfig::from_env();
+    if !config.label_matches(label) {
+        panic!(
+            "Label '{}' does not match the pattern `{}` set by {}",
+            label,
+            config.label_pattern.as_deref().unwrap_or_default(),
+            config::LABEL_PATTERN_VAR
+        );
+    }
+}
+
+fn produce_doc_string_for_image_data(label: &str, mime: &str, bytes: &[u8]) -> String {
+    check_label(label);
+    format!(" [{}]: {}", label, data_uri(mime, bytes))
+}
+
+/// Escapes a string for use as HTML text or attribute value.
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes a string for use in a double-quoted CSS string inside a `<style>` element.
+fn escape_css_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | '"' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // Avoid closing the `<style>` element, and avoid line breaks in the doc string
+            '<' | '\n' | '\r' => escaped.push_str(&format!("\\{:x} ", c as u32)),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether a character may appear in the HTML `id`s and CSS class names that are derived from
+/// labels, which are then used in CSS selectors and URL fragments without escaping.
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Checks that a label can be used as an HTML `id`, i.e. that it is non-empty and only contains
+/// ASCII letters, digits, `-` and `_`.
+fn check_id_label(label: &str, span: proc_macro2::Span) -> parse::Result<()> {
+    if label.is_empty() || !label.chars().all(is_id_char) {
+        return Err(syn::Error::new(
+            span,
+            "The label is used as an HTML `id`, and may only contain ASCII letters, digits, `-` \
+             and `_`.",
+        ));
+    }
+    Ok(())
+}
+
+/// Produces a doc string for the image, embedding the file exactly as it is.
+fn produce_doc_string_for_raw_image(image_desc: &ImageDescription) -> String {
+    check_label(&image_desc.label);
+    let mime = image_mime_type(image_desc);
+    let encoded = encode_base64_image(image_desc);
+    format!(
+        " [{label}]: data:{mime};base64,{encoded}",
+        label = &image_desc.label,
+        mime = mime,
+        encoded = &encoded
+    )
+}
+
+/// Produces a doc string for the image, processing PNG and SVG images as described in
+/// [`prepare_image`].
+///
+/// Processed images are read into memory in full, and encoded from there. All other images are
+/// encoded directly from file, see [`encode_base64_image`].
+fn produce_doc_string_for_image(image_desc: &ImageDescription) -> String {
+    let mime = image_mime_type(image_desc);
+    if mime == "image/png" || mime == "image/svg+xml" {
+        // PNG files need to be processed as a whole to strip metadata, and SVG files to check
+        // that they are well-formed and to apply the configured rounding and sanitizing
+        let bytes = read_image(image_desc);
+        produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes)
+    } else {
+        produce_doc_string_for_raw_image(image_desc)
+    }
+}
+
+/// Produces a doc string for inclusion in Markdown documentation.
+///
+/// Several images may be embedded at once by separating label/path pairs with semicolons, e.g.
+/// `embed_image!("a", "images/a.png"; "b", "images/b.png")`.
+///
+/// Please see the crate-level documentation for usage instructions.
+#[proc_macro]
+pub fn embed_image(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| -> parse::Result<_> {
+        let images = input.parse_terminated::<_, syn::Token![;]>(ImageDescription::parse)?;
+        if images.is_empty() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Expected a label and an image path.",
+            ));
+        }
+        Ok(images)
+    };
+    let images = syn::parse_macro_input!(item with parser);
+    if !should_embed() {
+        images.iter().for_each(check_image_exists);
+        return quote!("").into();
+    }
+    let doc_strings: Vec<_> = images.iter().map(produce_doc_string_for_image).collect();
+
+    // Ensure that the "image table" at the end is separated from the rest of the documentation,
+    // otherwise the markdown parser will not treat them as a "lookup table" for the image data
+    let s = format!("\n \n {}", doc_strings.join("\n"));
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, and writes the embedded image
+/// to disk for inspection if `EMBED_DOC_IMAGE_DEBUG` is set.
+///
+/// This works like [`embed_image!`](macro@embed_image). In addition, if the environment variable
+/// `EMBED_DOC_IMAGE_DEBUG` is `true`, the embedded data is written to
+/// `embed-doc-image-debug/<label>.<extension>` in the target directory, where any character of
+/// the label other than ASCII letters, digits, `-` and `_` is replaced by `-`. This shows the
+/// image exactly as it is embedded, e.g. after metadata has been stripped, without having to
+/// build the documentation. Without the variable, nothing is written.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_debug_output!("diagram", "images/diagram.png")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_debug_output(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    if EmbedDocImageConfig::from_env().debug {
+        let name = sanitize_label(&image_desc.label);
+        let extension = image_desc.path.extension().unwrap_or_default();
+        let dir = tools::target_dir(&crate_root_dir()).join("embed-doc-image-debug");
+        let path = dir.join(name).with_extension(extension);
+        std::fs::create_dir_all(&dir)
+            .and_then(|()| std::fs::write(&path, &bytes))
+            .unwrap_or_else(|err| {
+                panic!(
+                    "Failed to write image ({}) to {}: {}",
+                    image_desc,
+                    path.display(),
+                    err
+                )
+            });
+    }
+    // The image is written even if it is not embedded, since that is the point of the macro
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, asserting that the image file
+/// was modified recently.
+///
+/// This works like [`embed_image!`](macro@embed_image), but fails to compile if the file was
+/// last modified more than `max_age_secs` seconds ago. This is meant for images generated by
+/// earlier steps of a CI pipeline: if such a step is skipped or fails silently, the build fails
+/// instead of embedding an image left over from a previous run. Note that the check only runs
+/// when the macro is expanded, which Cargo does not repeat merely because time has passed, and
+/// that checking out a repository gives all files a fresh modification time. It is therefore
+/// only meaningful for files that are not under version control.
+///
+/// ```rust,ignore
+/// #[doc = embed_doc_image::embed_image_max_file_age!(
+///     "plot",
+///     "generated/plot.png",
+///     max_age_secs = 3600
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_max_file_age(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let max_age = (|| -> parse::Result<u64> {
+        let max_age = options.take_int("max_age_secs")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing maximum age, e.g. `max_age_secs = 3600`.",
+            )
+        })?;
+        options.finish()?;
+        Ok(max_age)
+    })();
+    let max_age = match max_age {
+        Ok(max_age) => max_age,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let path = resolve_path(&image_desc.path);
+    let modified = std::fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or_else(|err| {
+            panic!(
+                "Failed to determine the modification time of image ({}): {}",
+                image_desc, err
+            )
+        });
+    // Files modified in the future, e.g. due to clock skew, count as new
+    let age = modified.elapsed().map_or(0, |age| age.as_secs());
+    if age > max_age {
+        let message = format!(
+            "Image ({}) was last modified {} seconds ago, which exceeds the maximum age of {} \
+             seconds. Was the step that generates it skipped?",
+            image_desc, age, max_age
+        );
+        return syn::Error::new(proc_macro2::Span::call_site(), message)
+            .to_compile_error()
+            .into();
+    }
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let doc_string = produce_doc_string_for_image(&image_desc);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, asserting the format of the
+/// image.
+///
+/// This works like [`embed_image!`](macro@embed_image), but additionally checks the contents of
+/// the file against the MIME type given as `expected_mime`, and fails to compile if they do not
+/// match. This guards against an image being replaced by one in a different format, e.g. a PNG
+/// file by a JPEG file with the same name, which would otherwise go unnoticed.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_assert_format!(
+///     "diagram",
+///     "images/diagram.png",
+///     expected_mime = "image/png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_assert_format(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let expected_mime = (|| -> parse::Result<syn::LitStr> {
+        let expected_mime = options.take_str("expected_mime")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing expected MIME type, e.g. `expected_mime = \"image/png\"`.",
+            )
+        })?;
+        options.finish()?;
+        Ok(expected_mime)
+    })();
+    let expected_mime = match expected_mime {
+        Ok(expected_mime) => expected_mime,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let bytes = read_image(&image_desc);
+    let actual_mime = magic::sniff_mime_type(&bytes);
+    if actual_mime != Some(expected_mime.value().as_str()) {
+        let message = format!(
+            "Expected image ({}) to be {}, but its contents are {}",
+            image_desc,
+            expected_mime.value(),
+            actual_mime.unwrap_or("of an unrecognized format")
+        );
+        return syn::Error::new(expected_mime.span(), message)
+            .to_compile_error()
+            .into();
+    }
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let doc_string =
+        produce_doc_string_for_image_data(&image_desc.label, &image_mime_type(&image_desc), &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, enforcing a maximum size of
+/// the image.
+///
+/// This works like [`embed_image!`](macro@embed_image), but fails to compile if the image is
+/// wider than `max_width` or taller than `max_height` pixels. At least one of the limits must be
+/// given. The dimensions are read from the header of the file, and it is an error if they cannot
+/// be determined, e.g. for SVG files without an absolute size or `viewBox`.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_max_dimensions!(
+///     "diagram",
+///     "images/diagram.png",
+///     max_width = 800,
+///     max_height = 600
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_max_dimensions(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let limits = (|| -> parse::Result<(Option<u32>, Option<u32>)> {
+        let max_width = options.take_int("max_width")?;
+        let max_height = options.take_int("max_height")?;
+        options.finish()?;
+        if max_width.is_none() && max_height.is_none() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing limit, e.g. `max_width = 800` or `max_height = 600`.",
+            ));
+        }
+        Ok((max_width, max_height))
+    })();
+    let (max_width, max_height) = match limits {
+        Ok(limits) => limits,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let (width, height) = match dimensions::image_dimensions(&mime, &bytes) {
+        Some(dimensions) => dimensions,
+        None => {
+            return syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!(
+                    "Unable to determine the dimensions of image ({}) to check them against the \
+                     limits. The header of the file may be truncated or malformed.",
+                    image_desc
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    let mut violations = Vec::new();
+    if let Some(max_width) = max_width.filter(|&max_width| width > max_width) {
+        violations.push(format!("width must not exceed {} pixels", max_width));
+    }
+    if let Some(max_height) = max_height.filter(|&max_height| height > max_height) {
+        violations.push(format!("height must not exceed {} pixels", max_height));
+    }
+    if !violations.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            format!(
+                "Image ({}) is {}x{} pixels, but its {}.",
+                image_desc,
+                width,
+                height,
+                violations.join(" and ")
+            ),
+        )
+        .to_compile_error()
+        .into();
+    }
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Renders an SVG image to PNG with Inkscape, at the given resolution in dots per inch.
+///
+/// Rendered images are cached in the target directory, keyed by the path and modification time
+/// of the SVG image and the resolution.
+fn render_with_inkscape(image_desc: &ImageDescription, dpi: u32) -> Result<Vec<u8>, String> {
+    let input = resolve_path(&image_desc.path);
+    let modified = std::fs::metadata(&input)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|err| format!("Failed to read {}: {}", input.display(), err))?;
+    let modified = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let key = {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+        // The key only needs to be stable for as long as the cache is, so the standard hasher
+        // will do, which does not depend on the `content-hash` feature
+        let mut hasher = DefaultHasher::new();
+        (&input, modified, dpi).hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    };
+    let cache_dir = tools::target_dir(&crate_root_dir())
+        .join("embed-doc-image")
+        .join("inkscape");
+    let cached = cache_dir.join(format!("{}.png", key));
+    if let Ok(png) = std::fs::read(&cached) {
+        return Ok(png);
+    }
+
+    let png = tools::run(
+        "inkscape",
+        [
+            std::ffi::OsString::from("--export-type=png"),
+            format!("--export-dpi={}", dpi).into(),
+            "--export-filename=-".into(),
+            input.into_os_string(),
+        ],
+    )?;
+    if !png::is_png(&png) {
+        return Err("`inkscape` did not produce a PNG image".to_string());
+    }
+    // Failing to cache the image only costs time on the next build
+    let _ = std::fs::create_dir_all(&cache_dir).and_then(|()| std::fs::write(&cached, &png));
+    Ok(png)
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, rendering an SVG image to PNG
+/// with [Inkscape](https://inkscape.org).
+///
+/// Some SVG images use features that only Inkscape supports, such as flowed text, and are not
+/// displayed correctly by browsers. This macro renders such images with the `inkscape` command
+/// line tool (version 1.0 or later) at the resolution given by `dpi` (96 by default), and embeds
+/// the resulting PNG image. Compilation fails if `inkscape` is not found in `PATH`. Rendered
+/// images are cached in the target directory, so unchanged images are not rendered again.
+///
+/// ```rust,ignore
+/// #[doc = embed_doc_image::embed_image_from_inkscape!("diagram", "images/diagram.svg", dpi = 150)]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_from_inkscape(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let dpi = (|| -> parse::Result<u32> {
+        let dpi = match options.take_int("dpi")? {
+            Some(0) => {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "The resolution must be positive.",
+                ));
+            }
+            Some(dpi) => dpi,
+            None => 96,
+        };
+        options.finish()?;
+        Ok(dpi)
+    })();
+    let dpi = match dpi {
+        Ok(dpi) => dpi,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !is_svg_path(&image_desc.path) {
+        panic!("Image ({}) is not an SVG image", image_desc);
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    // Track the image as for all other macros, and check that it is well-formed
+    read_image(&image_desc);
+    let png = match render_with_inkscape(&image_desc, dpi) {
+        Ok(png) => png,
+        Err(err) => {
+            let message = format!(
+                "Failed to render image ({}) with Inkscape: {}",
+                image_desc, err
+            );
+            return syn::Error::new(proc_macro2::Span::call_site(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, "image/png", &png);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, using the output of a command
+/// as the image.
+///
+/// Many tools, such as Graphviz `dot`, PlantUML or `gnuplot`, can write images to stdout. This
+/// macro runs the command `cmd` with the arguments `args` in the crate root when the
+/// documentation is built, and embeds its output. The MIME type is given with the `mime`
+/// option, or otherwise inferred from the output. Compilation fails if the command is not found
+/// in `PATH` or exits unsuccessfully, in which case its stderr is included in the error.
+///
+/// Files passed in `args` are not tracked: a macro used as `#[doc = ...]` can only expand to a
+/// string, not to the `include_bytes!` constants that make the compiler record files as
+/// dependencies, so changes to the input files alone do not cause the crate to be rebuilt. Track
+/// their directory with [`embed_image_track_dir!`](macro@embed_image_track_dir), or print
+/// `cargo:rerun-if-changed` for them from a build script.
+///
+/// Keep in mind that the command also runs on docs.rs, where most tools are not installed.
+///
+/// ```rust,ignore
+/// #[doc = embed_doc_image::embed_image_from_command!(
+///     "graph",
+///     cmd = "dot",
+///     args = ["-Tsvg", "images/graph.dot"],
+///     mime = "image/svg+xml"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_from_command(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| -> parse::Result<_> {
+        let label = input.parse::<syn::LitStr>()?;
+        let (args, options) = Options::parse_with(input, "args", |input| {
+            let content;
+            syn::bracketed!(content in input);
+            let args = content
+                .parse_terminated::<syn::LitStr, syn::Token![,]>(<syn::LitStr as Parse>::parse)?;
+            Ok(args.into_iter().map(|arg| arg.value()).collect::<Vec<_>>())
+        })?;
+        Ok((label, args.unwrap_or_default(), options))
+    };
+    let (label, args, mut options) = syn::parse_macro_input!(item with parser);
+    let settings = (|| -> parse::Result<(syn::LitStr, Option<String>)> {
+        let cmd = options.take_str("cmd")?.ok_or_else(|| {
+            syn::Error::new(
+                label.span(),
+                "Missing command, e.g. `cmd = \"dot\", args = [\"-Tsvg\", \"graph.dot\"]`.",
+            )
+        })?;
+        let mime = options.take_str("mime")?.map(|mime| mime.value());
+        options.finish()?;
+        Ok((cmd, mime))
+    })();
+    let (cmd, mime) = match settings {
+        Ok(settings) => settings,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let root = crate_root_dir();
+    let program = match tools::find_in_path(&cmd.value(), &root) {
+        Some(program) => program,
+        None => {
+            let message = format!(
+                "`{}` was not found. Make sure that it is installed and in `PATH`.",
+                cmd.value()
+            );
+            return syn::Error::new(cmd.span(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+    let bytes = match tools::run_in(&root, program.as_os_str(), &args) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            return syn::Error::new(cmd.span(), format!("Failed to generate image: {}", err))
+                .to_compile_error()
+                .into();
+        }
+    };
+    let mime = match mime.or_else(|| magic::sniff_mime_type(&bytes).map(str::to_string)) {
+        Some(mime) => mime,
+        None => {
+            let message = format!(
+                "Unable to determine the MIME type of the output of `{}`. Specify it with the \
+                 `mime` option, e.g. `mime = \"image/svg+xml\"`.",
+                cmd.value()
+            );
+            return syn::Error::new(cmd.span(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+    let doc_string = produce_doc_string_for_image_data(&label.value(), &mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, rendering a
+/// [Graphviz](https://graphviz.org) graph.
+///
+/// The graph is rendered from the given `.dot` file by the `dot` command line tool of Graphviz,
+/// which must be installed and in `PATH` when the documentation is built. By default, the graph
+/// is embedded as SVG. With `format = "png"`, it is embedded as PNG instead, e.g. if the fonts
+/// used in the graph are not available to all readers.
+///
+/// The `.dot` file is not tracked: a macro used as `#[doc = ...]` can only expand to a string,
+/// not to the `include_bytes!` constant that makes the compiler record a file as a dependency,
+/// so changes to the graph alone do not cause the crate to be rebuilt. Track the directory of
+/// the graph with [`embed_image_track_dir!`](macro@embed_image_track_dir), or print
+/// `cargo:rerun-if-changed` for the file from a build script.
+///
+/// ```rust,ignore
+/// #[doc = embed_doc_image::embed_image_from_dot!("states", "diagrams/states.dot")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_from_dot(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let as_png = (|| -> parse::Result<bool> {
+        let as_png = match options.take_str("format")? {
+            Some(format) => match format.value().to_ascii_lowercase().as_str() {
+                "svg" => false,
+                "png" => true,
+                _ => {
+                    return Err(syn::Error::new(
+                        format.span(),
+                        "Unsupported format. Expected `svg` or `png`.",
+                    ));
+                }
+            },
+            None => false,
+        };
+        options.finish()?;
+        Ok(as_png)
+    })();
+    let as_png = match as_png {
+        Ok(as_png) => as_png,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let root = crate_root_dir();
+    let dot = match tools::find_in_path("dot", &root) {
+        Some(dot) => dot,
+        None => {
+            return syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Graphviz `dot` was not found. Make sure that Graphviz is installed and that `dot` \
+                 is in `PATH`.",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    let input = resolve_path(&image_desc.path);
+    if !input.is_file() {
+        panic!(
+            "Failed to load graph ({}) at {}",
+            image_desc,
+            input.display()
+        );
+    }
+    let (flag, mime) = if as_png {
+        ("-Tpng", "image/png")
+    } else {
+        ("-Tsvg", "image/svg+xml")
+    };
+    let args = [std::ffi::OsStr::new(flag), input.as_os_str()];
+    let bytes = match tools::run_in(&root, dot.as_os_str(), args) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            let message = format!("Failed to render graph ({}): {}", image_desc, err);
+            return syn::Error::new(proc_macro2::Span::call_site(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+    let bytes = if as_png {
+        bytes
+    } else {
+        if let Err(err) = svg::validate(&bytes) {
+            panic!(
+                "Graphviz produced a malformed SVG image for ({}): {}",
+                image_desc, err
+            );
+        }
+        // Treat the output like any other SVG image
+        if EmbedDocImageConfig::from_env().sanitize_svg {
+            let svg = String::from_utf8_lossy(&bytes).into_owned();
+            svg::strip_elements(&svg, "script").into_bytes()
+        } else {
+            bytes
+        }
+    };
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, rendering a
+/// [PlantUML](https://plantuml.com) diagram as SVG.
+///
+/// The diagram is rendered from the given file with the `plantuml` command if it is in `PATH`.
+/// Otherwise, with the `plantuml-server` feature, it is sent to a PlantUML server with `curl`.
+/// The server is `https://www.plantuml.com/plantuml` unless `EMBED_DOC_IMAGE_PLANTUML_SERVER`
+/// gives the URL of another one, e.g. one run locally, and requests time out after the number
+/// of seconds given by `EMBED_DOC_IMAGE_PLANTUML_TIMEOUT` (10 by default). Images rendered by
+/// the server are cached in the target directory, and the cached image is used if the server
+/// cannot be reached later on. Note that the server receives the source of the diagram.
+///
+/// ```rust,ignore
+/// #[doc = embed_doc_image::embed_image_from_plantuml!("login", "diagrams/login.puml")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_from_plantuml(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+
+    let input = resolve_path(&image_desc.path);
+    if !input.is_file() {
+        panic!(
+            "Failed to load diagram ({}) at {}",
+            image_desc,
+            input.display()
+        );
+    }
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let svg = match plantuml::render(&crate_root_dir(), &input) {
+        Ok((svg, warning)) => {
+            if let Some(warning) = warning {
+                emit_warning(&format!("Diagram ({}): {}", image_desc, warning));
+            }
+            svg
+        }
+        Err(err) => {
+            let message = format!("Failed to render diagram ({}): {}", image_desc, err);
+            return syn::Error::new(proc_macro2::Span::call_site(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+    // Treat the output like any other SVG image
+    let rendered = ImageDescription {
+        label: image_desc.label.clone(),
+        path: image_desc.path.with_extension("svg"),
+    };
+    let svg = prepare_image(&rendered, svg);
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, "image/svg+xml", &svg);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Retrieves the contents of a file, given relative to the crate root, at a git revision.
+fn read_at_revision(path: &Path, revision: &str) -> Result<Vec<u8>, String> {
+    // Git expects forward slashes, and paths starting with `./` to be relative to the working
+    // directory rather than the repository root
+    let components: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    let object = format!("{}:./{}", revision, components.join("/"));
+    tools::run(
+        "git",
+        [
+            std::ffi::OsStr::new("-C"),
+            crate_root_dir().as_os_str(),
+            std::ffi::OsStr::new("show"),
+            std::ffi::OsStr::new(&object),
+        ],
+    )
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, using an image as it was at a
+/// given git revision.
+///
+/// This works like [`embed_image!`](macro@embed_image), except that the image is retrieved with
+/// `git show <revision>:<path>` instead of being read from the working tree. This is useful for
+/// showing e.g. what an output looked like in an earlier release. The path is relative to the
+/// crate root as usual, and `git` must be available in `PATH`.
+///
+/// Cargo cannot track changes to git history, so the image is only retrieved again when the
+/// crate is rebuilt for other reasons.
+///
+/// ```rust,ignore
+/// #[doc = embed_doc_image::embed_image_at_revision!(
+///     "screenshot",
+///     "images/screenshot.png",
+///     revision = "v1.2.3"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_at_revision(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let revision = (|| -> parse::Result<String> {
+        let revision = options.take_str("revision")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing revision, e.g. `revision = \"v1.2.3\"`.",
+            )
+        })?;
+        options.finish()?;
+        if revision.value().is_empty() || revision.value().starts_with('-') {
+            return Err(syn::Error::new(revision.span(), "Invalid revision."));
+        }
+        Ok(revision.value())
+    })();
+    let revision = match revision {
+        Ok(revision) => revision,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    // The image need not exist in the working tree, so there is nothing to check
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let bytes = read_at_revision(&image_desc.path, &revision).unwrap_or_else(|err| {
+        panic!(
+            "Failed to retrieve image ({}) at revision {}: {}",
+            image_desc, revision, err
+        )
+    });
+    let bytes = prepare_image(&image_desc, bytes);
+    let mime = image_mime_type(&image_desc);
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, scaling the image down if it
+/// exceeds the given size.
+///
+/// This works like [`embed_image!`](macro@embed_image) for images no wider than `max_width` and
+/// no taller than `max_height` pixels. Larger images are scaled down at compile time to fit,
+/// keeping their proportions, so that e.g. screenshots can be committed at full resolution.
+/// Scaled JPEG images are re-encoded as JPEG, and all other images as PNG. Only the first frame
+/// of animated images is kept when scaling. This requires the `image` feature, and does not
+/// support SVG images, which can be scaled by the browser instead.
+///
+/// ```rust
+/// # #[cfg(feature = "image")]
+/// #[doc = embed_doc_image::embed_image_shrink_to_fit!(
+///     "screenshot",
+///     "images/screenshot.png",
+///     max_width = 1200,
+///     max_height = 900
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_shrink_to_fit(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let limits = (|| -> parse::Result<(u32, u32)> {
+        let mut take_limit = |key: &str| -> parse::Result<u32> {
+            match options.take_int(key)? {
+                Some(0) => Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    format!("`{}` must be positive.", key),
+                )),
+                Some(limit) => Ok(limit),
+                None => Ok(u32::MAX),
+            }
+        };
+        let max_width = take_limit("max_width")?;
+        let max_height = take_limit("max_height")?;
+        options.finish()?;
+        if max_width == u32::MAX && max_height == u32::MAX {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing limit, e.g. `max_width = 1200` or `max_height = 900`.",
+            ));
+        }
+        Ok((max_width, max_height))
+    })();
+    let (max_width, max_height) = match limits {
+        Ok(limits) => limits,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let as_jpeg = mime == "image/jpeg";
+    let shrunk = raster::shrink_to_fit(&bytes, max_width, max_height, as_jpeg)
+        .unwrap_or_else(|err| panic!("Unable to scale image ({}): {}", image_desc, err));
+    let doc_string = match shrunk {
+        Some(shrunk) => {
+            let mime = if as_jpeg { "image/jpeg" } else { "image/png" };
+            produce_doc_string_for_image_data(&image_desc.label, mime, &shrunk)
+        }
+        None => produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes),
+    };
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, making the background of the
+/// image transparent.
+///
+/// Images exported with a white or colored background look out of place on the dark rustdoc
+/// themes. This macro makes every pixel whose color is close to `bg_color` (`"#FFFFFF"` by
+/// default) fully transparent and embeds the result as PNG. `tolerance` (0 to 255, 10 by
+/// default) is the largest difference in each of the red, green and blue components for which
+/// a pixel still counts as background. Note that all matching pixels are affected, not only
+/// those connected to the edges of the image, so a white fill inside a shape becomes transparent
+/// as well. Only the first frame of animated images is kept. This requires the `image` feature.
+///
+/// ```rust
+/// # #[cfg(feature = "image")]
+/// #[doc = embed_doc_image::embed_image_transparent_bg!(
+///     "diagram",
+///     "images/diagram.png",
+///     bg_color = "#FFFFFF",
+///     tolerance = 10
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_transparent_bg(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let settings = (|| -> parse::Result<(raster::Rgb, u8)> {
+        let background = match options.take_str("bg_color")? {
+            Some(color) => parse_hex_color(&color.value()).ok_or_else(|| {
+                syn::Error::new(
+                    color.span(),
+                    "Invalid color. Expected a hex color such as `#ffffff`.",
+                )
+            })?,
+            None => [255, 255, 255],
+        };
+        let tolerance = options.take_int("tolerance")?.unwrap_or(10);
+        options.finish()?;
+        Ok((background, tolerance))
+    })();
+    let (background, tolerance) = match settings {
+        Ok(settings) => settings,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let png = raster::make_transparent(&bytes, background, tolerance)
+        .unwrap_or_else(|err| panic!("Failed to process image ({}): {}", image_desc, err));
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, "image/png", &png);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, replacing transparency in the
+/// image with an opaque background.
+///
+/// Images with a transparent background that were drawn for a light background can be hard to
+/// read on the dark rustdoc themes, where the dark page background shows through. This macro
+/// composites the image onto `bg_color` (`"#FFFFFF"` by default) and embeds the result as PNG
+/// without an alpha channel, which also makes it smaller. Only the first frame of animated images
+/// is kept. This requires the `image` feature. For images that should adapt to dark themes
+/// instead, see [`embed_image_dark_mode_invert!`](macro@embed_image_dark_mode_invert).
+///
+/// ```rust
+/// # #[cfg(feature = "image")]
+/// #[doc = embed_doc_image::embed_image_strip_alpha!(
+///     "diagram",
+///     "images/transparent.png",
+///     bg_color = "#FFFFFF"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_strip_alpha(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let background = (|| -> parse::Result<raster::Rgb> {
+        let background = match options.take_str("bg_color")? {
+            Some(color) => parse_hex_color(&color.value()).ok_or_else(|| {
+                syn::Error::new(
+                    color.span(),
+                    "Invalid color. Expected a hex color such as `#ffffff`.",
+                )
+            })?,
+            None => [255, 255, 255],
+        };
+        options.finish()?;
+        Ok(background)
+    })();
+    let background = match background {
+        Ok(background) => background,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let png = raster::flatten(&bytes, background)
+        .unwrap_or_else(|err| panic!("Failed to process image ({}): {}", image_desc, err));
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, "image/png", &png);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, converting the image to another
+/// format.
+///
+/// Browsers cannot display some formats, such as TIFF images from scientific workflows, or
+/// display them inconsistently, such as BMP and ICO files. This macro decodes the image at
+/// compile time and embeds it in the format given by `target_format`, which is one of `png`,
+/// `webp` (lossless) and `jpeg`. For ICO files, the largest image is used. Animated GIF images
+/// are rejected, since only their first frame would be kept; use
+/// [`embed_image_gif_to_webp!`](macro@embed_image_gif_to_webp) for those instead. This requires
+/// the `image` feature.
+///
+/// ```rust
+/// # #[cfg(feature = "image")]
+/// #[doc = embed_doc_image::embed_image_format_convert!(
+///     "micrograph",
+///     "images/micrograph.tiff",
+///     target_format = "png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_format_convert(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let target = (|| -> parse::Result<raster::TargetFormat> {
+        let name = options.take_str("target_format")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing target format, e.g. `target_format = \"png\"`.",
+            )
+        })?;
+        options.finish()?;
+        raster::TargetFormat::NAMES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(&name.value()))
+            .map(|(_, format)| *format)
+            .ok_or_else(|| {
+                syn::Error::new(
+                    name.span(),
+                    "Unsupported target format. Expected `png`, `webp` or `jpeg`.",
+                )
+            })
+    })();
+    let target = match target {
+        Ok(target) => target,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let converted = raster::convert(&bytes, target)
+        .unwrap_or_else(|err| panic!("Failed to convert image ({}): {}", image_desc, err));
+    let doc_string =
+        produce_doc_string_for_image_data(&image_desc.label, target.mime(), &converted);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Returns the MIME type of a resource that may be referenced from an SVG image, i.e. an image,
+/// a stylesheet or a font used by a stylesheet, by its file extension.
+fn svg_resource_mime_type(extension: &str) -> Option<&'static str> {
+    if extension.eq_ignore_ascii_case("css") {
+        Some("text/css")
+    } else {
+        mime_type_for_extension(extension).or_else(|| font_mime_type(extension))
+    }
+}
+
+/// Loads a resource referenced from a file in `dir`, inlining the resources that it references
+/// in turn if it is an SVG image or stylesheet.
+///
+/// `stack` holds the files currently being inlined, which is used to detect cycles. Returns
+/// `None` for references that are not local files, such as absolute URLs and fragments.
+fn load_inlined_resource(
+    dir: &Path,
+    reference: &str,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Option<svg::Resource>, String> {
+    let is_url = reference
+        .split_once(':')
+        .filter(|(scheme, _)| !scheme.contains('/'))
+        .is_some();
+    let reference = reference.split(['#', '?']).next().unwrap_or_default();
+    if is_url || reference.is_empty() {
+        return Ok(None);
+    }
+    let path = dunce::canonicalize(dir.join(reference))
+        .map_err(|err| format!("failed to resolve {}: {}", reference, err))?;
+    if let Some(start) = stack.iter().position(|other| *other == path) {
+        let cycle: Vec<_> = stack[start..]
+            .iter()
+            .chain(Some(&path))
+            .map(|path| path.display().to_string())
+            .collect();
+        return Err(format!("circular reference: {}", cycle.join(" -> ")));
+    }
+    let mime = path
+        .extension()
+        .and_then(|ext| svg_resource_mime_type(&ext.to_string_lossy()))
+        .ok_or_else(|| format!("unrecognized extension for {}", path.display()))?;
+    let bytes = read(&path).map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+    let parent = path.parent().unwrap_or(dir).to_path_buf();
+    stack.push(path);
+    let bytes = match mime {
+        "image/svg+xml" => svg::inline_resources(&bytes, &mut |reference| {
+            load_inlined_resource(&parent, reference, stack)
+        }),
+        "text/css" => String::from_utf8(bytes)
+            .map_err(|_| "stylesheet is not UTF-8".to_string())
+            .and_then(|css| {
+                svg::inline_css_urls(&css, &mut |reference| {
+                    load_inlined_resource(&parent, reference, stack)
+                })
+            })
+            .map(String::into_bytes),
+        _ => Ok(bytes),
+    };
+    stack.pop();
+    Ok(Some(svg::Resource {
+        mime: mime.to_string(),
+        bytes: bytes?,
+    }))
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, with an SVG image whose
+/// external resources are embedded in it.
+///
+/// SVG images that reference other files, e.g. with `<image href="photo.jpg"/>`, are broken when
+/// embedded as a data URI, since browsers do not resolve such references for data URIs. This
+/// macro works like [`embed_image!`](macro@embed_image), but replaces references to local files
+/// relative to the SVG file with their contents. References in `href` and `xlink:href`
+/// attributes and in CSS `url(...)` functions become data URIs, and stylesheets included with
+/// `<link rel="stylesheet">` become `<style>` elements. Referenced SVG images and stylesheets are
+/// processed in the same way, and circular references are reported as errors. Absolute URLs and
+/// fragments are left as they are. This requires the `svg` feature.
+#[proc_macro]
+pub fn embed_image_self_contained_svg(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    if !is_svg_path(&image_desc.path) {
+        panic!("Image ({}) is not an SVG image", image_desc);
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let path = resolve_path(&image_desc.path);
+    let dir = path.parent().unwrap_or(&path).to_path_buf();
+    let mut stack = vec![path];
+    let bytes = svg::inline_resources(&read_image(&image_desc), &mut |reference| {
+        load_inlined_resource(&dir, reference, &mut stack)
+    })
+    .unwrap_or_else(|err| {
+        panic!(
+            "Failed to inline resources of SVG image ({}): {}",
+            image_desc, err
+        )
+    });
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, "image/svg+xml", &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, keeping all image metadata.
+///
+/// By default, textual and EXIF metadata is stripped from PNG files before they are embedded,
+/// since it often makes up a considerable part of the file without contributing to the image.
+/// This macro works exactly like [`embed_image!`](macro@embed_image), except that the image
+/// is embedded exactly as it is stored on disk.
+#[proc_macro]
+pub fn embed_image_with_metadata(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let doc_string = produce_doc_string_for_raw_image(&image_desc);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Removes data that changes on every export of an otherwise identical image.
+///
+/// This covers `tIME` chunks in PNG files and `<metadata>` elements in SVG files. Other formats
+/// are left untouched.
+fn make_reproducible(image_desc: &ImageDescription, mime: &str, bytes: Vec<u8>) -> Vec<u8> {
+    match mime {
+        "image/png" => png::retain_chunks(&bytes, |chunk| &chunk.kind != b"tIME")
+            .unwrap_or_else(|| panic!("Invalid PNG file for image ({})", image_desc)),
+        "image/svg+xml" => {
+            let svg = String::from_utf8(bytes)
+                .unwrap_or_else(|_| panic!("SVG file for image ({}) is not UTF-8", image_desc));
+            svg::strip_elements(&svg, "metadata").into_bytes()
+        }
+        _ => bytes,
+    }
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, with deterministic image data.
+///
+/// This works exactly like [`embed_image!`](macro@embed_image), except that timestamps and
+/// other metadata that typically change on every export (PNG `tIME` chunks and SVG `<metadata>`
+/// elements) are stripped before the image is encoded. This ensures that re-exporting the same
+/// logical image does not change the generated documentation.
+#[proc_macro]
+pub fn embed_image_reproducible(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let mime = image_mime_type(&image_desc);
+    let bytes = make_reproducible(&image_desc, &mime, read_image(&image_desc));
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, with an optimized SVG image.
+///
+/// This works like [`embed_image!`](macro@embed_image), except that the SVG file is made
+/// smaller before it is encoded, which is worthwhile for files exported from editors such as
+/// Inkscape. The XML declaration, comments, Inkscape- and Sodipodi-specific elements and
+/// attributes, and empty groups are removed, and whitespace in path data is collapsed. These
+/// transformations do not change how the image is rendered. No external tools are needed, but
+/// the `svg` feature must be enabled.
+#[proc_macro]
+pub fn embed_image_svg_optimize(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    let mime = image_mime_type(&image_desc);
+    if mime != "image/svg+xml" {
+        panic!("Image ({}) is not an SVG image", image_desc);
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = svg::optimize(&read_image(&image_desc))
+        .unwrap_or_else(|err| panic!("Failed to optimize SVG image ({}): {}", image_desc, err));
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Checks that alt text is actually descriptive, i.e. that it is not empty and does not merely
+/// repeat the label or file name of the image.
+fn validate_alt_text(image_desc: &ImageDescription, alt: &syn::LitStr) -> parse::Result<String> {
+    let value = alt.value();
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(syn::Error::new(alt.span(), "Alt text must not be empty."));
+    }
+
+    let mut lazy_candidates = vec![image_desc.label.clone()];
+    lazy_candidates.extend(
+        image_desc
+            .path
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned()),
+    );
+    lazy_candidates.extend(
+        image_desc
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned()),
+    );
+    let is_lazy = lazy_candidates
+        .iter()
+        .any(|candidate| candidate.eq_ignore_ascii_case(trimmed));
+    if is_lazy {
+        return Err(syn::Error::new(
+            alt.span(),
+            "Alt text should describe the image, not repeat its label or file name.",
+        ));
+    }
+    Ok(value)
+}
+
+/// Produces an HTML `<img>` tag with explicit alt text for inclusion in Markdown documentation.
+///
+/// Unlike with [`embed_image!`](macro@embed_image), the alt text is given at the embed site
+/// rather than at the usage site, which lets us verify at compile time that it is present and
+/// descriptive. The alt text must be non-empty and must not simply repeat the label or file name
+/// of the image.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_with_alt!(
+///     "graph",
+///     "images/diagram.png",
+///     alt = "A diagram showing three connected nodes"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_with_alt(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let alt = (|| -> parse::Result<String> {
+        let alt = options.take_str("alt")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing alt text, e.g. `alt = \"A diagram of ...\"`.",
+            )
+        })?;
+        options.finish()?;
+        validate_alt_text(&image_desc, &alt)
+    })();
+    let alt = match alt {
+        Ok(alt) => alt,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let s = format!(
+        "<img src=\"{}\" alt=\"{}\">",
+        data_uri(&mime, &bytes),
+        escape_html(&alt)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<img>` element whose alt text is taken from the description stored in the
+/// image file itself.
+///
+/// Image editors can store a description of the image in the file, namely in a `tEXt` or
+/// `iTXt` chunk with the keyword `Description` in PNG files, or in the `ImageDescription` EXIF
+/// tag in JPEG files. Reading EXIF data requires the `exif` feature. If the image has no
+/// description, the label is used as alt text instead, and a warning is printed, since labels
+/// rarely describe images well. In that case, consider giving the alt text explicitly with
+/// [`embed_image_with_alt!`](macro@embed_image_with_alt).
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_auto_alt!("graph", "images/diagram.png")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_auto_alt(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    // Metadata may have been stripped from the embedded image, so read it from the original file
+    let alt = metadata::description(&mime, &read_image_raw(&image_desc)).unwrap_or_else(|| {
+        let hint = if mime == "image/jpeg" && !cfg!(feature = "exif") {
+            " Reading the EXIF description of JPEG images requires the `exif` feature."
+        } else {
+            ""
+        };
+        emit_warning(&format!(
+            "Image ({}) has no description, so its label is used as alt text. Consider giving \
+             descriptive alt text with `embed_image_with_alt!`.{}",
+            image_desc, hint
+        ));
+        image_desc.label.clone()
+    });
+    let s = format!(
+        "<img src=\"{}\" alt=\"{}\">",
+        data_uri(&mime, &bytes),
+        escape_html(&alt)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// The MIME type of PDF documents. PDF documents are not images, so the shared table of MIME
+/// types leaves them out, and `embed_image!` rejects them.
+const PDF_MIME_TYPE: &str = "application/pdf";
+
+/// Produces an HTML `<object>` element embedding a PDF document.
+///
+/// PDFs are not images, but they can be embedded in exactly the same way, which is useful e.g.
+/// for crates that generate PDFs. The path is relative to the crate root, and the size of the
+/// embedded viewer can be adjusted with the optional `width` and `height` options (in pixels,
+/// 600x400 by default).
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_pdf!("spec", "assets/spec.pdf", width = 800, height = 600)]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_pdf(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let size = (|| -> parse::Result<(u32, u32)> {
+        let width = options.take_int("width")?.unwrap_or(600);
+        let height = options.take_int("height")?.unwrap_or(400);
+        options.finish()?;
+        Ok((width, height))
+    })();
+    let (width, height) = match size {
+        Ok(size) => size,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let is_pdf = image_desc
+        .path
+        .extension()
+        .filter(|ext| ext.eq_ignore_ascii_case("pdf"))
+        .is_some();
+    if !is_pdf {
+        panic!("Expected a PDF file for ({})", image_desc);
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let s = format!(
+        "<object type=\"{mime}\" data=\"{}\" width=\"{}\" height=\"{}\" \
+         aria-label=\"{}\"></object>",
+        data_uri(PDF_MIME_TYPE, &bytes),
+        width,
+        height,
+        escape_html(&image_desc.label),
+        mime = PDF_MIME_TYPE
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Validates that `version` is a semantic version.
+///
+/// Missing minor and patch components are allowed, so that e.g. `2.1` is accepted as `2.1.0`.
+#[cfg(feature = "semver")]
+fn validate_version(version: &syn::LitStr) -> parse::Result<()> {
+    let value = version.value();
+    let core_len = value.find(['-', '+']).unwrap_or(value.len());
+    let (core, rest) = value.split_at(core_len);
+    let missing = 2usize.saturating_sub(core.matches('.').count());
+    let padded = format!("{}{}{}", core, ".0".repeat(missing), rest);
+    semver::Version::parse(&padded).map(|_| ()).map_err(|err| {
+        syn::Error::new(
+            version.span(),
+            format!("Invalid version `{}`: {}", value, err),
+        )
+    })
+}
+
+#[cfg(not(feature = "semver"))]
+fn validate_version(version: &syn::LitStr) -> parse::Result<()> {
+    if version.value().trim().is_empty() {
+        return Err(syn::Error::new(
+            version.span(),
+            "Version must not be empty.",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a value can be placed in an HTML comment, which ends at the first `--`, on a
+/// single line of the doc string.
+fn check_comment_value(value: &str, span: proc_macro2::Span, what: &str) -> parse::Result<()> {
+    if value.contains("--") {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "{} `{}` must not contain `--`, which would end the HTML comment it is placed in.",
+                what, value
+            ),
+        ));
+    }
+    if value.chars().any(char::is_control) {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "{} {:?} must not contain line breaks or other control characters.",
+                what, value
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the SHA-256 hash of `bytes` in hexadecimal.
+///
+/// This requires the `content-hash` feature, which the features whose code calls this function
+/// enable. Macros that are always available check that the feature is enabled first.
+#[cfg(feature = "content-hash")]
+fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(not(feature = "content-hash"))]
+fn sha256_hex(_bytes: &[u8]) -> String {
+    unreachable!("SHA-256 hashes require the `content-hash` feature")
+}
+
+/// Fails with a compile error, unless the `content-hash` feature is enabled.
+fn require_content_hash(what: &str) -> parse::Result<()> {
+    if !cfg!(feature = "content-hash") {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            format!(
+                "{} requires the `content-hash` feature of `embed-doc-image`.",
+                what
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, annotated with the version of
+/// the image.
+///
+/// This works like [`embed_image!`](macro@embed_image), but additionally places an HTML comment
+/// of the form
+///
+/// ```text
+/// <!-- embed-doc-image: label=ferris version=2.1 sha256=... -->
+/// ```
+///
+/// in front of the image data, which lets documentation auditing tools extract which versions of
+/// which images are embedded in the rendered documentation. With the `semver` feature enabled,
+/// the version is validated as a semantic version (missing minor and patch components are
+/// allowed). Neither the label nor the version may contain `--`, which would end the comment,
+/// nor line breaks or other control characters.
+/// The `sha256` field, the SHA-256 hash of the embedded data, is only included with the
+/// `content-hash` feature.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_doc_image_version!("ferris", "images/ferris.png", version = "2.1")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_doc_image_version(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let version = (|| -> parse::Result<String> {
+        let version = options.take_str("version")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing version, e.g. `version = \"2.1\"`.",
+            )
+        })?;
+        options.finish()?;
+        validate_version(&version)?;
+        check_comment_value(&image_desc.label, proc_macro2::Span::call_site(), "Label")?;
+        check_comment_value(&version.value(), version.span(), "Version")?;
+        Ok(version.value())
+    })();
+    let version = match version {
+        Ok(version) => version,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let hash = if cfg!(feature = "content-hash") {
+        format!(" sha256={}", sha256_hex(&bytes))
+    } else {
+        String::new()
+    };
+    let comment = format!(
+        "<!-- embed-doc-image: label={} version={}{} -->",
+        image_desc.label, version, hash
+    );
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}\n \n {}", comment, doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, annotated with a hash of the
+/// image.
+///
+/// This works like [`embed_image!`](macro@embed_image), but additionally places an HTML comment
+/// of the form
+///
+/// ```text
+/// <!-- content-hash:sha256:... -->
+/// ```
+///
+/// in front of the image data. The hash is the SHA-256 hash of the embedded data, i.e. of the
+/// contents of the data URI, so tools can verify the integrity of embedded images and detect
+/// when they change between versions of the documentation without decoding them.
+///
+/// This macro requires the `content-hash` feature.
+#[proc_macro]
+pub fn embed_image_content_hash(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    if let Err(err) = require_content_hash("Hashing images") {
+        return err.to_compile_error().into();
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let comment = format!("<!-- content-hash:sha256:{} -->", sha256_hex(&bytes));
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}\n \n {}", comment, doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Reads an image for [`embed_image_hash_label!`](macro@embed_image_hash_label), returning it
+/// along with its label: the file name without extension, followed by the first 8 hexadecimal
+/// digits of the SHA-256 hash of the embedded data.
+fn hash_labeled_image(path: &syn::LitStr) -> (ImageDescription, Vec<u8>) {
+    let path = PathBuf::from(path.value());
+    let mut image_desc = ImageDescription {
+        label: file_stem_label(&path),
+        path,
+    };
+    let bytes = read_image(&image_desc);
+    image_desc.label = format!("{}-{}", image_desc.label, &sha256_hex(&bytes)[..8]);
+    (image_desc, bytes)
+}
+
+/// Produces a string literal with a label for the image that changes whenever the image does.
+///
+/// The label consists of the file name without its extension, followed by the first 8
+/// hexadecimal digits of the SHA-256 hash of the embedded data, e.g. `diagram-a1b2c3d4`.
+/// Anchors and URLs derived from the label therefore change when the image is updated, so that
+/// caches do not serve an outdated version. The image itself is embedded with
+/// [`embed_image_hash_labeled!`](macro@embed_image_hash_labeled), which uses the same label.
+/// Both macros require the `content-hash` feature.
+///
+/// ```rust
+/// use embed_doc_image::{embed_image_hash_label, embed_image_hash_labeled};
+///
+/// # #[cfg(feature = "content-hash")]
+/// #[doc = concat!("![Diagram][", embed_image_hash_label!("images/diagram.png"), "]")]
+/// #[doc = embed_image_hash_labeled!("images/diagram.png")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_hash_label(item: TokenStream) -> TokenStream {
+    let path = syn::parse_macro_input!(item as syn::LitStr);
+    if let Err(err) = require_content_hash("Hash labels") {
+        return err.to_compile_error().into();
+    }
+    let (image_desc, _) = hash_labeled_image(&path);
+    let label = image_desc.label;
+    let tokens = quote! {
+        #label
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, labeling the image with the
+/// label given by [`embed_image_hash_label!`](macro@embed_image_hash_label).
+///
+/// This works like [`embed_image!`](macro@embed_image), except that the label is derived from
+/// the file name and the contents of the image instead of being given explicitly.
+#[proc_macro]
+pub fn embed_image_hash_labeled(item: TokenStream) -> TokenStream {
+    let path = syn::parse_macro_input!(item as syn::LitStr);
+    if let Err(err) = require_content_hash("Hash labels") {
+        return err.to_compile_error().into();
+    }
+    if !should_embed() {
+        let path = PathBuf::from(path.value());
+        check_image_exists(&ImageDescription {
+            label: file_stem_label(&path),
+            path,
+        });
+        return quote!("").into();
+    }
+    let (image_desc, bytes) = hash_labeled_image(&path);
+    let mime = image_mime_type(&image_desc);
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, with gamma and color space
+/// information removed from the PNG image.
+///
+/// PNG files may contain `gAMA`, `sRGB`, `iCCP` and `cHRM` chunks, which browsers interpret
+/// differently. A diagram may therefore look different in Safari on macOS than in Chrome on
+/// Windows. This macro works like [`embed_image!`](macro@embed_image), but removes these chunks
+/// so that all browsers display the same colors. Only PNG images are supported.
+///
+/// To remove the chunks from all PNG images, set `EMBED_DOC_IMAGE_STRIP_PNG_GAMMA` instead, as
+/// described in the crate documentation.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_strip_gamma!("diagram", "images/diagram.png")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_strip_gamma(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let bytes = png::strip_gamma(&bytes)
+        .unwrap_or_else(|| panic!("Image ({}) is not a valid PNG file", image_desc));
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, "image/png", &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, with the coordinates in the
+/// SVG image rounded to fewer decimal places.
+///
+/// SVG images exported from vector editors often contain coordinates with ten or more decimal
+/// places, as in `d="M 123.4567891011 456.7891011121"`. This macro works like
+/// [`embed_image!`](macro@embed_image), but rounds the numbers in path data, transforms and
+/// geometric attributes to `digits` decimal places (2 by default), which typically reduces the
+/// size of the image by 20–40% without visible differences. Only SVG images are supported, and
+/// the `svg` feature must be enabled.
+///
+/// To round the coordinates of all SVG images, set `EMBED_DOC_IMAGE_SVG_PRECISION` instead, as
+/// described in the crate documentation.
+///
+/// ```rust
+/// # #[cfg(feature = "svg")]
+/// #[doc = embed_doc_image::embed_image_shrink_svg_precision!("diagram", "images/diagram.svg", digits = 2)]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_shrink_svg_precision(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let digits = (|| -> parse::Result<u8> {
+        let digits = options.take_int("digits")?.unwrap_or(2);
+        options.finish()?;
+        Ok(digits)
+    })();
+    let digits = match digits {
+        Ok(digits) => digits,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !is_svg_path(&image_desc.path) {
+        panic!("Image ({}) is not an SVG image", image_desc);
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let rounded = svg::round_numbers(&bytes, digits).unwrap_or_else(|err| {
+        panic!(
+            "Failed to round numbers in SVG image ({}): {}",
+            image_desc, err
+        )
+    });
+    let doc_string =
+        produce_doc_string_for_image_data(&image_desc.label, "image/svg+xml", &rounded);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Returns the MIME type of a font by its file extension, ignoring case, or `None` for other
+/// files.
+///
+/// Fonts are not images, so the shared table of MIME types leaves them out, and `embed_image!`
+/// rejects them.
+fn font_mime_type(extension: &str) -> Option<&'static str> {
+    match extension.to_ascii_lowercase().as_str() {
+        "woff" => Some("font/woff"),
+        "woff2" => Some("font/woff2"),
+        "ttf" => Some("font/ttf"),
+        "otf" => Some("font/otf"),
+        _ => None,
+    }
+}
+
+/// Produces an HTML snippet embedding a font and demonstrating it on a sample text.
+///
+/// Although this crate is about images, fonts can be embedded in the same way, which is useful
+/// for crates that document fonts or typographic conventions. The font is registered with
+/// `@font-face` under the given label as its font family, so it can also be used elsewhere in
+/// the same page, e.g. in inline SVG. The optional `sample` option sets the text used to
+/// demonstrate the font (`"Sample Text"` by default). WOFF, WOFF2, TTF and OTF fonts are
+/// supported. Since the label is placed in a `<style>` element, it must not contain `<`.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_font!("my-font", "assets/my-font.woff2", sample = "Hello")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_font(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let sample = (|| -> parse::Result<String> {
+        let sample = options.take_str("sample")?;
+        options.finish()?;
+        // The label ends up in a `<style>` element, whose contents are not HTML-escaped
+        if image_desc.label.contains('<') {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!(
+                    "Label '{}' is used as the font family and must not contain `<`.",
+                    image_desc.label
+                ),
+            ));
+        }
+        Ok(sample.map_or_else(|| "Sample Text".to_string(), |sample| sample.value()))
+    })();
+    let sample = match sample {
+        Ok(sample) => sample,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let mime = image_desc
+        .path
+        .extension()
+        .and_then(|ext| font_mime_type(&ext.to_string_lossy()))
+        .unwrap_or_else(|| panic!("Expected a font file for ({})", image_desc));
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let family = escape_css_string(&image_desc.label);
+    let s = format!(
+        "<style>@font-face {{ font-family: \"{family}\"; src: url('{uri}'); }}</style>\
+         <span style=\"font-family: &quot;{attr_family}&quot;\">{sample}</span>",
+        attr_family = escape_html(&family),
+        family = family,
+        uri = data_uri(mime, &bytes),
+        sample = escape_html(&sample)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// The CDN location of the `lottie-player` web component used by `embed_image_lottie!`.
+const LOTTIE_PLAYER_CDN_URL: &str =
+    "https://unpkg.com/@lottiefiles/lottie-player@latest/dist/lottie-player.js";
+
+/// The MIME type of Lottie animations. JSON files are not images, so the shared table of MIME
+/// types leaves them out, and `embed_image!` rejects them.
+const LOTTIE_MIME_TYPE: &str = "application/json";
+
+/// The MIME type of the player script embedded by `embed_image_lottie!`.
+const LOTTIE_PLAYER_MIME_TYPE: &str = "text/javascript";
+
+/// Produces an HTML snippet playing a [Lottie](https://airbnb.io/lottie/) animation.
+///
+/// The animation JSON is embedded as a data URI and played with the `lottie-player` web
+/// component. By default the player script is loaded from a CDN, which means that the animation
+/// will not play in documentation viewed offline, and a warning is printed during compilation.
+/// To avoid this, pass the path to a local copy of the player script (relative to the crate
+/// root) with the `player` option, in which case the script is embedded as well.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_lottie!(
+///     "loading",
+///     "assets/loading.json",
+///     player = "assets/lottie-player.js"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_lottie(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let player = (|| -> parse::Result<Option<syn::LitStr>> {
+        let player = options.take_str("player")?;
+        options.finish()?;
+        Ok(player)
+    })();
+    let player = match player {
+        Ok(player) => player,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let player_src = match player {
+        Some(player) => {
+            // The player is a script, not an image, so it is read as it is
+            let path = resolve_path(Path::new(&player.value()));
+            let script = std::fs::read(&path).unwrap_or_else(|_| {
+                panic!("Failed to load Lottie player script at {}", path.display())
+            });
+            data_uri(LOTTIE_PLAYER_MIME_TYPE, &script)
+        }
+        None => {
+            emit_warning(&format!(
+                "Lottie animation ({}) loads its player from {}, which will not work in offline \
+                 documentation. Use the `player` option to embed a local copy.",
+                image_desc, LOTTIE_PLAYER_CDN_URL
+            ));
+            LOTTIE_PLAYER_CDN_URL.to_string()
+        }
+    };
+    let bytes = read_image(&image_desc);
+    let s = format!(
+        "<script src=\"{}\"></script>\
+         <lottie-player src=\"{}\" aria-label=\"{}\" autoplay loop></lottie-player>",
+        player_src,
+        data_uri(LOTTIE_MIME_TYPE, &bytes),
+        escape_html(&image_desc.label)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Renders a Mermaid diagram to SVG with the `mmdc` command line tool.
+fn render_mermaid(image_desc: &ImageDescription) -> Result<Vec<u8>, String> {
+    if !cfg!(feature = "mermaid-render") {
+        return Err("The `mermaid-render` feature is not enabled.".to_string());
+    }
+    let input = resolve_path(&image_desc.path);
+    let output = tools::temp_path(&image_desc.label, "svg");
+    tools::run(
+        "mmdc",
+        [
+            std::ffi::OsStr::new("-i"),
+            input.as_os_str(),
+            std::ffi::OsStr::new("-o"),
+            output.as_os_str(),
+        ],
+    )?;
+    let svg = read(&output).map_err(|err| format!("Failed to read output of `mmdc`: {}", err));
+    let _ = std::fs::remove_file(&output);
+    svg
+}
+
+/// Renders a LaTeX equation to SVG with `latex` and `dvisvgm`.
+fn render_latex(label: &str, equation: &str) -> Result<Vec<u8>, String> {
+    if !cfg!(feature = "latex") {
+        return Err("The `latex` feature is not enabled.".to_string());
+    }
+    let dir = tools::temp_path(label, "latex");
+    std::fs::create_dir_all(&dir)
+        .map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+    let render = || {
+        let document = format!(
+            "\\documentclass[preview]{{standalone}}\n\\usepackage{{amsmath,amssymb}}\n\
+             \\begin{{document}}\n$\\displaystyle {}$\n\\end{{document}}\n",
+            equation
+        );
+        std::fs::write(dir.join("equation.tex"), document)
+            .map_err(|err| format!("Failed to write LaTeX document: {}", err))?;
+        let latex = tools::run_in(
+            &dir,
+            "latex".as_ref(),
+            ["-interaction=nonstopmode", "-halt-on-error", "equation.tex"],
+        );
+        if let Err(err) = latex {
+            // LaTeX reports errors in its log rather than on stderr
+            let log = std::fs::read_to_string(dir.join("equation.log")).unwrap_or_default();
+            let errors: Vec<_> = log.lines().filter(|line| line.starts_with('!')).collect();
+            return Err(if errors.is_empty() {
+                err
+            } else {
+                format!("`latex` failed: {}", errors.join(" "))
+            });
+        }
+        tools::run_in(
+            &dir,
+            "dvisvgm".as_ref(),
+            ["--no-fonts", "--exact-bbox", "--stdout", "equation.dvi"],
+        )
+    };
+    let svg = render();
+    let _ = std::fs::remove_dir_all(&dir);
+    svg
+}
+
+/// The script used to render Mermaid diagrams client-side.
+const MERMAID_SCRIPT: &str = "<script type=\"module\">\
+    import mermaid from 'https://cdn.jsdelivr.net/npm/mermaid@10/dist/mermaid.esm.min.mjs'; \
+    mermaid.initialize({ startOnLoad: true });</script>";
+
+/// Produces an HTML snippet displaying a [Mermaid](https://mermaid.js.org) diagram.
+///
+/// If the `mermaid-render` feature is enabled and the `mmdc` command line tool is available in
+/// `PATH`, the diagram is rendered to SVG at build time and embedded as an image. Otherwise, the
+/// diagram source is embedded in a `<pre class="mermaid">` block, which is rendered client-side
+/// by the Mermaid JavaScript library. For environments without JavaScript, a pre-rendered image
+/// can be provided with the `fallback_image` option, which is then shown instead.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_mermaid!(
+///     "architecture",
+///     "assets/architecture.mmd",
+///     fallback_image = "images/architecture.png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_mermaid(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let fallback_image = (|| -> parse::Result<Option<syn::LitStr>> {
+        let fallback_image = options.take_str("fallback_image")?;
+        options.finish()?;
+        Ok(fallback_image)
+    })();
+    let fallback_image = match fallback_image {
+        Ok(fallback_image) => fallback_image,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let alt = escape_html(&image_desc.label);
+    let s = match render_mermaid(&image_desc) {
+        Ok(svg) => format!(
+            "<img src=\"{}\" alt=\"{}\">",
+            data_uri("image/svg+xml", &svg),
+            alt
+        ),
+        Err(err) => {
+            if cfg!(feature = "mermaid-render") {
+                emit_warning(&format!(
+                    "Unable to render Mermaid diagram ({}), falling back to client-side \
+                     rendering. {}",
+                    image_desc, err
+                ));
+            }
+            let source = String::from_utf8(read_image(&image_desc))
+                .unwrap_or_else(|_| panic!("Mermaid diagram ({}) is not valid UTF-8", image_desc));
+            let mut s = format!(
+                "<pre class=\"mermaid\">{}</pre>{}",
+                escape_html(&source),
+                MERMAID_SCRIPT
+            );
+            if let Some(fallback_image) = fallback_image {
+                let fallback_desc = ImageDescription {
+                    label: image_desc.label.clone(),
+                    path: PathBuf::from(fallback_image.value()),
+                };
+                s.push_str(&format!(
+                    "<noscript><img src=\"{}\" alt=\"{}\"></noscript>",
+                    data_uri(
+                        &image_mime_type(&fallback_desc),
+                        &read_image(&fallback_desc)
+                    ),
+                    alt
+                ));
+            }
+            s
+        }
+    };
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, rendering a LaTeX equation as
+/// an SVG image.
+///
+/// rustdoc does not render math. With the `latex` feature, this macro typesets the equation (in
+/// display style, with `amsmath` and `amssymb` available) with the `latex` and `dvisvgm`
+/// command line tools, which are part of TeX Live and MiKTeX, and embeds the result. If the
+/// feature is disabled or the tools are not found in `PATH`, compilation fails, unless a
+/// pre-rendered image is given with the `fallback` option, which is then embedded instead. Since
+/// LaTeX is not installed on docs.rs, crates published there should always provide a fallback.
+///
+/// ```rust
+/// /// ![Gaussian integral][eq]
+/// # #[cfg(feature = "latex")]
+/// #[doc = embed_doc_image::embed_image_from_latex!(
+///     "eq",
+///     r"\int_0^\infty e^{-x^2} dx = \frac{\sqrt{\pi}}{2}",
+///     fallback = "images/equation.png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_from_latex(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| -> parse::Result<_> {
+        let label = input.parse::<syn::LitStr>()?;
+        input.parse::<syn::Token![,]>()?;
+        let equation = input.parse::<syn::LitStr>()?;
+        let options = input.parse::<Options>()?;
+        Ok((label, equation, options))
+    };
+    let (label, equation, mut options) = syn::parse_macro_input!(item with parser);
+    let fallback = (|| -> parse::Result<Option<syn::LitStr>> {
+        if equation.value().trim().is_empty() {
+            return Err(syn::Error::new(equation.span(), "The equation is empty."));
+        }
+        let fallback = options.take_str("fallback")?;
+        options.finish()?;
+        Ok(fallback)
+    })();
+    let fallback = match fallback {
+        Ok(fallback) => fallback,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let fallback = fallback.map(|fallback| ImageDescription {
+        label: label.value(),
+        path: PathBuf::from(fallback.value()),
+    });
+    if !embeds_images(&fallback) {
+        return quote!("").into();
+    }
+    let doc_string = match (render_latex(&label.value(), &equation.value()), fallback) {
+        (Ok(svg), _) => produce_doc_string_for_image_data(&label.value(), "image/svg+xml", &svg),
+        (Err(err), Some(fallback)) => {
+            if cfg!(feature = "latex") {
+                emit_warning(&format!(
+                    "Unable to render LaTeX equation (label='{}'), falling back to {}. {}",
+                    label.value(),
+                    fallback.path.display(),
+                    err
+                ));
+            }
+            produce_doc_string_for_image(&fallback)
+        }
+        (Err(err), None) => {
+            let message = format!(
+                "Failed to render LaTeX equation: {} Provide a pre-rendered image with the \
+                 `fallback` option for environments without LaTeX.",
+                err
+            );
+            return syn::Error::new(equation.span(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML table displaying several images at once.
+///
+/// This is useful e.g. for giving an overview of an icon set. The images are laid out in a grid
+/// with `cols` columns (4 by default), overflowing to new rows as needed. With
+/// `show_labels = true`, each image is captioned with its label.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_table!(
+///     [
+///         ("icon-add", "images/icons/add.png"),
+///         ("icon-remove", "images/icons/remove.png"),
+///         ("icon-edit", "images/icons/edit.png")
+///     ],
+///     cols = 4,
+///     show_labels = true
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_table(item: TokenStream) -> TokenStream {
+    let ImageListWithOptions {
+        images,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageListWithOptions);
+    let layout = (|| -> parse::Result<(usize, bool)> {
+        let cols = match options.take_int("cols")? {
+            Some(0) => {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "The number of columns must be positive.",
+                ));
+            }
+            Some(cols) => cols,
+            None => 4,
+        };
+        let show_labels = options.take_bool("show_labels")?.unwrap_or(false);
+        options.finish()?;
+        Ok((cols, show_labels))
+    })();
+    let (cols, show_labels) = match layout {
+        Ok(layout) => layout,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images(&images) {
+        return quote!("").into();
+    }
+    let mut s = String::from("<table>");
+    for row in images.chunks(cols) {
+        s.push_str("<tr>");
+        for image_desc in row {
+            let mime = image_mime_type(image_desc);
+            let label = escape_html(&image_desc.label);
+            s.push_str(&format!(
+                "<td><img src=\"{}\" alt=\"{}\">",
+                data_uri(&mime, &read_image(image_desc)),
+                label
+            ));
+            if show_labels {
+                s.push_str(&format!("<br><code>{}</code>", label));
+            }
+            s.push_str("</td>");
+        }
+        s.push_str("</tr>");
+    }
+    s.push_str("</table>");
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Parses a hex color such as `#ff5733` or `#f53` into its red, green and blue components.
+fn parse_hex_color(color: &str) -> Option<raster::Rgb> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let component = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match hex.len() {
+        3 => {
+            let expand = |i: usize| component(&hex[i..i + 1].repeat(2));
+            Some([expand(0)?, expand(1)?, expand(2)?])
+        }
+        6 => Some([
+            component(&hex[0..2])?,
+            component(&hex[2..4])?,
+            component(&hex[4..6])?,
+        ]),
+        _ => None,
+    }
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, containing an SVG image of
+/// a color palette.
+///
+/// No image file is needed: each color is drawn as a square swatch of `swatch_size` pixels
+/// (40 by default), with its name and hex value written underneath. Colors must be given in hex
+/// notation, e.g. `#ff5733` or `#f53`. The image is referenced with the given label, like for
+/// [`embed_image!`](macro@embed_image).
+///
+/// ```rust
+/// /// ![Color palette][palette]
+/// #[doc = embed_doc_image::embed_image_color_palette!(
+///     "palette",
+///     colors = [("#FF5733", "Danger"), ("#33A1FF", "Primary"), ("#28C76F", "Success")],
+///     swatch_size = 40
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_color_palette(item: TokenStream) -> TokenStream {
+    let PaletteWithOptions {
+        label,
+        colors,
+        mut options,
+    } = syn::parse_macro_input!(item as PaletteWithOptions);
+    let swatch_size = (|| -> parse::Result<u32> {
+        let swatch_size = options.take_int("swatch_size")?.unwrap_or(40);
+        options.finish()?;
+        Ok(swatch_size)
+    })();
+    let swatch_size = match swatch_size {
+        Ok(swatch_size) => swatch_size,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    if let Some((color, _)) = colors
+        .iter()
+        .find(|(color, _)| parse_hex_color(&color.value()).is_none())
+    {
+        return syn::Error::new(
+            color.span(),
+            "Invalid color. Expected a hex color such as `#ff5733`.",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    if !should_embed() {
+        return quote!("").into();
+    }
+    // Make room for the labels, which are roughly 7 pixels per character at this font size
+    const FONT_SIZE: u32 = 12;
+    let label_width = colors
+        .iter()
+        .map(|(color, name)| 7 * color.value().len().max(name.value().len()) as u32)
+        .max()
+        .unwrap_or(0);
+    let column_width = swatch_size.max(label_width) + FONT_SIZE;
+    let width = (column_width * colors.len() as u32).max(1);
+    let height = swatch_size + 3 * FONT_SIZE;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"{f}\">",
+        w = width,
+        h = height,
+        f = FONT_SIZE
+    );
+    for (index, (color, name)) in colors.iter().enumerate() {
+        let x = index as u32 * column_width;
+        let color = svg::escape(&color.value());
+        svg.push_str(&format!(
+            "<rect x=\"{x}\" width=\"{s}\" height=\"{s}\" fill=\"{c}\" stroke=\"#888\"/>\
+             <text x=\"{x}\" y=\"{y1}\">{n}</text>\
+             <text x=\"{x}\" y=\"{y2}\" fill=\"#666\">{c}</text>",
+            x = x,
+            s = swatch_size,
+            c = color,
+            n = svg::escape(&name.value()),
+            y1 = swatch_size + FONT_SIZE + 2,
+            y2 = swatch_size + 2 * FONT_SIZE + 4
+        ));
+    }
+    svg.push_str("</svg>");
+
+    let doc_string =
+        produce_doc_string_for_image_data(&label.value(), "image/svg+xml", svg.as_bytes());
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, containing an SVG bar or line
+/// chart of the given data.
+///
+/// No image file is needed: the chart is generated from `data`, a list of `(name, value)`
+/// pairs. The image is referenced with the given label, like for
+/// [`embed_image!`](macro@embed_image). The following options are supported:
+///
+/// - `kind`: `"bar"` (the default) or `"line"`.
+/// - `color`: the color of the bars or line, e.g. `"#e15759"`.
+/// - `x_label` and `y_label`: axis labels.
+/// - `grid`: whether to draw horizontal grid lines (`true` by default).
+/// - `width` and `height`: the size of the chart in pixels (480x300 by default).
+///
+/// ```rust
+/// /// ![Performance][perf]
+/// #[doc = embed_doc_image::embed_image_chart!(
+///     "perf",
+///     kind = "bar",
+///     data = [("Vec", 12.5), ("BTreeSet", 8.3), ("HashMap", 15.1)],
+///     y_label = "ns/op"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_chart(item: TokenStream) -> TokenStream {
+    let ChartWithOptions {
+        label,
+        data,
+        mut options,
+    } = syn::parse_macro_input!(item as ChartWithOptions);
+    let chart_options = (|| -> parse::Result<chart::ChartOptions> {
+        let mut chart_options = chart::ChartOptions::default();
+        if let Some(kind) = options.take_str("kind")? {
+            chart_options.kind = match kind.value().as_str() {
+                "bar" => chart::ChartKind::Bar,
+                "line" => chart::ChartKind::Line,
+                _ => {
+                    return Err(syn::Error::new(
+                        kind.span(),
+                        "Invalid chart kind. Expected `bar` or `line`.",
+                    ));
+                }
+            };
+        }
+        if let Some(color) = options.take_str("color")? {
+            chart_options.color = color.value();
+        }
+        chart_options.x_label = options.take_str("x_label")?.map(|l| l.value());
+        chart_options.y_label = options.take_str("y_label")?.map(|l| l.value());
+        if let Some(grid) = options.take_bool("grid")? {
+            chart_options.grid = grid;
+        }
+        if let Some(width) = options.take_int("width")? {
+            chart_options.width = width;
+        }
+        if let Some(height) = options.take_int("height")? {
+            chart_options.height = height;
+        }
+        options.finish()?;
+        Ok(chart_options)
+    })();
+    let chart_options = match chart_options {
+        Ok(chart_options) => chart_options,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let data: Vec<(&str, f64)> = data
+        .iter()
+        .map(|(name, value)| (name.as_str(), *value))
+        .collect();
+    let svg = chart::render_chart(&data, &chart_options);
+    let doc_string =
+        produce_doc_string_for_image_data(&label.value(), "image/svg+xml", svg.as_bytes());
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces CSS rules that show the elements matched by `selector` as a full-screen overlay
+/// while they are the target of the URL fragment.
+///
+/// The overlay contains a link of class `embed-doc-lightbox-close`, which is hidden otherwise.
+/// It covers the whole overlay, so that clicking anywhere closes it.
+fn lightbox_css(selector: &str) -> String {
+    format!(
+        " {s} .embed-doc-lightbox-close {{ display: none; }} \
+         {s}:target {{ position: fixed; top: 0; left: 0; width: 100vw; height: 100vh; \
+         margin: 0; z-index: 1000; display: flex; align-items: center; justify-content: center; \
+         background: rgba(0, 0, 0, 0.8); }} \
+         {s}:target img {{ max-width: 90vw; max-height: 90vh; }} \
+         {s}:target .embed-doc-lightbox-close {{ display: block; position: absolute; top: 0; \
+         left: 0; width: 100%; height: 100%; box-sizing: border-box; padding: 0.5em 1em; \
+         text-align: right; font-size: 2em; color: white; text-decoration: none; }}",
+        s = selector
+    )
+}
+
+/// Produces an HTML fragment showing an image that is enlarged when clicked.
+///
+/// This is useful for large diagrams that are hard to read at the width of the documentation.
+/// Clicking the image shows it on top of the page, scaled to fit the window, and clicking again
+/// or on the ✕ closes it. The zoom is implemented with the CSS `:target` pseudo-class, so no
+/// JavaScript is needed. The styles are scoped by `id`s derived from the label, which must
+/// therefore be unique within the page and may only contain ASCII letters, digits, `-` and `_`.
+/// The label is also used as alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_with_zoom!("architecture", "images/architecture.png")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_with_zoom(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    if let Err(err) = check_id_label(&image_desc.label, proc_macro2::Span::call_site()) {
+        return err.to_compile_error().into();
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let id = escape_html(&image_desc.label);
+    let zoom_id = format!("{}-zoom", id);
+    let s = format!(
+        "<style>#{zoom_id} img {{ max-width: 100%; cursor: zoom-in; }}{css}</style>\
+         <div id=\"{id}\"><div id=\"{zoom_id}\"><a href=\"#{zoom_id}\">\
+         <img src=\"{uri}\" alt=\"{id}\"></a>\
+         <a class=\"embed-doc-lightbox-close\" href=\"#{id}\" title=\"Close\">✕</a></div></div>",
+        zoom_id = zoom_id,
+        css = lightbox_css(&format!("#{}", zoom_id)),
+        id = id,
+        uri = data_uri(&image_mime_type(&image_desc), &read_image(&image_desc))
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Whether browsers can display images of the given MIME type in `<img>` elements and CSS.
+fn is_browser_image(mime: &str) -> bool {
+    mime.starts_with("image/") && mime != "image/tiff"
+}
+
+/// Produces an HTML fragment showing an image that is replaced by a high-resolution variant when
+/// the documentation is printed.
+///
+/// The `screen` image is shown by default, while the `print` image replaces it in a
+/// `@media print` style rule, e.g. when printing or exporting the page to PDF. No JavaScript is
+/// needed. Both images must be in a format that browsers can display, so an SVG is usually the
+/// best choice for the print variant. PDF documents cannot be used as images, even when
+/// printing. The label is used as alt text and as the `id` of the image, and must therefore be
+/// unique within the page and may only contain ASCII letters, digits, `-` and `_`.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_hires!(
+///     "diagram",
+///     screen = "images/diagram.png",
+///     print = "images/diagram.svg"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_hires(item: TokenStream) -> TokenStream {
+    let LabelWithOptions { label, mut options } = syn::parse_macro_input!(item as LabelWithOptions);
+    let images = (|| -> parse::Result<_> {
+        check_id_label(&label.value(), label.span())?;
+        let mut take_image = |key: &str| -> parse::Result<ImageDescription> {
+            let path = options.take_str(key)?.ok_or_else(|| {
+                syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    format!(
+                        "Missing {} image, e.g. `{} = \"images/diagram.png\"`.",
+                        key, key
+                    ),
+                )
+            })?;
+            let image_desc = ImageDescription {
+                label: label.value(),
+                path: PathBuf::from(path.value()),
+            };
+            let mime = image_desc
+                .path
+                .extension()
+                .and_then(|ext| mime_type_for_extension(&ext.to_string_lossy()));
+            match mime {
+                Some(mime) if is_browser_image(mime) => Ok(image_desc),
+                Some(mime) => Err(syn::Error::new(
+                    path.span(),
+                    format!(
+                        "The {} image has MIME type {}, which browsers cannot display as an image.",
+                        key, mime
+                    ),
+                )),
+                None => Err(syn::Error::new(
+                    path.span(),
+                    format!("Unrecognized extension for the {} image.", key),
+                )),
+            }
+        };
+        let screen = take_image("screen")?;
+        let print = take_image("print")?;
+        options.finish()?;
+        Ok((screen, print))
+    })();
+    let (screen, print) = match images {
+        Ok(images) => images,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&screen, &print]) {
+        return quote!("").into();
+    }
+    let id = escape_html(&label.value());
+    let s = format!(
+        "<style>@media print {{ #{id} {{ content: url(\"{print}\"); }} }}</style>\
+         <img id=\"{id}\" src=\"{screen}\" alt=\"{id}\">",
+        id = id,
+        print = data_uri(&image_mime_type(&print), &read_image(&print)),
+        screen = data_uri(&image_mime_type(&screen), &read_image(&screen))
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Returns the MIME type of a video by its file extension, ignoring case, or `None` for formats
+/// that `embed_image_video_poster!` does not support.
+///
+/// Videos are not images, so the shared table of MIME types leaves them out, and `embed_image!`
+/// rejects them.
+fn video_mime_type(extension: &str) -> Option<&'static str> {
+    match extension.to_ascii_lowercase().as_str() {
+        "webm" => Some("video/webm"),
+        "mp4" => Some("video/mp4"),
+        "ogv" => Some("video/ogg"),
+        _ => None,
+    }
+}
+
+/// Produces an HTML `<video>` element embedding a video clip together with a poster image.
+///
+/// The poster image is shown until the video is played, and in place of the video where videos
+/// are not supported. The video is muted, loops and is only loaded once it is played, so that it
+/// does not slow down loading the page. Since the video is embedded in the page nonetheless, a
+/// warning is printed for videos larger than 1 MiB. WebM, MP4 and Ogg videos are supported.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_video_poster!(
+///     "demo",
+///     video = "images/demo.webm",
+///     poster = "images/screenshot.png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_video_poster(item: TokenStream) -> TokenStream {
+    let LabelWithOptions { label, mut options } = syn::parse_macro_input!(item as LabelWithOptions);
+    let files = (|| -> parse::Result<_> {
+        let mut take_file = |key: &str, expected: &str| -> parse::Result<ImageDescription> {
+            let path = options.take_str(key)?.ok_or_else(|| {
+                syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    format!(
+                        "Missing {} file, e.g. `{} = \"images/demo.{}\"`.",
+                        key, key, expected
+                    ),
+                )
+            })?;
+            let extension = Path::new(&path.value())
+                .extension()
+                .map(|ext| ext.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let supported = match key {
+                "video" => video_mime_type(&extension).is_some(),
+                _ => mime_type_for_extension(&extension)
+                    .filter(|mime| is_browser_image(mime))
+                    .is_some(),
+            };
+            if !supported {
+                return Err(syn::Error::new(
+                    path.span(),
+                    format!("Unsupported format for the {} file.", key),
+                ));
+            }
+            Ok(ImageDescription {
+                label: label.value(),
+                path: PathBuf::from(path.value()),
+            })
+        };
+        let video = take_file("video", "webm")?;
+        let poster = take_file("poster", "png")?;
+        options.finish()?;
+        Ok((video, poster))
+    })();
+    let (video, poster) = match files {
+        Ok(files) => files,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&video, &poster]) {
+        return quote!("").into();
+    }
+    let video_mime = video
+        .path
+        .extension()
+        .and_then(|ext| video_mime_type(&ext.to_string_lossy()))
+        .expect("the video format was checked when parsing");
+    let video_bytes = read_image_raw(&video);
+    if video_bytes.len() as u64 > VIDEO_SIZE_WARNING_THRESHOLD {
+        emit_warning(&format!(
+            "video ({}) is {:.1} MiB, which makes the documentation slow to load. Consider \
+             shortening or compressing the video, or hosting it externally.",
+            video,
+            video_bytes.len() as f64 / (1024.0 * 1024.0)
+        ));
+    }
+    let s = format!(
+        "<video src=\"{}\" poster=\"{}\" aria-label=\"{}\" controls muted loop \
+         preload=\"none\"></video>",
+        data_uri(video_mime, &video_bytes),
+        data_uri(&image_mime_type(&poster), &read_image(&poster)),
+        escape_html(&label.value())
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<picture>` element embedding a GIF animation converted to animated WebP,
+/// with the original GIF as fallback.
+///
+/// With the `image` feature, the frames of the GIF image are re-encoded as a lossless animated
+/// WebP image, which browsers that support WebP animations show instead of the GIF image. Since
+/// both images are embedded, this only pays off in rendering performance and quality on such
+/// browsers, not in the size of the page. If the WebP image turns out larger than the original,
+/// or the `image` feature is disabled, only the GIF image is embedded. The label is used as alt
+/// text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_gif_to_webp!("animation", "images/animation.gif")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_gif_to_webp(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    let mime = image_mime_type(&image_desc);
+    if mime != "image/gif" {
+        panic!("Image ({}) is not a GIF image", image_desc);
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let gif = read_image(&image_desc);
+    let webp = if cfg!(feature = "image") {
+        let webp = raster::gif_to_webp(&gif)
+            .unwrap_or_else(|err| panic!("Failed to convert image ({}): {}", image_desc, err));
+        Some(webp).filter(|webp| webp.len() < gif.len())
+    } else {
+        None
+    };
+
+    let img = format!(
+        "<img src=\"{}\" alt=\"{}\">",
+        data_uri("image/gif", &gif),
+        escape_html(&image_desc.label)
+    );
+    let s = match webp {
+        Some(webp) => format!(
+            "<picture><source type=\"image/webp\" srcset=\"{}\">{}</picture>",
+            data_uri("image/webp", &webp),
+            img
+        ),
+        None => img,
+    };
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Formats supported by [`embed_image_responsive!`](macro@embed_image_responsive), as option key
+/// and MIME type, in the order browsers should prefer them.
+const RESPONSIVE_FORMATS: [(&str, &str); 4] = [
+    ("avif", "image/avif"),
+    ("webp", "image/webp"),
+    ("png", "image/png"),
+    ("jpeg", "image/jpeg"),
+];
+
+/// Produces an HTML `<picture>` element that lets the browser choose between several formats of
+/// the same image.
+///
+/// Each of the options `avif`, `webp`, `png` and `jpeg` gives the path to the image in that
+/// format, and at least one must be given. The modern formats are offered as `<source>`
+/// elements, from which the browser picks the first one it supports. A PNG or JPEG image is used
+/// as the fallback `<img>`, which should therefore be given to support older browsers. A warning
+/// is printed if only one format is given, since a plain `<img>` would then do the same job. The
+/// label is used as alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_responsive!(
+///     "diagram",
+///     avif = "images/diagram.avif",
+///     webp = "images/diagram.webp",
+///     png = "images/diagram.png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_responsive(item: TokenStream) -> TokenStream {
+    let LabelWithOptions { label, mut options } = syn::parse_macro_input!(item as LabelWithOptions);
+    let images = (|| -> parse::Result<Vec<(&str, ImageDescription)>> {
+        let mut images = Vec::new();
+        for (key, mime) in RESPONSIVE_FORMATS {
+            if let Some(path) = options.take_str(key)? {
+                let image_desc = ImageDescription {
+                    label: label.value(),
+                    path: PathBuf::from(path.value()),
+                };
+                let path_mime = image_desc
+                    .path
+                    .extension()
+                    .and_then(|ext| mime_type_for_extension(&ext.to_string_lossy()));
+                if path_mime != Some(mime) {
+                    return Err(syn::Error::new(
+                        path.span(),
+                        format!("The {} image must have MIME type {}.", key, mime),
+                    ));
+                }
+                images.push((mime, image_desc));
+            }
+        }
+        options.finish()?;
+        if images.is_empty() {
+            return Err(syn::Error::new(
+                label.span(),
+                "Missing image, e.g. `png = \"images/diagram.png\"`. \
+                 The supported formats are avif, webp, png and jpeg.",
+            ));
+        }
+        Ok(images)
+    })();
+    let mut images = match images {
+        Ok(images) => images,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    if images.len() == 1 {
+        emit_warning(&format!(
+            "Only one format given for image ({}), which offers no benefit over a plain <img>.",
+            images[0].1
+        ));
+    }
+
+    if !embeds_images(images.iter().map(|(_, image_desc)| image_desc)) {
+        return quote!("").into();
+    }
+    // The last image is the least preferred one, i.e. PNG or JPEG if given
+    let (fallback_mime, fallback) = images.pop().unwrap();
+    let mut s = String::from("<picture>");
+    for (mime, image_desc) in &images {
+        s.push_str(&format!(
+            "<source type=\"{}\" srcset=\"{}\">",
+            mime,
+            data_uri(mime, &read_image(image_desc))
+        ));
+    }
+    s.push_str(&format!(
+        "<img src=\"{}\" alt=\"{}\"></picture>",
+        data_uri(fallback_mime, &read_image(&fallback)),
+        escape_html(&label.value())
+    ));
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML widget for stepping through several images, one at a time.
+///
+/// This is useful for a sequence of related diagrams, e.g. an overview followed by several
+/// detail views. Only the current page is shown, with links to the previous and the next page
+/// below it. Navigation is implemented with the CSS `:target` pseudo-class, so no JavaScript is
+/// needed. The first page is shown initially. Where CSS is not available, all pages are shown
+/// below each other. The label is used as alt text and as the `id` of the widget, and the pages
+/// have the ids `label-1`, `label-2` and so on, so they can be linked to directly. The label
+/// must therefore be unique within the page and may only contain ASCII letters, digits, `-` and
+/// `_`.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_multi_page!(
+///     "arch",
+///     pages = ["images/architecture.png", "images/diagram.png", "images/pipeline.png"]
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_multi_page(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| LabelWithPaths::parse_with_key(input, "pages");
+    let LabelWithPaths {
+        label,
+        paths: pages,
+        options,
+    } = syn::parse_macro_input!(item with parser);
+    let pages = (|| -> parse::Result<Vec<PathBuf>> {
+        check_id_label(&label.value(), label.span())?;
+        options.finish()?;
+        match pages {
+            Some(pages) if !pages.is_empty() => Ok(pages),
+            _ => Err(syn::Error::new(
+                label.span(),
+                "Missing pages, e.g. `pages = [\"images/overview.png\", \"images/detail.png\"]`.",
+            )),
+        }
+    })();
+    let pages = match pages {
+        Ok(pages) => pages,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let pages: Vec<_> = pages
+        .into_iter()
+        .map(|path| ImageDescription {
+            label: label.value(),
+            path,
+        })
+        .collect();
+
+    if !embeds_images(&pages) {
+        return quote!("").into();
+    }
+    let id = escape_html(&label.value());
+    // The first page is hidden only while another page is the target
+    let mut s = format!(
+        "<style>#{id} > .embed-doc-page {{ display: none; }} \
+         #{id} > .embed-doc-page:first-child, #{id} > .embed-doc-page:target {{ display: block; }} \
+         #{id}:has(> .embed-doc-page:target) > .embed-doc-page:first-child:not(:target) \
+         {{ display: none; }} #{id} img {{ max-width: 100%; }} \
+         #{id} nav {{ display: flex; justify-content: space-between; }}</style>\
+         <div class=\"embed-doc-multi-page\" id=\"{id}\">",
+        id = id
+    );
+    let count = pages.len();
+    for (index, image_desc) in pages.iter().enumerate() {
+        let page = index + 1;
+        let link = |target: usize, text: &str| {
+            if (1..=count).contains(&target) {
+                format!("<a href=\"#{}-{}\">{}</a>", id, target, text)
+            } else {
+                format!("<span>{}</span>", text)
+            }
+        };
+        s.push_str(&format!(
+            "<div class=\"embed-doc-page\" id=\"{id}-{page}\">\
+             <img src=\"{src}\" alt=\"{id} ({page}/{count})\">\
+             <nav>{previous}<span>{page} / {count}</span>{next}</nav></div>",
+            id = id,
+            page = page,
+            count = count,
+            src = data_uri(&image_mime_type(image_desc), &read_image(image_desc)),
+            previous = link(page - 1, "← Previous"),
+            next = link(page + 1, "Next →")
+        ));
+    }
+    s.push_str("</div>");
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML element that shows a sequence of images as an animation.
+///
+/// This is useful for illustrating the steps of an algorithm. Each frame is shown for
+/// `duration_ms` milliseconds (500 by default), and the animation starts over after the last
+/// frame unless `loop = false` is given, in which case it stops at the last frame. The animation
+/// is implemented with CSS keyframes in an inline `<style>` element, so no JavaScript is needed.
+/// Readers who prefer reduced motion see the first frame only. The element has the size of the
+/// first frame, and the label is used as alt text and as the `id` of the element, so it must be
+/// unique within the page and may only contain ASCII letters, digits, `-` and `_`.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_sequence_animation!(
+///     "anim",
+///     frames = ["images/frame01.png", "images/frame02.png", "images/frame03.png"],
+///     duration_ms = 500,
+///     loop = true
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_sequence_animation(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| LabelWithPaths::parse_with_key(input, "frames");
+    let LabelWithPaths {
+        label,
+        paths: frames,
+        mut options,
+    } = syn::parse_macro_input!(item with parser);
+    let settings = (|| -> parse::Result<(Vec<PathBuf>, u32, bool)> {
+        check_id_label(&label.value(), label.span())?;
+        let duration_ms = match options.take_int("duration_ms")? {
+            Some(0) => {
+                return Err(syn::Error::new(
+                    label.span(),
+                    "The duration of each frame must be positive.",
+                ));
+            }
+            Some(duration_ms) => duration_ms,
+            None => 500,
+        };
+        let repeat = options.take_bool("loop")?.unwrap_or(true);
+        options.finish()?;
+        match frames {
+            Some(frames) if !frames.is_empty() => Ok((frames, duration_ms, repeat)),
+            _ => Err(syn::Error::new(
+                label.span(),
+                "Missing frames, e.g. `frames = [\"images/frame01.png\", \"images/frame02.png\"]`.",
+            )),
+        }
+    })();
+    let (frames, duration_ms, repeat) = match settings {
+        Ok(settings) => settings,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let frames: Vec<_> = frames
+        .into_iter()
+        .map(|path| ImageDescription {
+            label: label.value(),
+            path,
+        })
+        .collect();
+
+    if !embeds_images(&frames) {
+        return quote!("").into();
+    }
+    let id = escape_html(&label.value());
+    // Each frame is stored once in a custom property, which both the keyframes and the initial
+    // background refer to
+    let mut properties = String::new();
+    let mut keyframes = String::new();
+    let mut size = None;
+    for (index, image_desc) in frames.iter().enumerate() {
+        let bytes = read_image(image_desc);
+        let mime = image_mime_type(image_desc);
+        if size.is_none() {
+            size = Some(
+                dimensions::image_dimensions(&mime, &bytes).unwrap_or_else(|| {
+                    panic!(
+                        "Unable to determine the dimensions of image ({}), which are needed to \
+                         size the animation",
+                        image_desc
+                    )
+                }),
+            );
+        }
+        properties.push_str(&format!(
+            " --frame-{}: url(\"{}\");",
+            index,
+            data_uri(&mime, &bytes)
+        ));
+        keyframes.push_str(&format!(
+            " {:.3}% {{ background-image: var(--frame-{}); }}",
+            100.0 * index as f64 / frames.len() as f64,
+            index
+        ));
+    }
+    let (width, height) = size.expect("there is at least one frame");
+    let count = frames.len() as u64;
+    let (iterations, fill_mode) = if repeat {
+        ("infinite", "none")
+    } else {
+        ("1", "forwards")
+    };
+    // The last frame is held until the end of the animation by the `step-end` timing function
+    let s = format!(
+        "<style>@keyframes {id}-frames {{{keyframes} 100% {{ background-image: var(--frame-{last}); }} }} \
+         #{id} {{{properties} background-image: var(--frame-0); background-size: contain; \
+         background-repeat: no-repeat; width: {width}px; max-width: 100%; \
+         aspect-ratio: {width} / {height}; animation: {id}-frames {total}ms step-end {iterations} \
+         {fill_mode}; }} @media (prefers-reduced-motion: reduce) {{ #{id} {{ \
+         animation-play-state: paused; }} }}</style>\
+         <div class=\"embed-doc-anim\" id=\"{id}\" role=\"img\" aria-label=\"{id}\"></div>",
+        id = id,
+        keyframes = keyframes,
+        last = count - 1,
+        properties = properties,
+        width = width,
+        height = height,
+        total = u64::from(duration_ms) * count,
+        iterations = iterations,
+        fill_mode = fill_mode
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML gallery of images, which can optionally be enlarged by clicking on them.
+///
+/// The images are laid out in a grid of `cols` columns (3 by default). With `captions = true`,
+/// each image is captioned with its file name. With `lightbox = true`, clicking an image shows
+/// it enlarged on top of the page, and clicking again closes it. The lightbox is implemented
+/// with the CSS `:target` pseudo-class, so no JavaScript is needed. The label is used as the
+/// `id` of the gallery and must therefore be unique within the page and may only contain ASCII
+/// letters, digits, `-` and `_`.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_gallery!(
+///     "gallery",
+///     ["images/diagram.png", "images/architecture.png", "images/screenshot.png"],
+///     cols = 3,
+///     lightbox = true
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_gallery(item: TokenStream) -> TokenStream {
+    let GalleryWithOptions {
+        label,
+        paths,
+        mut options,
+    } = syn::parse_macro_input!(item as GalleryWithOptions);
+    let layout = (|| -> parse::Result<(usize, bool, bool)> {
+        check_id_label(&label.value(), label.span())?;
+        let cols = match options.take_int("cols")? {
+            Some(0) => {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "The number of columns must be positive.",
+                ));
+            }
+            Some(cols) => cols,
+            None => 3,
+        };
+        let captions = options.take_bool("captions")?.unwrap_or(false);
+        let lightbox = options.take_bool("lightbox")?.unwrap_or(false);
+        options.finish()?;
+        Ok((cols, captions, lightbox))
+    })();
+    let (cols, captions, lightbox) = match layout {
+        Ok(layout) => layout,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let images: Vec<_> = paths
+        .into_iter()
+        .map(|path| ImageDescription {
+            label: label.value(),
+            path,
+        })
+        .collect();
+
+    if !embeds_images(&images) {
+        return quote!("").into();
+    }
+    let id = escape_html(&label.value());
+    let mut s = format!(
+        "<style>#{id} {{ display: grid; grid-template-columns: repeat({cols}, 1fr); gap: 1em; }} \
+         #{id} figure {{ margin: 0; }} #{id} img {{ max-width: 100%; }}",
+        id = id,
+        cols = cols
+    );
+    if lightbox {
+        s.push_str(&lightbox_css(&format!("#{} figure", id)));
+    }
+    s.push_str(&format!(
+        "</style><div class=\"embed-doc-gallery\" id=\"{}\">",
+        id
+    ));
+    for (index, image_desc) in images.iter().enumerate() {
+        let name = image_desc
+            .path
+            .file_name()
+            .map(|name| escape_html(&name.to_string_lossy()))
+            .unwrap_or_default();
+        let img = format!(
+            "<img src=\"{}\" alt=\"{}\">",
+            data_uri(&image_mime_type(image_desc), &read_image(image_desc)),
+            name
+        );
+        if lightbox {
+            let figure_id = format!("{}-{}", id, index + 1);
+            s.push_str(&format!(
+                "<figure id=\"{figure_id}\"><a href=\"#{figure_id}\">{img}</a>\
+                 <a class=\"embed-doc-lightbox-close\" href=\"#{id}\" title=\"Close\">✕</a>",
+                figure_id = figure_id,
+                img = img,
+                id = id
+            ));
+        } else {
+            s.push_str(&format!("<figure>{}", img));
+        }
+        if captions {
+            s.push_str(&format!("<figcaption>{}</figcaption>", name));
+        }
+        s.push_str("</figure>");
+    }
+    s.push_str("</div>");
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML table showing the input and output of an image processing operation side by
+/// side, after checking their dimensions.
+///
+/// If `expected_dimensions = (width, height)` is given, compilation fails unless both images
+/// have exactly these pixel dimensions. This makes the documentation double as a compile-time
+/// assertion that the images produced by e.g. a resampling pipeline have the correct size. Both
+/// images are captioned with their role and dimensions, and the label is used in their alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_resize_and_compare!(
+///     "upscale",
+///     input = "images/input.png",
+///     output = "images/output.png",
+///     expected_dimensions = (640, 480)
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_resize_and_compare(item: TokenStream) -> TokenStream {
+    let LabelWithDimensions {
+        label,
+        dimensions: expected,
+        mut options,
+    } = syn::parse_macro_input!(item as LabelWithDimensions);
+    let paths = (|| -> parse::Result<[(&str, syn::LitStr); 2]> {
+        let mut take_required = |key: &str| {
+            options.take_str(key)?.ok_or_else(|| {
+                syn::Error::new(
+                    label.span(),
+                    format!(
+                        "Missing option `{}`, e.g. `{} = \"images/{}.png\"`.",
+                        key, key, key
+                    ),
+                )
+            })
+        };
+        let input = take_required("input")?;
+        let output = take_required("output")?;
+        options.finish()?;
+        Ok([("Input", input), ("Output", output)])
+    })();
+    let paths = match paths {
+        Ok(paths) => paths,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    // The dimensions are checked in every build, but the images are only embedded in
+    // documentation builds
+    let embed = should_embed();
+    let mut s = String::from("<table><tr>");
+    for (role, path) in &paths {
+        let image_desc = ImageDescription {
+            label: label.value(),
+            path: PathBuf::from(path.value()),
+        };
+        let mime = image_mime_type(&image_desc);
+        let bytes = read_image(&image_desc);
+        let dimensions = match dimensions::image_dimensions(&mime, &bytes) {
+            Some(dimensions) => dimensions,
+            None => {
+                return syn::Error::new(
+                    path.span(),
+                    "Unable to determine the dimensions of the image.",
+                )
+                .to_compile_error()
+                .into();
+            }
+        };
+        if let Some(expected) = expected.filter(|expected| *expected != dimensions) {
+            return syn::Error::new(
+                path.span(),
+                format!(
+                    "{} image has dimensions {}x{}, expected {}x{}.",
+                    role, dimensions.0, dimensions.1, expected.0, expected.1
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+        if !embed {
+            continue;
+        }
+        s.push_str(&format!(
+            "<td><img src=\"{}\" alt=\"{} ({})\"><br>{} ({}x{})</td>",
+            data_uri(&mime, &bytes),
+            escape_html(&label.value()),
+            role.to_lowercase(),
+            role,
+            dimensions.0,
+            dimensions.1
+        ));
+    }
+    if !embed {
+        return quote!("").into();
+    }
+    s.push_str("</tr></table>");
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces HTML showing an enlarged region of an image, e.g. a part of a screenshot.
+///
+/// The `region = (x=.., y=.., w=.., h=..)` of the image (in pixels) is cropped and scaled up by
+/// the factor `scale` (2 by default). With `border_color`, e.g. `"#ff0000"`, a border of that
+/// color is drawn around the enlarged region. Unless `context = false` is passed, the whole
+/// image is shown above the enlarged region, with the region outlined in the border color (red
+/// by default). The label is used as alt text.
+///
+/// This macro requires the `image` feature. The processed images are embedded as PNG.
+///
+/// ```rust
+/// # #[cfg(feature = "image")]
+/// #[doc = embed_doc_image::embed_image_crop_and_resize!(
+///     "toolbar",
+///     "images/screenshot.png",
+///     region = (x=100, y=200, w=300, h=150),
+///     scale = 2.0,
+///     border_color = "#ff0000"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_crop_and_resize(item: TokenStream) -> TokenStream {
+    let ImageWithRegion {
+        image: image_desc,
+        region,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithRegion);
+    let settings = (|| -> parse::Result<(raster::Region, f64, Option<raster::Rgb>, bool)> {
+        let region = region.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing region, e.g. `region = (x=100, y=200, w=300, h=150)`.",
+            )
+        })?;
+        let scale = options.take_float("scale")?.unwrap_or(2.0);
+        if !(scale.is_finite() && scale > 0.0) {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "The scale must be positive.",
+            ));
+        }
+        let border_color = match options.take_str("border_color")? {
+            Some(color) => Some(parse_hex_color(&color.value()).ok_or_else(|| {
+                syn::Error::new(
+                    color.span(),
+                    "Invalid color. Expected a hex color such as `#ff0000`.",
+                )
+            })?),
+            None => None,
+        };
+        let context = options.take_bool("context")?.unwrap_or(true);
+        options.finish()?;
+        Ok((region, scale, border_color, context))
+    })();
+    let (region, scale, border_color, context) = match settings {
+        Ok(settings) => settings,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let fail = |err: String| -> ! { panic!("Failed to process image ({}): {}", image_desc, err) };
+    let label = escape_html(&image_desc.label);
+    let mut s = String::from("<div>");
+    if context {
+        let outlined = raster::outline_region(&bytes, region, border_color.unwrap_or([255, 0, 0]))
+            .unwrap_or_else(|err| fail(err));
+        s.push_str(&format!(
+            "<img src=\"{}\" alt=\"{}\"><br>",
+            data_uri("image/png", &outlined),
+            label
+        ));
+    }
+    let detail =
+        raster::crop_and_scale(&bytes, region, scale, border_color).unwrap_or_else(|err| fail(err));
+    s.push_str(&format!(
+        "<img src=\"{}\" alt=\"{} (detail)\"></div>",
+        data_uri("image/png", &detail),
+        label
+    ));
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces HTML showing an image with a highlighted region, along with an enlarged view of
+/// that region.
+///
+/// A red rectangle is drawn around the region `highlight = (x=.., y=.., w=.., h=..)` of the
+/// image (in pixels), and the region is shown enlarged by the factor `zoom_factor` (2 by
+/// default) below the image, or next to it with `side_by_side = true`. The image links to the
+/// enlarged view. The rectangle is drawn on whole pixels, so that it stays sharp. The label is
+/// used as alt text and, with the suffix `-zoom`, as the `id` of the enlarged view, so it may
+/// only contain ASCII letters, digits, `-` and `_`.
+///
+/// This macro requires the `image` feature. The processed images are embedded as PNG.
+///
+/// ```rust
+/// # #[cfg(feature = "image")]
+/// #[doc = embed_doc_image::embed_image_with_zoom_box!(
+///     "diagram",
+///     "images/diagram.png",
+///     highlight = (x=100, y=50, w=200, h=100),
+///     zoom_factor = 3.0
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_with_zoom_box(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| ImageWithRegion::parse_with_key(input, "highlight");
+    let ImageWithRegion {
+        image: image_desc,
+        region,
+        mut options,
+    } = syn::parse_macro_input!(item with parser);
+    let settings = (|| -> parse::Result<(raster::Region, f64, bool)> {
+        check_id_label(&image_desc.label, proc_macro2::Span::call_site())?;
+        let region = region.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing highlight, e.g. `highlight = (x=100, y=50, w=200, h=100)`.",
+            )
+        })?;
+        let zoom_factor = options.take_float("zoom_factor")?.unwrap_or(2.0);
+        if !(zoom_factor.is_finite() && zoom_factor > 0.0) {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "The zoom factor must be positive.",
+            ));
+        }
+        let side_by_side = options.take_bool("side_by_side")?.unwrap_or(false);
+        options.finish()?;
+        Ok((region, zoom_factor, side_by_side))
+    })();
+    let (region, zoom_factor, side_by_side) = match settings {
+        Ok(settings) => settings,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let fail = |err: String| -> ! { panic!("Failed to process image ({}): {}", image_desc, err) };
+    let red = [255, 0, 0];
+    let outlined = raster::outline_region(&bytes, region, red).unwrap_or_else(|err| fail(err));
+    let zoomed = raster::crop_and_scale(&bytes, region, zoom_factor, Some(red))
+        .unwrap_or_else(|err| fail(err));
+    let label = escape_html(&image_desc.label);
+    let s = format!(
+        "<div style=\"display: flex; flex-direction: {direction}; flex-wrap: wrap; gap: 1em; \
+         align-items: flex-start;\"><a href=\"#{label}-zoom\"><img src=\"{outlined}\" \
+         alt=\"{label}\" style=\"max-width: 100%;\"></a><img id=\"{label}-zoom\" \
+         src=\"{zoomed}\" alt=\"{label} (detail)\" style=\"max-width: 100%;\"></div>",
+        direction = if side_by_side { "row" } else { "column" },
+        label = label,
+        outlined = data_uri("image/png", &outlined),
+        zoomed = data_uri("image/png", &zoomed)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a `<style>` block with CSS classes for icons in a sprite sheet, along with a
+/// demonstration of each icon.
+///
+/// All images are scaled to fit squares of `size` pixels (16 by default) and tiled into a single
+/// PNG image, which is embedded only once. Each image gets a CSS class made of the label and its
+/// file name without extension, e.g. `sprite-add` for `icons/add.png` with the label `sprite`,
+/// which displays the icon when applied to a `<span>`. The classes can be used anywhere in the
+/// same page, e.g. `<span class="sprite-add"></span>` in the documentation of other items.
+/// This requires the `image` feature.
+///
+/// ```rust
+/// # #[cfg(feature = "image")]
+/// #[doc = embed_doc_image::embed_image_sprite_css!(
+///     "sprite",
+///     ["images/icons/add.png", "images/icons/remove.png", "images/icons/edit.png"],
+///     size = 24
+/// )]
+/// pub mod icons {}
+/// ```
+#[proc_macro]
+pub fn embed_image_sprite_css(item: TokenStream) -> TokenStream {
+    let GalleryWithOptions {
+        label,
+        paths,
+        mut options,
+    } = syn::parse_macro_input!(item as GalleryWithOptions);
+    let size = (|| -> parse::Result<u32> {
+        let size = options.take_int("size")?.unwrap_or(16);
+        options.finish()?;
+        let is_valid_label = !label.value().is_empty() && label.value().chars().all(is_id_char);
+        if !is_valid_label {
+            return Err(syn::Error::new(
+                label.span(),
+                "The label is used as a CSS class prefix, and may only contain ASCII letters, \
+                 digits, `-` and `_`.",
+            ));
+        }
+        if paths.is_empty() {
+            return Err(syn::Error::new(
+                label.span(),
+                "Expected at least one image.",
+            ));
+        }
+        if size == 0 {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Size must be positive.",
+            ));
+        }
+        Ok(size)
+    })();
+    let size = match size {
+        Ok(size) => size,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let mut classes: Vec<String> = Vec::new();
+    let mut image_descs = Vec::new();
+    for path in paths {
+        let class = format!("{}-{}", label.value(), file_stem_label(&path));
+        let image_desc = ImageDescription {
+            label: label.value(),
+            path,
+        };
+        if classes.contains(&class) {
+            panic!(
+                "Several images would have the CSS class '{}', including image ({}).",
+                class, image_desc
+            );
+        }
+        image_descs.push(image_desc);
+        classes.push(class);
+    }
+    if !embeds_images(&image_descs) {
+        return quote!("").into();
+    }
+    let images: Vec<_> = image_descs.iter().map(read_image).collect();
+    let sheet = raster::sprite_sheet(&images, size).unwrap_or_else(|err| {
+        panic!(
+            "Failed to create sprite sheet for label '{}': {}",
+            label.value(),
+            err
+        )
+    });
+
+    let selectors: Vec<_> = classes
+        .iter()
+        .map(|class| format!("span.{}", class))
+        .collect();
+    let mut s = format!(
+        "<style>{} {{ display: inline-block; width: {size}px; height: {size}px; \
+         vertical-align: middle; background-image: url(\"{}\"); }}",
+        selectors.join(", "),
+        data_uri("image/png", &sheet.png),
+        size = size
+    );
+    for (selector, (x, y)) in selectors.iter().zip(&sheet.positions) {
+        s.push_str(&format!(
+            " {} {{ background-position: {}px {}px; }}",
+            selector,
+            -i64::from(*x),
+            -i64::from(*y)
+        ));
+    }
+    s.push_str("</style>");
+    for class in &classes {
+        s.push_str(&format!(
+            "<span class=\"{class}\" title=\"{class}\"></span> ",
+            class = class
+        ));
+    }
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<img>` tag for a diagram that is inverted in dark mode.
+///
+/// Black-on-white line art is hard to read on the dark rustdoc themes. This macro embeds the
+/// image along with a style rule that inverts it when a dark theme is used, either explicitly or
+/// through the system preference. The inversion is followed by a hue rotation of 180 degrees,
+/// which restores the original hues, so that e.g. a red arrow stays red. This works well for
+/// simple diagrams, but not for photos or detailed illustrations, which are better served by
+/// separate light and dark variants. The label is used as alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_dark_mode_invert!("diagram", "images/diagram.png")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_dark_mode_invert(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let selector = format!(
+        "img[src^=\"data:\"][alt=\"{}\"]",
+        escape_css_string(&image_desc.label)
+    );
+    // Recent versions of rustdoc set `data-theme` on the root element, while older versions only
+    // follow the system preference
+    let s = format!(
+        "<style>@media (prefers-color-scheme: dark) {{ \
+         :root:not([data-theme=\"light\"]) {sel} {{ {filter} }} }} \
+         :root[data-theme=\"dark\"] {sel}, :root[data-theme=\"ayu\"] {sel} {{ {filter} }}</style>\
+         <img src=\"{src}\" alt=\"{alt}\" style=\"filter: invert(0)\">",
+        sel = selector,
+        // The rule must override the inline style of the image
+        filter = "filter: invert(1) hue-rotate(180deg) !important;",
+        src = data_uri(&mime, &bytes),
+        alt = escape_html(&image_desc.label)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<img>` tag with a CSS class for inclusion in Markdown documentation.
+///
+/// This allows embedded images to be styled consistently, e.g. with a stylesheet embedded with
+/// [`embed_doc_image_css!`](macro@embed_doc_image_css) or passed to `rustdoc` with
+/// `--extend-css`. The label is used as alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_doc_image_css!("assets/custom.css")]
+/// #[doc = embed_doc_image::embed_image_with_class!(
+///     "diagram",
+///     "images/diagram.png",
+///     class = "doc-diagram"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_with_class(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let class = (|| -> parse::Result<String> {
+        let class = options.take_str("class")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing CSS class, e.g. `class = \"doc-diagram\"`.",
+            )
+        })?;
+        options.finish()?;
+        Ok(class.value())
+    })();
+    let class = match class {
+        Ok(class) => class,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let s = format!(
+        "<img src=\"{}\" class=\"{}\" alt=\"{}\">",
+        data_uri(&mime, &bytes),
+        escape_html(&class),
+        escape_html(&image_desc.label)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML figure showing an image with a link to download it below.
+///
+/// The link points to the same data as the image and carries the `download` attribute, so
+/// clicking it saves the image under the name given by `filename`, which defaults to the file
+/// name of the image. Note that the image data is included twice in the page, once for the image
+/// and once for the link. The label is used as alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_download_link!(
+///     "architecture",
+///     "images/architecture.png",
+///     filename = "architecture-diagram.png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_download_link(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let filename = (|| -> parse::Result<Option<String>> {
+        let filename = options.take_str("filename")?;
+        options.finish()?;
+        Ok(filename.map(|filename| filename.value()))
+    })();
+    let filename = match filename {
+        Ok(filename) => filename.unwrap_or_else(|| {
+            image_desc
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        }),
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let src = data_uri(&image_mime_type(&image_desc), &read_image(&image_desc));
+    let s = format!(
+        "<figure><img src=\"{src}\" alt=\"{alt}\">\
+         <figcaption style=\"font-size: 0.8em;\">\
+         <a href=\"{src}\" download=\"{filename}\">Download</a></figcaption></figure>",
+        src = src,
+        alt = escape_html(&image_desc.label),
+        filename = escape_html(&filename)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<img>` tag with a border for inclusion in Markdown documentation.
+///
+/// Screenshots with a white background blend in with the light rustdoc theme. This macro draws a
+/// border around the image, without the need for a custom stylesheet as with
+/// [`embed_image_with_class!`](macro@embed_image_with_class). The `border` and `padding` options
+/// take CSS values, and default to `1px solid #ccc` and `8px`, respectively. The label is used
+/// as alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_with_border!(
+///     "screenshot",
+///     "images/screenshot.png",
+///     border = "1px solid #ccc",
+///     padding = "8px"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_with_border(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let style = (|| -> parse::Result<(String, String)> {
+        let mut take_css_value = |key: &str, default: &str| -> parse::Result<String> {
+            match options.take_str(key)? {
+                Some(value) => {
+                    let css = value.value();
+                    if css.trim().is_empty() || css.contains([';', '{', '}']) {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            format!(
+                                "Invalid {}. Expected a single CSS value such as `{}`.",
+                                key, default
+                            ),
+                        ));
+                    }
+                    Ok(css.trim().to_string())
+                }
+                None => Ok(default.to_string()),
+            }
+        };
+        let border = take_css_value("border", "1px solid #ccc")?;
+        let padding = take_css_value("padding", "8px")?;
+        options.finish()?;
+        Ok((border, padding))
+    })();
+    let (border, padding) = match style {
+        Ok(style) => style,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let s = format!(
+        "<img src=\"{}\" alt=\"{}\" style=\"border: {}; padding: {}; box-sizing: border-box;\">",
+        data_uri(&mime, &bytes),
+        escape_html(&image_desc.label),
+        escape_html(&border),
+        escape_html(&padding)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<img>` tag with a title for inclusion in Markdown documentation.
+///
+/// Most browsers show the title as a tooltip when hovering over the image, which is useful for
+/// supplementary information that would otherwise clutter the text. The label is used as alt
+/// text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_with_title!(
+///     "diagram",
+///     "images/diagram.png",
+///     title = "Simplified view, see the module docs for details"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_with_title(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let title = (|| -> parse::Result<String> {
+        let title = options.take_str("title")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing title, e.g. `title = \"Simplified view\"`.",
+            )
+        })?;
+        options.finish()?;
+        Ok(title.value())
+    })();
+    let title = match title {
+        Ok(title) => title,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let s = format!(
+        "<img src=\"{}\" alt=\"{}\" title=\"{}\">",
+        data_uri(&mime, &bytes),
+        escape_html(&image_desc.label),
+        escape_html(&title)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML image with a side note next to it, like the margin notes in technical books.
+///
+/// The image is shown on the left and the note on the right, set in a smaller font with an
+/// accent border to its left. On narrow screens, the note is shown below the image instead. The
+/// label is used as alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_side_note!(
+///     "diagram",
+///     "images/diagram.png",
+///     note = "Note: arrows indicate data flow direction"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_side_note(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let note = (|| -> parse::Result<String> {
+        let note = options.take_str("note")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing note, e.g. `note = \"Arrows indicate data flow direction\"`.",
+            )
+        })?;
+        options.finish()?;
+        Ok(note.value())
+    })();
+    let note = match note {
+        Ok(note) => note,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    // Inline styles cannot contain media queries, so stacking on narrow screens needs a rule
+    let s = format!(
+        "<style>@media (max-width: 600px) {{ .embed-doc-side-note {{ \
+         flex-direction: column; }} }}</style>\
+         <div class=\"embed-doc-side-note\" style=\"display: flex; gap: 1em; \
+         align-items: flex-start;\"><img src=\"{}\" alt=\"{}\" style=\"max-width: 100%;\">\
+         <aside style=\"font-size: 0.875em; border-left: 3px solid #888; \
+         padding-left: 0.75em;\">{}</aside></div>",
+        data_uri(&mime, &bytes),
+        escape_html(&image_desc.label),
+        escape_html(&note)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<img>` tag with a long description for screen readers.
+///
+/// Complex diagrams often cannot be described adequately in a short alt text. This macro puts
+/// the `description` in a paragraph with the `hidden` attribute after the image, and refers to
+/// it with `aria-describedby`. Screen readers read out the description along with the image,
+/// while it is not shown to sighted readers. The label is used as alt text, and with the suffix
+/// `-desc` as the `id` of the description, so it must be unique within the page and may only
+/// contain ASCII letters, digits, `-` and `_`.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_accessible_description!(
+///     "pipeline",
+///     "images/pipeline.png",
+///     description = "This diagram shows how requests flow from the load balancer to ..."
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_accessible_description(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let description = (|| -> parse::Result<String> {
+        check_id_label(&image_desc.label, proc_macro2::Span::call_site())?;
+        let description = options.take_str("description")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing description, e.g. `description = \"This diagram shows ...\"`.",
+            )
+        })?;
+        if description.value().trim().is_empty() {
+            return Err(syn::Error::new(
+                description.span(),
+                "The description must not be empty.",
+            ));
+        }
+        options.finish()?;
+        Ok(description.value())
+    })();
+    let description = match description {
+        Ok(description) => description,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let label = escape_html(&image_desc.label);
+    // Hidden content is still announced when it is referenced by `aria-describedby`
+    let s = format!(
+        "<img src=\"{src}\" alt=\"{label}\" aria-describedby=\"{label}-desc\">\
+         <p id=\"{label}-desc\" hidden>{description}</p>",
+        src = data_uri(&mime, &bytes),
+        label = label,
+        description = escape_html(&description)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<img>` tag preceded by a named anchor, so that other documentation can link
+/// directly to the image.
+///
+/// The anchor is given with the `anchor` option and may only contain ASCII letters, digits, `-`
+/// and `_`. It must be unique within the page. The image can then be linked to with e.g.
+/// `[See the architecture overview](#arch-overview)`, or from other pages by appending the
+/// fragment to their URL. The label is used as alt text.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_named_anchor!(
+///     "architecture",
+///     "images/architecture.png",
+///     anchor = "arch-overview"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_named_anchor(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let anchor = (|| -> parse::Result<String> {
+        let anchor = options.take_str("anchor")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing anchor, e.g. `anchor = \"arch-overview\"`.",
+            )
+        })?;
+        options.finish()?;
+        let value = anchor.value();
+        let is_valid = !value.is_empty() && value.chars().all(is_id_char);
+        if !is_valid {
+            return Err(syn::Error::new(
+                anchor.span(),
+                "Invalid anchor. Anchors must be non-empty and may only contain ASCII letters, \
+                 digits, `-` and `_`.",
+            ));
+        }
+        Ok(value)
+    })();
+    let anchor = match anchor {
+        Ok(anchor) => anchor,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let s = format!(
+        "<a id=\"{}\"></a><img src=\"{}\" alt=\"{}\">",
+        anchor,
+        data_uri(&mime, &bytes),
+        escape_html(&image_desc.label)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a `<style>` block containing the given CSS file for inclusion in documentation.
+///
+/// The path is relative to the crate root. Note that the styles apply to the whole rendered
+/// page, not just the documentation of the item they are embedded in.
+#[proc_macro]
+pub fn embed_doc_image_css(item: TokenStream) -> TokenStream {
+    let path = syn::parse_macro_input!(item as syn::LitStr);
+    let path = resolve_path(Path::new(&path.value()));
+    let css = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("Failed to load stylesheet at {}", path.display()));
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let s = format!("<style>\n{}\n</style>", css.trim());
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, using an image from a dependency.
+///
+/// This works like [`embed_image!`](macro@embed_image), except that the path is relative to
+/// the root of the given dependency rather than the current crate. This is useful e.g. when
+/// re-exporting items from a dependency along with the images in their documentation.
+///
+/// The sources of the dependency are located by looking for a path dependency in the current
+/// crate's `Cargo.toml`, and otherwise by looking up its version in `Cargo.lock` and searching
+/// the `vendor` directory next to `Cargo.lock` and the Cargo registry. Git dependencies are not
+/// supported.
+///
+/// ```rust,ignore
+/// #[doc = embed_doc_image::embed_image_from_pkg!(
+///     "logo",
+///     crate = "dep_crate",
+///     path = "images/logo.png"
+/// )]
+/// pub use dep_crate::Foo;
+/// ```
+#[proc_macro]
+pub fn embed_image_from_pkg(item: TokenStream) -> TokenStream {
+    let LabelWithOptions { label, mut options } = syn::parse_macro_input!(item as LabelWithOptions);
+    let source = (|| -> parse::Result<(syn::LitStr, syn::LitStr)> {
+        let missing = |option: &str| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!("Missing option `{}`.", option),
+            )
+        };
+        let krate = options.take_str("crate")?.ok_or_else(|| missing("crate"))?;
+        let path = options.take_str("path")?.ok_or_else(|| missing("path"))?;
+        options.finish()?;
+        Ok((krate, path))
+    })();
+    let (krate, path) = match source {
+        Ok(source) => source,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let package_dir = match packages::locate_package(&crate_root_dir(), &krate.value()) {
+        Ok(package_dir) => package_dir,
+        Err(err) => return syn::Error::new(krate.span(), err).to_compile_error().into(),
+    };
+    let image_desc = ImageDescription {
+        label: label.value(),
+        path: package_dir.join(path.value()),
+    };
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let doc_string = produce_doc_string_for_image(&image_desc);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, using a remote image such as
+/// a status badge.
+///
+/// The image is fetched at build time with `curl`, which requires the `fetch` feature. Fetched
+/// images are cached in the target directory for the duration given by `cache_for` (e.g. `30m`,
+/// `1h` or `7d`, one hour by default), so that we do not hit the network on every build. The
+/// cache can be invalidated by setting the `EMBED_DOC_IMAGE_FETCH_EPOCH` environment variable to
+/// a new value, e.g. the current date. The server must report an `image/*` content type, unless
+/// the format of the image is recognized from its contents, in which case that is used instead.
+///
+/// Note that the embedded image is a snapshot taken at build time. For badges that reflect
+/// e.g. the current build status, this means that the documentation on `docs.rs` will show the
+/// status at the time of publishing.
+///
+/// ```rust,ignore
+/// /// ![Build status][build-status]
+/// #[doc = embed_doc_image::embed_image_badge!(
+///     "build-status",
+///     "https://img.shields.io/github/actions/workflow/status/owner/repo/ci.yml",
+///     cache_for = "1h"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_badge(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let ttl = (|| -> parse::Result<std::time::Duration> {
+        let ttl = match options.take_str("cache_for")? {
+            Some(cache_for) => fetch::parse_duration(&cache_for.value()).ok_or_else(|| {
+                syn::Error::new(
+                    cache_for.span(),
+                    "Invalid duration. Expected e.g. `30m`, `1h` or `7d`.",
+                )
+            })?,
+            None => std::time::Duration::from_secs(60 * 60),
+        };
+        options.finish()?;
+        if !cfg!(feature = "fetch") {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Fetching remote images requires the `fetch` feature of `embed-doc-image`.",
+            ));
+        }
+        Ok(ttl)
+    })();
+    let ttl = match ttl {
+        Ok(ttl) => ttl,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let url = image_desc.path.to_string_lossy();
+    let (mime, bytes) = fetch::fetch_cached(&crate_root_dir(), &url, ttl)
+        .unwrap_or_else(|err| panic!("Failed to fetch image ({}): {}", image_desc, err));
+    let doc_string = produce_doc_string_for_image_data(&image_desc.label, &mime, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, from an image that is already
+/// `base64`-encoded.
+///
+/// This works like [`embed_image!`](macro@embed_image), except that the image data is given
+/// directly as a `base64` string literal along with its MIME type, e.g. as produced by a code
+/// generator. The data is decoded at compile time to make sure that it is valid. Whitespace in
+/// the data is ignored, so long strings may be wrapped.
+///
+/// ```rust
+/// /// ![Dot][dot]
+/// #[doc = embed_doc_image::embed_image_from_base64!(
+///     "dot",
+///     "image/png",
+///     "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII="
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_from_base64(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| -> parse::Result<_> {
+        let label = input.parse::<syn::LitStr>()?;
+        input.parse::<syn::Token![,]>()?;
+        let mime = input.parse::<syn::LitStr>()?;
+        input.parse::<syn::Token![,]>()?;
+        let data = input.parse::<syn::LitStr>()?;
+        input.parse::<Option<syn::Token![,]>>()?;
+        Ok((label, mime, data))
+    };
+    let (label, mime, data) = syn::parse_macro_input!(item with parser);
+    let mime_value = mime.value();
+    let valid_mime = match mime_value.split_once('/') {
+        Some((kind, subtype)) => !kind.is_empty() && !subtype.is_empty(),
+        None => false,
+    };
+    if !valid_mime {
+        return syn::Error::new(mime.span(), "Invalid MIME type. Expected e.g. `image/png`.")
+            .to_compile_error()
+            .into();
+    }
+    let encoded: String = data
+        .value()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let bytes = match base64::decode(&encoded) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            return syn::Error::new(data.span(), format!("Invalid base64 data: {}", err))
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let doc_string = produce_doc_string_for_image_data(&label.value(), &mime_value, &bytes);
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, from SVG source given directly
+/// as a string literal.
+///
+/// This works like [`embed_image!`](macro@embed_image) for SVG images, except that the SVG
+/// source is written in the macro call instead of a file. This is convenient for small diagrams,
+/// or for SVG source produced by a code generator. As for files, the SVG source is checked for
+/// well-formedness with the `svg` feature, and sanitized if `EMBED_DOC_IMAGE_SANITIZE_SVG` is
+/// set.
+///
+/// ```rust
+/// /// ![Circle][circle]
+/// #[doc = embed_doc_image::embed_image_from_raw_svg!(
+///     "circle",
+///     r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20">
+///         <circle cx="10" cy="10" r="8" fill="teal"/>
+///     </svg>"#
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_from_raw_svg(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| -> parse::Result<_> {
+        let label = input.parse::<syn::LitStr>()?;
+        input.parse::<syn::Token![,]>()?;
+        let svg = input.parse::<syn::LitStr>()?;
+        input.parse::<Option<syn::Token![,]>>()?;
+        Ok((label, svg))
+    };
+    let (label, svg) = syn::parse_macro_input!(item with parser);
+    let source = svg.value();
+    let checked = svg::validate(source.as_bytes()).and_then(|()| {
+        match magic::sniff_mime_type(source.as_bytes()) {
+            Some("image/svg+xml") => Ok(()),
+            _ => Err("no `<svg>` element".to_string()),
+        }
+    });
+    if let Err(err) = checked {
+        return syn::Error::new(svg.span(), format!("Malformed SVG image: {}", err))
+            .to_compile_error()
+            .into();
+    }
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let source = if EmbedDocImageConfig::from_env().sanitize_svg {
+        svg::strip_elements(&source, "script")
+    } else {
+        source
+    };
+
+    let doc_string =
+        produce_doc_string_for_image_data(&label.value(), "image/svg+xml", source.as_bytes());
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, using a generated QR code.
+///
+/// QR codes let readers of the documentation on a phone, or of a printed copy, open a link
+/// without typing it. The QR code for the given text, typically a URL, is generated at compile
+/// time without reading any files. By default, it is embedded as a PNG image that is at most
+/// `size` pixels wide (200 by default). With `format = "svg"`, it is embedded as an SVG image
+/// instead, which stays sharp at any zoom level.
+///
+/// This macro requires the `qrcode` feature, which enables the `image` feature for encoding PNG
+/// images.
+///
+/// ```rust
+/// /// ![Scan to open on crates.io][crates-io]
+/// # #[cfg(feature = "qrcode")]
+/// #[doc = embed_doc_image::embed_image_qrcode!("crates-io", "https://crates.io/crates/my-crate", size = 200)]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_qrcode(item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| -> parse::Result<_> {
+        let label = input.parse::<syn::LitStr>()?;
+        input.parse::<syn::Token![,]>()?;
+        let data = input.parse::<syn::LitStr>()?;
+        let options = input.parse::<Options>()?;
+        Ok((label, data, options))
+    };
+    let (label, data, mut options) = syn::parse_macro_input!(item with parser);
+    let settings = (|| -> parse::Result<(u32, bool)> {
+        let size = options.take_int("size")?.unwrap_or(200);
+        let svg = match options.take_str("format")? {
+            Some(format) => match format.value().to_ascii_lowercase().as_str() {
+                "png" => false,
+                "svg" => true,
+                _ => {
+                    return Err(syn::Error::new(
+                        format.span(),
+                        "Unsupported format. Expected `png` or `svg`.",
+                    ));
+                }
+            },
+            None => false,
+        };
+        options.finish()?;
+        Ok((size, svg))
+    })();
+    let (size, svg) = match settings {
+        Ok(settings) => settings,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let image = if svg {
+        qr::svg(&data.value(), size).map(|svg| ("image/svg+xml", svg.into_bytes()))
+    } else {
+        qr::png(&data.value(), size).map(|png| ("image/png", png))
+    };
+    let (mime_type, bytes) = match image {
+        Ok(image) => image,
+        Err(err) => {
+            return syn::Error::new(data.span(), format!("Failed to generate QR code: {}", err))
+                .to_compile_error()
+                .into();
+        }
+    };
+    let doc_string = produce_doc_string_for_image_data(&label.value(), mime_type, &bytes);
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, using an image generated by
+/// the build script.
+///
+/// Some images cannot be stored as static files, e.g. plots of functions implemented in the
+/// crate itself. A proc macro cannot call functions of the crate it is used in, so such images
+/// have to be generated by a build script, which writes them to `OUT_DIR`. This macro then
+/// embeds the image at the given path relative to `OUT_DIR`, like
+/// [`embed_image!`](macro@embed_image) does for paths relative to the crate root. The MIME type
+/// is inferred from the extension, unless it is given with the `mime` option.
+///
+/// The build script can write the image with `write_generated_image` from the
+/// `embed-doc-image-core` crate, added as a build dependency, which calls a function generating
+/// the image and writes the result to `OUT_DIR`. The generating function can be shared with the
+/// crate by including its module in the build script with `#[path = ".."]`.
+///
+/// ```rust,ignore
+/// // build.rs
+/// #[path = "src/plot.rs"]
+/// mod plot;
+///
+/// fn main() {
+///     embed_doc_image_core::write_generated_image("plot.png", plot::render).unwrap();
+///     println!("cargo:rerun-if-changed=src/plot.rs");
+/// }
+///
+/// // lib.rs
+/// /// ![Plot][plot]
+/// #[doc = embed_doc_image::embed_image_from_out_dir!("plot", "plot.png", mime = "image/png")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_from_out_dir(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let mime = (|| -> parse::Result<Option<syn::LitStr>> {
+        let mime = options.take_str("mime")?;
+        options.finish()?;
+        Ok(mime)
+    })();
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let mime = match mime {
+        Ok(mime) => mime,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let out_dir =
+        match std::env::var_os("OUT_DIR") {
+            Some(out_dir) => PathBuf::from(out_dir),
+            None => return syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "`OUT_DIR` is not set. Generated images require the crate to have a build script.",
+            )
+            .to_compile_error()
+            .into(),
+        };
+
+    let image_desc = ImageDescription {
+        path: out_dir.join(&image_desc.path),
+        ..image_desc
+    };
+    let mime = match mime {
+        Some(mime) => mime.value(),
+        None => image_mime_type(&image_desc),
+    };
+    let doc_string =
+        produce_doc_string_for_image_data(&image_desc.label, &mime, &read_image(&image_desc));
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Returns a `file://` URL for an absolute path.
+fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        // Windows paths such as `C:/...`
+        format!("file:///{}", path)
+    }
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, referencing a copy of the
+/// image on disk instead of embedding its data.
+///
+/// **Experimental**: this requires the `file-backed` feature. Embedding many large images
+/// inflates the generated documentation, since the data of every image is stored inline. This
+/// macro instead copies the image to `$OUT_DIR/embed-doc-image/` and references the copy with a
+/// `file://` URL, which keeps the documentation small.
+///
+/// `OUT_DIR` is only set for crates with a build script, so the documented crate needs one
+/// (an empty `fn main() {}` suffices). Since the image is referenced by an absolute local path,
+/// it only displays in locally built documentation, and not e.g. on `docs.rs`. To publish the
+/// documentation elsewhere, use [`embed_image!`](macro@embed_image) instead.
+///
+/// ```rust,ignore
+/// /// ![Diagram][diagram]
+/// #[doc = embed_doc_image::embed_image_file_backed!("diagram", "images/diagram.png")]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_file_backed(item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(item as ImageDescription);
+    if !cfg!(feature = "file-backed") {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "File-backed images require the experimental `file-backed` feature of \
+             `embed-doc-image`.",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let out_dir = match std::env::var_os("OUT_DIR") {
+        Some(out_dir) => PathBuf::from(out_dir),
+        None => {
+            return syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "`OUT_DIR` is not set. File-backed images require the crate to have a build \
+                 script.",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let extension = image_desc
+        .path
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // Name the copy by its content, so that identical images share a file
+    let dir = out_dir.join("embed-doc-image");
+    let path = dir.join(format!("{}.{}", sha256_hex(&bytes), extension));
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&path, &bytes))
+        .unwrap_or_else(|err| {
+            panic!(
+                "Failed to write image ({}) to {}: {}",
+                image_desc,
+                path.display(),
+                err
+            )
+        });
+
+    let s = format!("\n \n [{}]: <{}>", image_desc.label, file_url(&path));
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<object>` element embedding an SVG image with working hyperlinks.
+///
+/// This is a variant of [`embed_image_object!`](macro@embed_image_object) for SVG diagrams
+/// whose nodes link to other pages, e.g. to the documentation of modules. Such links do not work
+/// in images embedded with `<img>`. Since the SVG image gets its own browsing context, links
+/// should have `target="_top"` to open in the documentation page rather than inside the image.
+/// The `fallback` option sets the text shown by browsers that cannot display the image, and
+/// defaults to the label, which is also used as the accessible name of the image. A warning is
+/// printed if the image contains no links, since [`embed_image!`](macro@embed_image) would then
+/// suffice.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_interactive_svg!(
+///     "architecture",
+///     "images/architecture.svg",
+///     fallback = "Diagram of the architecture"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_interactive_svg(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let fallback = (|| -> parse::Result<Option<syn::LitStr>> {
+        let fallback = options.take_str("fallback")?;
+        options.finish()?;
+        Ok(fallback)
+    })();
+    let fallback = match fallback {
+        Ok(fallback) => fallback.map_or_else(|| image_desc.label.clone(), |text| text.value()),
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !is_svg_path(&image_desc.path) {
+        panic!("Image ({}) is not an SVG image", image_desc);
+    }
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    if !svg::has_links(&String::from_utf8_lossy(&bytes)) {
+        emit_warning(&format!(
+            "SVG image ({}) contains no links, so `embed_image!` would suffice.",
+            image_desc
+        ));
+    }
+    let s = format!(
+        "<object type=\"image/svg+xml\" data=\"{}\" aria-label=\"{}\">{}</object>",
+        data_uri("image/svg+xml", &bytes),
+        escape_html(&image_desc.label),
+        escape_html(&fallback)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces an HTML `<object>` element embedding an image, typically an interactive SVG.
+///
+/// SVG images embedded with `<img>` are sandboxed, which disables CSS hover effects, scripts and
+/// hyperlinks. Embedding them with `<object>` instead gives them their own browsing context and
+/// enables such interactivity. An `<img>` is placed inside the `<object>` as a fallback for
+/// browsers that cannot display it. By default the fallback shows the same image, but a different
+/// image (e.g. a pre-rendered PNG) can be given with the `fallback` option.
+///
+/// ```rust
+/// #[doc = embed_doc_image::embed_image_object!(
+///     "diagram",
+///     "images/architecture.svg",
+///     fallback = "images/diagram.png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_object(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let fallback = (|| -> parse::Result<Option<syn::LitStr>> {
+        let fallback = options.take_str("fallback")?;
+        options.finish()?;
+        Ok(fallback)
+    })();
+    let fallback = match fallback {
+        Ok(fallback) => fallback,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let mime = image_mime_type(&image_desc);
+    let uri = data_uri(&mime, &read_image(&image_desc));
+    let fallback_uri = match fallback {
+        Some(fallback) => {
+            let fallback_desc = ImageDescription {
+                label: image_desc.label.clone(),
+                path: PathBuf::from(fallback.value()),
+            };
+            data_uri(
+                &image_mime_type(&fallback_desc),
+                &read_image(&fallback_desc),
+            )
+        }
+        None => uri.clone(),
+    };
+    let label = escape_html(&image_desc.label);
+    let s = format!(
+        "<object type=\"{}\" data=\"{}\" aria-label=\"{}\">\
+         <img src=\"{}\" alt=\"{}\"></object>",
+        mime, uri, label, fallback_uri, label
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// The maximum width and height of images embedded with `embed_image_icon!`.
+const MAX_ICON_SIZE: u32 = 64;
+
+/// Produces an HTML `<img>` tag for a small icon, intended to be used inline in text.
+///
+/// The icon is displayed as a square of `size` pixels (16 by default), vertically aligned with
+/// the surrounding text. The label is used as alt text. To prevent accidentally embedding large
+/// images as icons, both the displayed size and the actual dimensions of the image must not
+/// exceed 64x64 pixels.
+///
+/// ```rust
+/// /// Error codes returned by the API.
+/// #[doc = embed_doc_image::embed_image_icon!("warning", "images/icons/warning.png", size = 16)]
+/// pub struct ErrorCode(u32);
+/// ```
+#[proc_macro]
+pub fn embed_image_icon(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let size = (|| -> parse::Result<u32> {
+        let size = options.take_int("size")?.unwrap_or(16);
+        options.finish()?;
+        if size == 0 || size > MAX_ICON_SIZE {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!("Icon size must be between 1 and {}.", MAX_ICON_SIZE),
+            ));
+        }
+        Ok(size)
+    })();
+    let size = match size {
+        Ok(size) => size,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    if let Some((width, height)) = dimensions::image_dimensions(&mime, &bytes) {
+        if width > MAX_ICON_SIZE || height > MAX_ICON_SIZE {
+            return syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!(
+                    "Icon ({}) is {}x{} pixels, but icons must not exceed {}x{} pixels.",
+                    image_desc, width, height, MAX_ICON_SIZE, MAX_ICON_SIZE
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let s = format!(
+        "<img src=\"{}\" width=\"{size}\" height=\"{size}\" \
+         style=\"vertical-align: middle;\" alt=\"{}\">",
+        data_uri(&mime, &bytes),
+        escape_html(&image_desc.label),
+        size = size
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Turns the `href` of an image map hotspot into a URL.
+///
+/// Rust paths such as `crate::module_a::module_b` are interpreted as modules, and are turned into
+/// links to their documentation relative to `doc_root`. Anything else is used as-is.
+fn resolve_map_href(href: &str, doc_root: &str) -> String {
+    match href.strip_prefix("crate::") {
+        Some(path)
+            if path
+                .split("::")
+                .all(|segment| syn::parse_str::<syn::Ident>(segment).is_ok()) =>
+        {
+            format!("{}{}/index.html", doc_root, path.replace("::", "/"))
+        }
+        _ if href == "crate" => format!("{}index.html", doc_root),
+        _ => href.to_string(),
+    }
+}
+
+/// Produces an HTML `<img>` tag with a clickable image map for inclusion in documentation.
+///
+/// Each hotspot is a rectangle given by its top-left corner (`x`, `y`), width `w` and height `h`
+/// in image pixels, along with the link it leads to. Links can be URLs or Rust module paths such
+/// as `crate::module_a`, which link to the documentation of the module. Since the generated
+/// links are relative, the path from the documented item's page to the crate's documentation
+/// root must be given with the `doc_root` option if it is not the crate root itself. For example,
+/// use `doc_root = "../"` for items inside a top-level module.
+///
+/// ```rust
+/// /// The crate consists of two subsystems. Click on one to learn more.
+/// #[doc = embed_doc_image::embed_image_map!(
+///     "architecture",
+///     "images/architecture.png",
+///     map = [
+///         ((x = 10, y = 20, w = 100, h = 50), href = "crate::module_a"),
+///         ((x = 120, y = 20, w = 100, h = 50), href = "crate::module_b"),
+///     ]
+/// )]
+/// pub struct Foo;
+/// ```
+#[proc_macro]
+pub fn embed_image_map(item: TokenStream) -> TokenStream {
+    let ImageWithMap {
+        image: image_desc,
+        areas,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithMap);
+    let doc_root = (|| -> parse::Result<String> {
+        let doc_root = options.take_str("doc_root")?;
+        options.finish()?;
+        Ok(doc_root
+            .map(|doc_root| doc_root.value())
+            .unwrap_or_default())
+    })();
+    let doc_root = match doc_root {
+        Ok(doc_root) => doc_root,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let name = escape_html(&format!("embed-doc-image-map-{}", image_desc.label));
+    let mut s = format!(
+        "<img src=\"{}\" alt=\"{}\" usemap=\"#{}\"><map name=\"{}\">",
+        data_uri(&mime, &bytes),
+        escape_html(&image_desc.label),
+        name,
+        name
+    );
+    for area in &areas {
+        let [x, y, w, h] = area.rect;
+        let href = area.href.value();
+        s.push_str(&format!(
+            "<area shape=\"rect\" coords=\"{},{},{},{}\" href=\"{}\" alt=\"{}\">",
+            x,
+            y,
+            x.saturating_add(w),
+            y.saturating_add(h),
+            escape_html(&resolve_map_href(&href, &doc_root)),
+            escape_html(&href)
+        ));
+    }
+    s.push_str("</map>");
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, with a text watermark drawn
+/// on top of the image.
+///
+/// This works like [`embed_image!`](macro@embed_image), except that the image is wrapped in an
+/// SVG document which overlays the given `text`, e.g. `"DRAFT"`. This makes it possible to mark
+/// images in e.g. pre-release documentation without modifying the source images. The following
+/// options are supported:
+///
+/// - `text`: the watermark text (required).
+/// - `opacity`: the opacity of the text, between 0 and 1 (0.3 by default).
+/// - `position`: one of `"center"`, `"top-left"`, `"top-right"`, `"bottom-left"` and
+///   `"bottom-right"` (the default).
+/// - `font_size`: the font size in pixels (an eighth of the image height by default).
+///
+/// The dimensions of the image must be known, which is the case for all supported formats
+/// except SVG files without an absolute size.
+///
+/// ```rust
+/// /// ![Diagram][diagram]
+/// #[doc = embed_doc_image::embed_image_watermark!(
+///     "diagram",
+///     "images/diagram.png",
+///     text = "DRAFT",
+///     opacity = 0.3,
+///     position = "bottom-right"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_watermark(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let watermark = (|| -> parse::Result<(String, f64, String, Option<u32>)> {
+        let text = options.take_str("text")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing watermark text, e.g. `text = \"DRAFT\"`.",
+            )
+        })?;
+        let opacity = options.take_float("opacity")?.unwrap_or(0.3);
+        if !(0.0..=1.0).contains(&opacity) {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Opacity must be between 0 and 1.",
+            ));
+        }
+        let position = match options.take_str("position")? {
+            Some(position) => {
+                let valid = [
+                    "center",
+                    "top-left",
+                    "top-right",
+                    "bottom-left",
+                    "bottom-right",
+                ];
+                if !valid.contains(&position.value().as_str()) {
+                    return Err(syn::Error::new(
+                        position.span(),
+                        format!("Invalid position. Expected one of {}.", valid.join(", ")),
+                    ));
+                }
+                position.value()
+            }
+            None => "bottom-right".to_string(),
+        };
+        let font_size = options.take_int("font_size")?;
+        options.finish()?;
+        Ok((text.value(), opacity, position, font_size))
+    })();
+    let (text, opacity, position, font_size) = match watermark {
+        Ok(watermark) => watermark,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let mime = image_mime_type(&image_desc);
+    let (width, height) = dimensions::image_dimensions(&mime, &bytes).unwrap_or_else(|| {
+        panic!(
+            "Unable to determine the dimensions of image ({}), which are needed to place the \
+             watermark",
+            image_desc
+        )
+    });
+    let font_size = font_size.unwrap_or_else(|| (height / 8).max(1));
+    let margin = font_size / 2;
+    let (vertical, horizontal) = position.split_once('-').unwrap_or((&position, &position));
+    let (x, anchor) = match horizontal {
+        "left" => (margin, "start"),
+        "right" => (width.saturating_sub(margin), "end"),
+        _ => (width / 2, "middle"),
+    };
+    let (y, baseline) = match vertical {
+        "top" => (margin, "hanging"),
+        "bottom" => (height.saturating_sub(margin), "alphabetic"),
+        _ => (height / 2, "central"),
+    };
+    let overlay = format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" \
+         font-weight=\"bold\" fill=\"black\" fill-opacity=\"{}\" text-anchor=\"{}\" \
+         dominant-baseline=\"{}\">{}</text>",
+        x,
+        y,
+        font_size,
+        opacity,
+        anchor,
+        baseline,
+        svg::escape(&text)
+    );
+    let svg = svg::wrap_image(width, height, &data_uri(&mime, &bytes), &overlay);
+    let doc_string =
+        produce_doc_string_for_image_data(&image_desc.label, "image/svg+xml", svg.as_bytes());
+
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, together with a text
+/// approximation of the image.
+///
+/// This works like [`embed_image!`](macro@embed_image), but additionally appends a collapsed
+/// `<details>` block with the summary "Text description", which contains the image rendered
+/// as block characters (`█`, `▓`, `▒`, `░`) that are `width` characters wide (40 by default).
+/// This gives a rough idea of the image in contexts that only show text. Note that the block
+/// appears where the macro is placed, not where the image is referenced.
+///
+/// This macro requires the `image` feature.
+///
+/// ```rust
+/// /// ![Logo][logo]
+/// # #[cfg(feature = "image")]
+/// #[doc = embed_doc_image::embed_image_ascii_fallback!("logo", "images/logo.png", width = 40)]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_ascii_fallback(item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(item as ImageWithOptions);
+    let width = (|| -> parse::Result<u32> {
+        let width = match options.take_int("width")? {
+            Some(0) => {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "The width must be positive.",
+                ));
+            }
+            Some(width) => width,
+            None => 40,
+        };
+        options.finish()?;
+        Ok(width)
+    })();
+    let width = match width {
+        Ok(width) => width,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let bytes = read_image(&image_desc);
+    let art = raster::ascii_art(&bytes, width)
+        .unwrap_or_else(|err| panic!("Unable to render image ({}) as text: {}", image_desc, err));
+    let doc_string =
+        produce_doc_string_for_image_data(&image_desc.label, &image_mime_type(&image_desc), &bytes);
+
+    let s = format!(
+        "\n \n {}\n\n<details><summary>Text description</summary><pre>{}</pre></details>",
+        doc_string,
+        escape_html(&art)
+    );
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Prints the raw and `base64`-encoded size of an image during compilation.
+///
+/// This is useful for keeping track of how much each image contributes to the size of the
+/// generated documentation. The path is relative to the crate root, and the macro expands to
+/// nothing. For example, `embed_image_print_size!("images/logo.png")` prints a line like
+///
+/// ```text
+/// warning: embed-doc-image: logo.png raw=12345 base64=16460
+/// ```
+///
+/// to stderr, like other warnings of this crate, where both sizes are given in bytes. The image
+/// is not read, only its size is looked up.
+#[proc_macro]
+pub fn embed_image_print_size(item: TokenStream) -> TokenStream {
+    let path = syn::parse_macro_input!(item as syn::LitStr);
+    let path = PathBuf::from(path.value());
+    let file_name = path
+        .file_name()
+        .unwrap_or_else(|| path.as_os_str())
+        .to_string_lossy()
+        .into_owned();
+    let image_desc = ImageDescription {
+        label: file_name,
+        path,
+    };
+    let full_path = resolve_path(&image_desc.path);
+    let raw_len = std::fs::metadata(&full_path)
+        .unwrap_or_else(|_| {
+            panic!(
+                "Failed to load image ({}) at {}",
+                image_desc,
+                full_path.display()
+            )
+        })
+        .len();
+    emit_warning(&format!(
+        "{} raw={} base64={}",
+        image_desc.label,
+        raw_len,
+        raw_len.div_ceil(3) * 4
+    ));
+    TokenStream::new()
+}
+
+/// Produces dependencies, see [`image_dependency`], on every file in a directory.
+fn dir_dependencies(dir: &Path, recursive: bool) -> proc_macro2::TokenStream {
+    let full_dir = resolve_path(dir);
+    let entries = std::fs::read_dir(&full_dir)
+        .unwrap_or_else(|_| panic!("Failed to read directory {}", full_dir.display()));
+    let mut dependencies = proc_macro2::TokenStream::new();
+    for entry in entries {
+        let entry =
+            entry.unwrap_or_else(|_| panic!("Failed to read directory {}", full_dir.display()));
+        let path = dir.join(entry.file_name());
+        if entry
+            .file_type()
+            .map(|file_type| file_type.is_dir())
+            .unwrap_or(false)
+        {
+            if recursive {
+                dependencies.extend(dir_dependencies(&path, recursive));
+            }
+        } else {
+            dependencies.extend(file_dependency(&path));
+        }
+    }
+    dependencies
+}
+
+/// Tracks all files in a directory, so that changes to any of them cause a rebuild.
+///
+/// This is useful for directories of generated images (e.g. plots produced by a benchmark run),
+/// whose file names may not be known in advance. The macro expands to an unnamed constant for
+/// every file in the directory, which makes the compiler record the files as dependencies of the
+/// crate, like [`embed_doc_image`](macro@embed_doc_image) does for the images it embeds.
+/// Subdirectories are ignored unless `recursive = true` is passed.
+///
+/// As with the images embedded by other macros, the files are only tracked in builds that embed
+/// images, see `EMBED_DOC_IMAGE_ALWAYS_EMBED`. Files added to the directory later are not
+/// noticed until the crate is rebuilt for another reason.
+///
+/// ```rust
+/// embed_doc_image::embed_image_track_dir!("images/generated", recursive = true);
+/// ```
+#[proc_macro]
+pub fn embed_image_track_dir(item: TokenStream) -> TokenStream {
+    let LabelWithOptions {
+        label: dir,
+        mut options,
+    } = syn::parse_macro_input!(item as LabelWithOptions);
+    let recursive = (|| -> parse::Result<bool> {
+        let recursive = options.take_bool("recursive")?.unwrap_or(false);
+        options.finish()?;
+        Ok(recursive)
+    })();
+    match recursive {
+        Ok(_) if !should_embed() => TokenStream::new(),
+        Ok(recursive) => dir_dependencies(Path::new(&dir.value()), recursive).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Produces a doc string embedding every image in a directory.
+///
+/// The directory is given relative to the crate root. Files are sorted by name and labeled by
+/// their position in that order, so that e.g.
+/// `embed_doc_images_from_dir!("images/steps", prefix = "step")` makes the images
+/// `step01.png`, `step02.png`, ... available under the labels `step-0`, `step-1`, and so on.
+/// Files without a recognized image extension are skipped. Subdirectories are ignored unless
+/// `recursive = true` is passed, in which case files are sorted by their path relative to the
+/// given directory.
+///
+/// Like [`embed_image!`](macro@embed_image), this is intended to be used as
+/// `doc = embed_doc_images_from_dir!(...)`, and only embeds the images when the documentation is
+/// built. Other builds only check that the directory exists.
+///
+/// The directory is not tracked: a macro used as `#[doc = ...]` can only expand to a string,
+/// not to the `include_bytes!` constants that make the compiler record files as dependencies,
+/// so editing or adding images does not cause the crate to be rebuilt. Track the directory with
+/// [`embed_image_track_dir!`](macro@embed_image_track_dir), passing the same `recursive` option.
+#[proc_macro]
+pub fn embed_doc_images_from_dir(item: TokenStream) -> TokenStream {
+    let dir_desc = syn::parse_macro_input!(item as DirDescription);
+    let mut files = Vec::new();
+    collect_image_files(&dir_desc.path, dir_desc.recursive, &mut files);
+    if !should_embed() {
+        return quote!("").into();
+    }
+    files.sort();
+
+    let mut s = String::from("\n \n");
+    for (index, path) in files.into_iter().enumerate() {
+        let image_desc = ImageDescription {
+            label: format!("{}-{}", dir_desc.prefix, index),
+            path,
+        };
+        s.push('\n');
+        s.push_str(&produce_doc_string_for_image(&image_desc));
+    }
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Replaces any character other than ASCII letters, digits, `-` and `_` by `-`, so that the
+/// result can be used in file names and as an HTML `id`.
+fn sanitize_label(name: &str) -> String {
+    name.chars()
+        .map(|c| if is_id_char(c) { c } else { '-' })
+        .collect()
+}
+
+/// Derives a label from the file name of a path without its extension, see [`sanitize_label`].
+fn file_stem_label(path: &Path) -> String {
+    sanitize_label(&path.file_stem().unwrap_or_default().to_string_lossy())
+}
+
+/// Collects the images in a directory with labels derived from their file names, as used by
+/// [`embed_doc_image_include_dir!`](macro@embed_doc_image_include_dir).
+///
+/// Each label is the prefix followed by the file name without its extension, where any
+/// character other than ASCII letters, digits, `-` and `_` is replaced by `-`.
+fn labeled_dir_images(dir: &Path, label_prefix: &str) -> Vec<ImageDescription> {
+    let mut files = Vec::new();
+    collect_image_files(dir, false, &mut files);
+    files.sort();
+
+    let mut images: Vec<ImageDescription> = Vec::new();
+    for path in files {
+        let label = format!("{}-{}", label_prefix, file_stem_label(&path));
+        if let Some(other) = images.iter().find(|image| image.label == label) {
+            panic!(
+                "Images {} and {} would both have the label '{}'. Please rename one of them.",
+                other.path.display(),
+                path.display(),
+                label
+            );
+        }
+        images.push(ImageDescription { label, path });
+    }
+    images
+}
+
+/// Parses a directory followed by the `label_prefix` option.
+fn parse_include_dir(input: ParseStream) -> parse::Result<(PathBuf, String)> {
+    let dir = input.parse::<syn::LitStr>()?;
+    let mut options = input.parse::<Options>()?;
+    let label_prefix = options.take_str("label_prefix")?.ok_or_else(|| {
+        syn::Error::new(
+            dir.span(),
+            "Missing label prefix, e.g. `label_prefix = \"icon\"`.",
+        )
+    })?;
+    options.finish()?;
+    Ok((PathBuf::from(dir.value()), label_prefix.value()))
+}
+
+/// Produces a doc string embedding every image in a directory, labeled by file name.
+///
+/// The directory is given relative to the crate root. Every file with a recognized image
+/// extension is embedded under a label made of the `label_prefix` and the file name without
+/// its extension, so that e.g. `embed_doc_image_include_dir!("images/icons", label_prefix = "icon")`
+/// makes `icons/save.png` and `icons/open file.svg` available under the labels `icon-save` and
+/// `icon-open-file`. Characters other than ASCII letters, digits, `-` and `_` are replaced by `-`,
+/// and it is an error if two files end up with the same label. Subdirectories are ignored.
+///
+/// A proc macro used in a doc attribute cannot also define items, so the labels are made
+/// available separately by
+/// [`embed_doc_image_include_dir_labels!`](macro@embed_doc_image_include_dir_labels).
+///
+/// ```rust
+/// /// ![Save][icon-save]
+/// #[doc = embed_doc_image::embed_doc_image_include_dir!("images/icons", label_prefix = "icon")]
+/// fn save() {}
+/// ```
+#[proc_macro]
+pub fn embed_doc_image_include_dir(item: TokenStream) -> TokenStream {
+    let (dir, label_prefix) = syn::parse_macro_input!(item with parse_include_dir);
+    let images = labeled_dir_images(&dir, &label_prefix);
+    if !should_embed() {
+        return quote!("").into();
+    }
+    let mut s = String::from("\n \n");
+    for image_desc in images {
+        s.push('\n');
+        s.push_str(&produce_doc_string_for_image(&image_desc));
+    }
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Produces a `&[&str]` expression listing the labels of the images that
+/// [`embed_doc_image_include_dir!`](macro@embed_doc_image_include_dir) embeds for the same
+/// arguments.
+///
+/// This makes it possible to enumerate the available images programmatically.
+///
+/// ```rust
+/// pub const EMBEDDED_IMAGE_LABELS: &[&str] =
+///     embed_doc_image::embed_doc_image_include_dir_labels!("images/icons", label_prefix = "icon");
+/// ```
+#[proc_macro]
+pub fn embed_doc_image_include_dir_labels(item: TokenStream) -> TokenStream {
+    let (dir, label_prefix) = syn::parse_macro_input!(item with parse_include_dir);
+    let labels = labeled_dir_images(&dir, &label_prefix)
+        .into_iter()
+        .map(|image_desc| image_desc.label);
+    let tokens = quote! {
+        &[#(#labels),*]
+    };
+    tokens.into()
+}
+
+/// Returns the attributes of the given item, if it is an item we support.
+fn item_attrs_mut(item: &mut Item) -> Option<&mut Vec<syn::Attribute>> {
+    match item {
+        Item::Const(ItemConst { attrs, .. })
+        | Item::Enum(ItemEnum { attrs, .. })
+        | Item::ExternCrate(ItemExternCrate { attrs, .. })
+        | Item::Fn(ItemFn { attrs, .. })
+        | Item::ForeignMod(ItemForeignMod { attrs, .. })
+        | Item::Impl(ItemImpl { attrs, .. })
+        | Item::Macro(ItemMacro { attrs, .. })
+        | Item::Macro2(ItemMacro2 { attrs, .. })
+        | Item::Mod(ItemMod { attrs, .. })
+        | Item::Static(ItemStatic { attrs, .. })
+        | Item::Struct(ItemStruct { attrs, .. })
+        | Item::Trait(ItemTrait { attrs, .. })
+        | Item::TraitAlias(ItemTraitAlias { attrs, .. })
+        | Item::Type(ItemType { attrs, .. })
+        | Item::Union(ItemUnion { attrs, .. })
+        | Item::Use(ItemUse { attrs, .. }) => Some(attrs),
+        _ => None,
+    }
+}
+
+/// Appends the image data for the given image to the doc attributes of an item.
+///
+/// This is the core of `embed_doc_image`, kept separate from the handling of the different kinds
+/// of items so that it can be reused for anything that carries attributes. Other proc macros can
+/// use `inject_image_doc` from `embed-doc-image-core` instead, since a `proc-macro` crate can only
+/// export macros.
+///
+/// Returns an item to emit next to the item, see [`image_dependency`].
+#[must_use]
+fn inject_image_doc(
+    attrs: &mut Vec<syn::Attribute>,
+    image_desc: &ImageDescription,
+) -> proc_macro2::TokenStream {
+    if !should_embed() {
+        check_image_exists(image_desc);
+        return proc_macro2::TokenStream::new();
+    }
+    let str = produce_doc_string_for_image(image_desc);
+    // Inject a doc string that "resolves" the image reference and supplies the base64-encoded
+    // data inline, separated from the docs by a blank line so that the Markdown parser treats it
+    // as the "bibliography"
+    append_image_doc(attrs, &str);
+    image_dependency(image_desc)
+}
+
+/// Produces an unnamed constant that includes the image file, so that the compiler records the
+/// file as a dependency of the crate and Cargo rebuilds the crate when the image changes.
+///
+/// Files read by `include_bytes!` end up in the dep-info files that Cargo uses to decide what to
+/// rebuild. Note that `cargo doc` does not use rustdoc's dep-info, but re-documents a package
+/// whenever a file in it changes. Unnamed constants do not show up in the documentation.
+fn image_dependency(image_desc: &ImageDescription) -> proc_macro2::TokenStream {
+    file_dependency(&image_desc.path)
+}
+
+/// Produces an unnamed constant that includes the file at the given path, relative to the crate
+/// root, see [`image_dependency`].
+fn file_dependency(path: &Path) -> proc_macro2::TokenStream {
+    match resolve_path(path).to_str() {
+        Some(path) => quote! {
+            const _: &[u8] = ::core::include_bytes!(#path);
+        },
+        // `include_bytes!` only takes string literals, so such paths cannot be tracked
+        None => proc_macro2::TokenStream::new(),
+    }
+}
+
+/// Parses the arguments of a `#[doc_image(label = "..", path = "..")]` field attribute.
+fn parse_field_image(attr: &syn::Attribute) -> parse::Result<ImageDescription> {
+    let mut options = attr.parse_args_with(Options::parse_list)?;
+    let mut take_required = |key: &str| {
+        options.take_str(key)?.ok_or_else(|| {
+            syn::Error::new_spanned(attr, format!("Missing `{}` in `doc_image` attribute.", key))
+        })
+    };
+    let label = take_required("label")?;
+    let path = take_required("path")?;
+    options.finish()?;
+    Ok(ImageDescription {
+        label: label.value(),
+        path: PathBuf::from(path.value()),
+    })
+}
+
+/// Embeds several images at once in the documentation of an item.
+///
+/// Given a list of `("label", "path")` pairs, the images are embedded as if
+/// [`embed_doc_image`](macro@embed_doc_image) had been applied to the item once per image. Each
+/// label may only be given once, and an empty list is an error.
+///
+/// ```rust
+/// # use embed_doc_image::embed_doc_images;
+/// /// Before: ![before][before]. After: ![after][after].
+/// #[embed_doc_images(("before", "images/before.png"), ("after", "images/after.png"))]
+/// pub fn transform() {}
+/// ```
+///
+/// To declare the images of a struct on its fields instead, use
+/// [`embed_field_doc_images`](macro@embed_field_doc_images).
+#[proc_macro_attribute]
+pub fn embed_doc_images(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let list = syn::parse_macro_input!(attr as ImageDescriptionList);
+    let mut input: syn::Item = syn::parse_macro_input!(item);
+    match item_attrs_mut(&mut input) {
+        Some(attrs) => {
+            let dependencies: proc_macro2::TokenStream = list
+                .images
+                .iter()
+                .map(|image| inject_image_doc(attrs, image))
+                .collect();
+            quote! { #input #dependencies }
+        }
+        None => syn::Error::new_spanned(
+            input,
+            "Unsupported item. Cannot apply attribute to the given item.",
+        )
+        .to_compile_error(),
+    }
+    .into()
+}
+
+/// Embeds the images declared on the fields of a struct in the documentation of the struct.
+///
+/// Each field may carry any number of `#[doc_image(label = "..", path = "..")]` attributes.
+/// These are removed from the fields, and the corresponding images are embedded as if
+/// [`embed_doc_image`](macro@embed_doc_image) had been applied to the struct once per image.
+/// This keeps the image paths next to the fields they illustrate.
+///
+/// ```rust
+/// # use embed_doc_image::embed_field_doc_images;
+/// # struct Icon;
+/// # struct State;
+/// /// A button with an icon. See ![icon][button-icon] and ![pressed][button-pressed].
+/// #[embed_field_doc_images]
+/// pub struct Button {
+///     #[doc_image(label = "button-icon", path = "images/icon.png")]
+///     icon: Icon,
+///     #[doc_image(label = "button-pressed", path = "images/pressed.png")]
+///     state: State,
+/// }
+/// ```
+///
+/// This was originally proposed as `#[derive(EmbedDocImages)]` in a separate
+/// `embed-doc-image-derive` crate. A derive macro cannot do the job, though: derives can only
+/// add new items next to the struct, and cannot change the attributes of the struct itself,
+/// which is where its documentation lives. An attribute macro can, and as this crate is already
+/// a proc macro crate, it needs neither a companion crate nor a `derive` feature.
+#[proc_macro_attribute]
+pub fn embed_field_doc_images(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::TokenStream::from(attr)
+                .into_iter()
+                .next()
+                .map_or_else(proc_macro2::Span::call_site, |token| token.span()),
+            "`embed_field_doc_images` does not take any arguments.",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let mut input: ItemStruct = syn::parse_macro_input!(item);
+    let mut images = Vec::new();
+    for field in input.fields.iter_mut() {
+        let mut result = Ok(());
+        field.attrs.retain(|attr| {
+            if !attr.path.is_ident("doc_image") {
+                return true;
+            }
+            match parse_field_image(attr) {
+                Ok(image) => images.push(image),
+                Err(err) => result = Err(err),
+            }
+            false
+        });
+        if let Err(err) = result {
+            return err.to_compile_error().into();
+        }
+    }
+    let dependencies: proc_macro2::TokenStream = images
+        .iter()
+        .map(|image| inject_image_doc(&mut input.attrs, image))
+        .collect();
+    quote! { #input #dependencies }.into()
+}
+
+/// Embeds the images declared on the fields of a struct in the documentation of the fields.
+///
+/// Each field may carry any number of `#[field_doc_image("label", "path")]` attributes, which
+/// work like [`embed_doc_image`](macro@embed_doc_image) on the field itself. Attribute macros
+/// cannot be applied to fields directly, so this attribute on the struct does the work and
+/// removes the helper attributes. To show the images in the documentation of the struct instead,
+/// use [`embed_field_doc_images`](macro@embed_field_doc_images).
+///
+/// ```rust
+/// use embed_doc_image::embed_struct_field_images;
+///
+/// # pub struct Icon;
+/// #[embed_struct_field_images]
+/// pub struct Button {
+///     /// The icon shown on the button, e.g. ![icon][button-icon]
+///     #[field_doc_image("button-icon", "images/icon.png")]
+///     pub icon: Icon,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn embed_struct_field_images(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::TokenStream::from(attr)
+                .into_iter()
+                .next()
+                .map_or_else(proc_macro2::Span::call_site, |token| token.span()),
+            "`embed_struct_field_images` does not take any arguments.",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let mut input: ItemStruct = syn::parse_macro_input!(item);
+
+    let mut dependencies = proc_macro2::TokenStream::new();
+    for field in input.fields.iter_mut() {
+        let mut images = Vec::new();
+        let mut result = Ok(());
+        field.attrs.retain(|attr| {
+            if !attr.path.is_ident("field_doc_image") {
+                return true;
+            }
+            match attr.parse_args::<ImageDescription>() {
+                Ok(image) => images.push(image),
+                Err(err) => result = Err(err),
+            }
+            false
+        });
+        if let Err(err) = result {
+            return err.to_compile_error().into();
+        }
+        for image in &images {
+            dependencies.extend(inject_image_doc(&mut field.attrs, image));
+        }
+    }
+    quote! { #input #dependencies }.into()
+}
+
+/// Embeds one of several images depending on `cfg` predicates.
+///
+/// This works like [`embed_doc_image`](macro@embed_doc_image), but selects the image for the
+/// given label based on `cfg` predicates, which are tried in order. The `default` image is used
+/// if none of the predicates hold. If no default is given and none of the predicates hold, no
+/// image is embedded.
+///
+/// ```rust
+/// # use embed_doc_image::embed_image_conditional;
+/// /// ![Architecture][architecture]
+/// #[embed_image_conditional(
+///     "architecture",
+///     cfg(target_os = "linux") => "images/linux_diagram.png",
+///     cfg(target_os = "windows") => "images/windows_diagram.png",
+///     default => "images/generic_diagram.png"
+/// )]
+/// fn foobar() {}
+/// ```
+///
+/// Note that when the documentation is built, all candidate images are read and encoded, but
+/// only the selected one ends up in the documentation. Other builds only check that the images
+/// exist.
+#[proc_macro_attribute]
+pub fn embed_image_conditional(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let images = syn::parse_macro_input!(attr as ConditionalImages);
+    let mut input: syn::Item = syn::parse_macro_input!(item);
+    let attrs = match item_attrs_mut(&mut input) {
+        Some(attrs) => attrs,
+        None => {
+            return syn::Error::new_spanned(
+                input,
+                "Unsupported item. Cannot apply attribute to the given item.",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    let candidates: Vec<_> = images
+        .branches
+        .iter()
+        .map(|(_, path)| path)
+        .chain(&images.default)
+        .map(|path| ImageDescription {
+            label: images.label.clone(),
+            path: path.clone(),
+        })
+        .collect();
+    if !should_embed() {
+        candidates.iter().for_each(check_image_exists);
+        return input.into_token_stream().into();
+    }
+    let dependencies: proc_macro2::TokenStream = candidates.iter().map(image_dependency).collect();
+
+    append_doc_separator(attrs);
+    let predicates: Vec<_> = images.branches.iter().map(|(pred, _)| pred).collect();
+    for (index, predicate) in predicates.iter().enumerate() {
+        // A branch only applies if none of the preceding branches do
+        let preceding = &predicates[..index];
+        let str = produce_doc_string_for_image(&candidates[index]);
+        attrs.push(syn::parse_quote! {
+            #[cfg_attr(all(#predicate, not(any(#(#preceding),*))), doc = #str)]
+        });
+    }
+    if images.default.is_some() {
+        let str = produce_doc_string_for_image(&candidates[predicates.len()]);
+        attrs.push(syn::parse_quote! {
+            #[cfg_attr(not(any(#(#predicates),*)), doc = #str)]
+        });
+    }
+    quote! { #input #dependencies }.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation, selecting the image by the
+/// value of an environment variable.
+///
+/// The variable is read when the macro is expanded, i.e. when the documentation is built, and
+/// its value selects one of the given paths. The `default` image is used if the variable is not
+/// set or has a value that is not listed. If no default is given, such values are a compile
+/// error. Only the selected image is read. Cargo does not know that the macro reads the
+/// variable, so after changing it, touch a source file of the crate to rebuild the
+/// documentation.
+///
+/// ```rust
+/// /// ![Architecture][architecture]
+/// #[doc = embed_doc_image::embed_image_env_conditional!(
+///     "architecture",
+///     env = "DEPLOY_ENV",
+///     "dev" => "images/dev_arch.png",
+///     "prod" => "images/prod_arch.png",
+///     default => "images/generic_arch.png"
+/// )]
+/// fn foobar() {}
+/// ```
+#[proc_macro]
+pub fn embed_image_env_conditional(item: TokenStream) -> TokenStream {
+    let images = syn::parse_macro_input!(item as EnvConditionalImages);
+    let var = images.var.value();
+    let value = std::env::var_os(&var);
+    let selected = value
+        .as_ref()
+        .and_then(|value| value.to_str())
+        .and_then(|value| {
+            images
+                .branches
+                .iter()
+                .find(|(expected, _)| expected == value)
+                .map(|(_, path)| path)
+        })
+        .or(images.default.as_ref());
+    let path = match (selected, value) {
+        (Some(path), _) => path.clone(),
+        (None, None) => {
+            let message = format!(
+                "The environment variable `{}` is not set, and no default image was given.",
+                var
+            );
+            return syn::Error::new(images.var.span(), message)
+                .to_compile_error()
+                .into();
+        }
+        (None, Some(value)) => {
+            let expected: Vec<_> = images
+                .branches
+                .iter()
+                .map(|(expected, _)| format!("`{}`", expected))
+                .collect();
+            let message = format!(
+                "The environment variable `{}` has the unknown value `{}`, and no default image \
+                 was given. Expected one of {}.",
+                var,
+                value.to_string_lossy(),
+                expected.join(", ")
+            );
+            return syn::Error::new(images.var.span(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let image_desc = ImageDescription {
+        label: images.label,
+        path,
+    };
+    if !embeds_images([&image_desc]) {
+        return quote!("").into();
+    }
+    let doc_string = produce_doc_string_for_image(&image_desc);
+    let s = format!("\n \n {}", doc_string);
+    let tokens = quote! {
+        #s
+    };
+    tokens.into()
+}
+
+/// Image extensions tried by [`embed_doc_image_auto`](macro@embed_doc_image_auto), in order of
+/// priority. Vector images come first, followed by lossless and then lossy formats.
+const AUTO_EXTENSIONS: [&str; 9] = [
+    "svg", "png", "webp", "avif", "jpg", "jpeg", "gif", "bmp", "ico",
+];
+
+/// Collects the labels of image references (`![alt][label]`, `![label][]` and `![label]`) in
+/// Markdown that are not defined in the Markdown itself, in order of first appearance.
+///
+/// Fenced code blocks are skipped, as are inner attributes (`#![...]`) in indented code.
+fn undefined_image_labels(markdown: &str) -> Vec<String> {
+    let mut referenced: Vec<String> = Vec::new();
+    let mut defined = Vec::new();
+    let mut in_code_block = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+        if let Some((label, _)) = trimmed
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once("]:"))
+        {
+            defined.push(label.to_string());
+            continue;
+        }
+        let mut rest = line;
+        while let Some(start) = rest.find("![") {
+            let is_attribute = rest[..start].ends_with('#');
+            let after_alt = &rest[start + 2..];
+            let (alt, after) = match after_alt.split_once(']') {
+                Some(split) => split,
+                None => break,
+            };
+            rest = after;
+            if is_attribute || after.starts_with('(') {
+                continue;
+            }
+            let label = match after
+                .strip_prefix('[')
+                .and_then(|after| after.split_once(']'))
+            {
+                Some((label, after)) => {
+                    rest = after;
+                    if label.is_empty() { alt } else { label }
+                }
+                None => alt,
+            };
+            if !label.is_empty() && !referenced.iter().any(|other| other == label) {
+                referenced.push(label.to_string());
+            }
+        }
+    }
+    referenced.retain(|label| !defined.contains(label));
+    referenced
+}
+
+/// Embeds the images referenced in the documentation of an item, finding them by label.
+///
+/// Every image reference without a definition in the documentation, e.g. `![Ferris][ferris]`,
+/// is resolved by looking for a file named after the label in the given directory, i.e.
+/// `images/ferris.svg`, `images/ferris.png` and so on. The directory is relative to the crate
+/// root. If several files match, the extensions are preferred in the order `svg`, `png`, `webp`,
+/// `avif`, `jpg`, `jpeg`, `gif`, `bmp`, `ico`, and a warning is printed. It is an error if no
+/// file matches. Links are not affected, so intra-doc links can be used as usual.
+///
+/// ```rust
+/// use embed_doc_image::embed_doc_image_auto;
+///
+/// /// ![Ferris][ferris] and ![Logo][logo].
+/// #[embed_doc_image_auto("images")]
+/// fn foobar() {}
+/// ```
+///
+/// Only the documentation written directly on the item is searched, and attributes below this
+/// one have not been applied yet. It should therefore be placed after any other attributes that
+/// add documentation.
+#[proc_macro_attribute]
+pub fn embed_doc_image_auto(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let dir = syn::parse_macro_input!(attr as syn::LitStr);
+    let mut input: syn::Item = syn::parse_macro_input!(item);
+    let attrs = match item_attrs_mut(&mut input) {
+        Some(attrs) => attrs,
+        None => {
+            return syn::Error::new_spanned(
+                input,
+                "Unsupported item. Cannot apply attribute to the given item.",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    let markdown: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(s),
+                ..
+            })) => Some(s.value()),
+            _ => None,
+        })
+        .collect();
+    let dir_path = PathBuf::from(dir.value());
+    let mut images = Vec::new();
+    for label in undefined_image_labels(&markdown.join("\n")) {
+        let mut candidates = AUTO_EXTENSIONS
+            .iter()
+            .map(|extension| dir_path.join(format!("{}.{}", label, extension)))
+            .filter(|path| resolve_path(path).is_file());
+        let path = match candidates.next() {
+            Some(path) => path,
+            None => {
+                return syn::Error::new(
+                    dir.span(),
+                    format!(
+                        "No image found for the label '{}' in {}. Expected a file named {}.<ext>, \
+                         where <ext> is one of {}.",
+                        label,
+                        dir_path.display(),
+                        label,
+                        AUTO_EXTENSIONS.join(", ")
+                    ),
+                )
+                .to_compile_error()
+                .into();
+            }
+        };
+        let ignored: Vec<_> = candidates.map(|path| path.display().to_string()).collect();
+        if !ignored.is_empty() {
+            emit_warning(&format!(
+                "Several images match the label '{}'. Using {} and ignoring {}.",
+                label,
+                path.display(),
+                ignored.join(", ")
+            ));
+        }
+        images.push(ImageDescription { label, path });
+    }
+    let dependencies: proc_macro2::TokenStream = images
+        .iter()
+        .map(|image| inject_image_doc(attrs, image))
+        .collect();
+    quote! { #input #dependencies }.into()
+}
+
+/// Appends a numbered figure with a caption to the documentation of an item.
+///
+/// Figures are numbered sequentially, in the order in which they appear in a source file, and
+/// the numbering starts from 1 again in every file. Modules that live in their own file therefore
+/// get their own numbering, while inline `mod` blocks share the numbering of the file they are
+/// in, since a macro cannot tell which module it is expanded in. Compilers older than Rust 1.88
+/// cannot tell the macro which file it is in either, so figures are numbered across the whole
+/// crate instead. The caption reads `Figure N: Caption text`. The label is used as the `id` of
+/// the figure, so that it can be linked to with `[Figure 1](#label)`, and may therefore only
+/// contain ASCII letters, digits, `-` and `_`.
+///
+/// The attribute name is long, so it may be convenient to import it under a shorter name:
+///
+/// ```rust
+/// use embed_doc_image::embed_image_figure_number as figure;
+///
+/// /// The overall architecture.
+/// #[figure("architecture", "images/architecture.svg", caption = "Overview of the components")]
+/// pub struct Engine;
+///
+/// /// The pipeline, see also the [architecture](#architecture).
+/// #[figure("pipeline", "images/pipeline.svg", caption = "The processing pipeline")]
+/// pub fn run() {}
+/// ```
+///
+/// Numbers are assigned when the attribute is expanded, so figures behind `#[cfg_attr(..)]` or
+/// on items that are configured away may leave gaps or shift the numbering. IDEs expand macros
+/// on demand rather than in source order, so the numbers they show in documentation on hover
+/// may be off. Like [`embed_doc_image`](macro@embed_doc_image), the attribute only embeds the
+/// figure when the documentation is built.
+#[proc_macro_attribute]
+pub fn embed_image_figure_number(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let ImageWithOptions {
+        image: image_desc,
+        mut options,
+    } = syn::parse_macro_input!(attr as ImageWithOptions);
+    let caption = (|| -> parse::Result<syn::LitStr> {
+        check_id_label(&image_desc.label, proc_macro2::Span::call_site())?;
+        let caption = options.take_str("caption")?.ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "Missing caption, e.g. `caption = \"Caption text\"`.",
+            )
+        })?;
+        options.finish()?;
+        Ok(caption)
+    })();
+    let caption = match caption {
+        Ok(caption) => caption.value(),
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let mut input: syn::Item = syn::parse_macro_input!(item);
+    let attrs = match item_attrs_mut(&mut input) {
+        Some(attrs) => attrs,
+        None => {
+            return syn::Error::new_spanned(
+                input,
+                "Unsupported item. Cannot apply attribute to the given item.",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    check_label(&image_desc.label);
+    if !should_embed() {
+        check_image_exists(&image_desc);
+        return input.into_token_stream().into();
+    }
+    let number = figures::next_number(figures::call_site());
+    let str = format!(
+        "<figure id=\"{id}\"><img src=\"{src}\" alt=\"{caption}\">\
+         <figcaption>Figure {number}: {caption}</figcaption></figure>",
+        id = escape_html(&image_desc.label),
+        src = data_uri(&image_mime_type(&image_desc), &read_image(&image_desc)),
+        caption = escape_html(&caption),
+        number = number
+    );
+    append_image_doc(attrs, &str);
+    let dependency = image_dependency(&image_desc);
+    quote! { #input #dependency }.into()
+}
+
+/// Produces a doc string for inclusion in Markdown documentation.
+///
+/// Please see the crate-level documentation for usage instructions.
+#[proc_macro_attribute]
+pub fn embed_doc_image(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let image_desc = syn::parse_macro_input!(attr as ImageDescription);
+    let mut input: syn::Item = syn::parse_macro_input!(item);
+    match item_attrs_mut(&mut input) {
+        Some(attrs) => {
+            let dependency = inject_image_doc(attrs, &image_desc);
+            quote! { #input #dependency }
+        }
+        None => syn::Error::new_spanned(
+            input,
+            "Unsupported item. Cannot apply attribute to the given item.",
+        )
+        .to_compile_error(),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse::Parser;
+
+    /// Returns the peak resident set size of this process in bytes, after resetting it if asked
+    /// to. The test binary of a proc macro crate does not use a `#[global_allocator]`, so we
+    /// measure the memory use of the whole process instead.
+    #[cfg(target_os = "linux")]
+    fn peak_resident_size(reset: bool) -> usize {
+        if reset {
+            std::fs::write("/proc/self/clear_refs", "5").unwrap();
+        }
+        let status = std::fs::read_to_string("/proc/self/status").unwrap();
+        let line = status
+            .lines()
+            .find(|line| line.starts_with("VmHWM:"))
+            .unwrap();
+        let kib: usize = line.split_whitespace().nth(1).unwrap().parse().unwrap();
+        kib * 1024
+    }
+
+    /// Encodes a 10 MB image from file and reports the time and peak memory it takes. Since the
+    /// memory is measured for the whole process, the benchmark must run on its own:
+    /// `cargo test --release --lib benchmark_encode_base64_image -- --ignored --nocapture`.
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore]
+    fn benchmark_encode_base64_image() {
+        let raw_len = 10 * 1024 * 1024;
+        let path = tools::temp_path("benchmark_encode_base64_image", "gif");
+        let bytes: Vec<u8> = (0..raw_len).map(|i| (i * 7 % 251) as u8).collect();
+        std::fs::write(&path, &bytes).unwrap();
+        let expected = base64::encode(&bytes);
+        drop(bytes);
+
+        let image = ImageDescription {
+            label: "benchmark".to_string(),
+            path: path.clone(),
+        };
+        let before = peak_resident_size(true);
+        let start = std::time::Instant::now();
+        let encoded = encode_base64_image(&image);
+        let elapsed = start.elapsed();
+        let peak = peak_resident_size(false) - before;
+        std::fs::remove_file(&path).unwrap();
+        println!(
+            "Encoded {:.1} MiB in {:?}, using at most {:.1} MiB",
+            raw_len as f64 / (1024.0 * 1024.0),
+            elapsed,
+            peak as f64 / (1024.0 * 1024.0)
+        );
+        assert!(encoded == expected, "the encoding differs");
+
+        // Only the encoding should be held in memory. Reading the whole file up front, or
+        // keeping a copy of the encoding for deduplication, would add at least 10 MB to that.
+        let capacity = raw_len * 4 / 3 + 4;
+        assert!(
+            peak < capacity + 2 * 1024 * 1024,
+            "used {} bytes for an image of {} bytes",
+            peak,
+            raw_len
+        );
+    }
+
+    fn parse_error<T: Parse>(input: &str) -> String {
+        match syn::parse_str::<T>(input) {
+            Ok(_) => panic!("expected an error for {:?}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_image_descriptions() {
+        let image: ImageDescription = syn::parse_str(r#""ferris", "images/ferris.png""#).unwrap();
+        assert_eq!(image.label, "ferris");
+        assert_eq!(image.path, Path::new("images/ferris.png"));
+        assert_eq!(
+            image.to_string(),
+            "label='ferris', path='images/ferris.png'"
+        );
+        assert!(syn::parse_str::<ImageDescription>(r#""ferris""#).is_err());
+        assert!(syn::parse_str::<ImageDescription>(r#"ferris, "ferris.png""#).is_err());
+    }
+
+    #[test]
+    fn parses_image_description_lists() {
+        let list: ImageDescriptionList =
+            syn::parse_str(r#"("a", "a.png"), ("b", "b.svg"),"#).unwrap();
+        let labels: Vec<_> = list.images.iter().map(|image| &image.label[..]).collect();
+        assert_eq!(labels, ["a", "b"]);
+    }
+
+    #[test]
+    fn rejects_invalid_image_description_lists() {
+        assert_eq!(
+            parse_error::<ImageDescriptionList>(r#"("a", "a.png"), ("a", "b.png")"#),
+            "The label `a` is given more than once."
+        );
+        assert_eq!(
+            parse_error::<ImageDescriptionList>(r#""a", "a.png""#),
+            "Expected a `(\"label\", \"path\")` pair."
+        );
+        assert!(parse_error::<ImageDescriptionList>("").starts_with("Expected at least one"));
+    }
+
+    #[test]
+    fn parses_rectangles() {
+        let rect = |input| parse_rect.parse_str(input);
+        assert_eq!(rect("(x=1, y=2, w=3, h=4)").unwrap(), [1, 2, 3, 4]);
+        assert_eq!(rect("(h=4, w=3, y=2, x=1,)").unwrap(), [1, 2, 3, 4]);
+        assert_eq!(
+            rect("(x=1, y=2, w=3)").unwrap_err().to_string(),
+            "Rectangles must specify all of `x`, `y`, `w` and `h`."
+        );
+        assert_eq!(
+            rect("(x=1, y=2, w=3, z=4)").unwrap_err().to_string(),
+            "Expected one of `x`, `y`, `w` and `h`."
+        );
+        assert!(rect("(x=-1, y=2, w=3, h=4)").is_err());
+    }
+
+    #[test]
+    fn parses_data_points() {
+        let point = |input| parse_data_point.parse_str(input);
+        assert_eq!(
+            point(r#"("Vec", 12.5)"#).unwrap(),
+            ("Vec".to_string(), 12.5)
+        );
+        assert_eq!(
+            point(r#"("Delta", -3)"#).unwrap(),
+            ("Delta".to_string(), -3.0)
+        );
+        assert_eq!(
+            point(r#"("Name", "12")"#).unwrap_err().to_string(),
+            "Expected a number."
+        );
+    }
+
+    #[test]
+    fn produces_data_uris() {
+        assert_eq!(data_uri("image/png", b"png"), "data:image/png;base64,cG5n");
+        assert_eq!(data_uri("image/gif", b""), "data:image/gif;base64,");
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape_html(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry's&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn escapes_css_strings() {
+        assert_eq!(
+            escape_css_string(r#"Fira "Sans" \ Mono"#),
+            r#"Fira \"Sans\" \\ Mono"#
+        );
+        assert_eq!(escape_css_string("</style>"), "\\3c /style>");
+        assert_eq!(escape_css_string("a\nb"), "a\\a b");
+    }
+
+    #[test]
+    fn accepts_only_labels_that_are_valid_ids() {
+        let span = proc_macro2::Span::call_site();
+        assert!(check_id_label("arch-overview_2", span).is_ok());
+        assert!(check_id_label("", span).is_err());
+        assert!(check_id_label("a b", span).is_err());
+        // Would break out of the selectors and `@keyframes` rules the label is used in
+        assert!(check_id_label("x{}", span).is_err());
+        assert!(check_id_label("a.b", span).is_err());
+    }
+
+    #[cfg(feature = "content-hash")]
+    #[test]
+    fn hashes_with_sha256() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn rejects_comment_values_with_double_dashes() {
+        let span = proc_macro2::Span::call_site();
+        assert!(check_comment_value("2.1.0-beta-1", span, "Version").is_ok());
+        let err = check_comment_value("2.1 --><script>", span, "Version").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Version `2.1 --><script>` must not contain `--`, which would end the HTML comment \
+             it is placed in."
+        );
+        assert!(check_comment_value("a--b", span, "Label").is_err());
+    }
+
+    #[test]
+    fn rejects_comment_values_with_control_characters() {
+        let span = proc_macro2::Span::call_site();
+        let err = check_comment_value("2.1\n\n<script>", span, "Version").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Version \"2.1\\n\\n<script>\" must not contain line breaks or other control \
+             characters."
+        );
+        assert!(check_comment_value("a\rb", span, "Label").is_err());
+        assert!(check_comment_value("a\u{0}b", span, "Label").is_err());
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_hex_color("#ff5733"), Some([0xff, 0x57, 0x33]));
+        assert_eq!(parse_hex_color("#F53"), Some([0xff, 0x55, 0x33]));
+        assert_eq!(parse_hex_color("ff5733"), None);
+        assert_eq!(parse_hex_color("#ff573"), None);
+        assert_eq!(parse_hex_color("#gg5733"), None);
+        assert_eq!(parse_hex_color("#+f5733"), None);
+        assert_eq!(parse_hex_color("#é5"), None);
+    }
+
+    #[test]
+    fn recognizes_browser_images() {
+        assert!(is_browser_image("image/png"));
+        assert!(is_browser_image("image/svg+xml"));
+        assert!(!is_browser_image("image/tiff"));
+        assert!(!is_browser_image("application/pdf"));
+    }
+
+    #[test]
+    fn recognizes_videos_fonts_and_svg_resources() {
+        assert_eq!(video_mime_type("WebM"), Some("video/webm"));
+        assert_eq!(video_mime_type("ogv"), Some("video/ogg"));
+        assert_eq!(video_mime_type("png"), None);
+        assert_eq!(font_mime_type("OTF"), Some("font/otf"));
+        assert_eq!(font_mime_type("svg"), None);
+        assert_eq!(svg_resource_mime_type("CSS"), Some("text/css"));
+        assert_eq!(svg_resource_mime_type("png"), Some("image/png"));
+        assert_eq!(svg_resource_mime_type("woff2"), Some("font/woff2"));
+        assert_eq!(svg_resource_mime_type("webm"), None);
+    }
+
+    #[test]
+    fn resolves_image_map_links() {
+        let root = "../";
+        assert_eq!(resolve_map_href("crate", root), "../index.html");
+        assert_eq!(resolve_map_href("crate::a::b", root), "../a/b/index.html");
+        assert_eq!(
+            resolve_map_href("https://example.com", root),
+            "https://example.com"
+        );
+        assert_eq!(resolve_map_href("crate::a b", root), "crate::a b");
+        assert_eq!(resolve_map_href("crate::", root), "crate::");
+    }
+
+    #[test]
+    fn derives_labels_from_file_names() {
+        assert_eq!(file_stem_label(Path::new("images/step 1.png")), "step-1");
+        assert_eq!(file_stem_label(Path::new("a_b-c.d.svg")), "a_b-c-d");
+        assert_eq!(file_stem_label(Path::new("ä.png")), "-");
+        // Labels given by the user are sanitized the same way, e.g. for debug output
+        assert_eq!(sanitize_label("../step 1.png"), "---step-1-png");
+    }
+
+    #[test]
+    fn finds_undefined_image_labels() {
+        let markdown = "\
+            ![Ferris][ferris] ![corro][] ![Corro][corro] ![logo]\n\
+            ![Inline](images/inline.png) ![Defined][defined]\n\
+            ```\n\
+            ![In code][code]\n\
+            ```\n\
+            \x20   #![no_std]\n\
+            [defined]: images/defined.png\n";
+        assert_eq!(
+            undefined_image_labels(markdown),
+            ["ferris", "corro", "logo"]
+        );
+        assert!(undefined_image_labels("![unterminated").is_empty());
     }
-    .into()
 }
@@ -0,0 +1,96 @@
+//! Identifying the format of files from their contents.
+//!
+//! Most formats start with a fixed signature ("magic bytes"). SVG files are text, so for them we
+//! look for an `<svg` tag near the start instead.
+
+use crate::{png, webp};
+
+/// Number of bytes at the start of a text file in which to look for an `<svg` tag. This leaves
+/// room for an XML declaration, comments and a doctype.
+const SVG_SNIFF_LEN: usize = 4096;
+
+/// Signatures of formats that can be identified by a fixed prefix, with their MIME types.
+const SIGNATURES: [(&[u8], &str); 10] = [
+    (b"\xFF\xD8\xFF", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"II*\0", "image/tiff"),
+    (b"MM\0*", "image/tiff"),
+    (b"\0\0\x01\0", "image/vnd.microsoft.icon"),
+    (b"%PDF-", "application/pdf"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+/// Determines the MIME type of a file from its contents.
+///
+/// Returns `None` if the format is not recognized.
+pub(crate) fn sniff_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    if png::is_png(bytes) {
+        return Some("image/png");
+    }
+    if webp::is_webp(bytes) {
+        return Some("image/webp");
+    }
+    // AVIF files are ISO base media files, starting with an `ftyp` box naming the brand
+    if bytes.get(4..8) == Some(b"ftyp") && matches!(bytes.get(8..12), Some(b"avif" | b"avis")) {
+        return Some("image/avif");
+    }
+    if let Some((_, mime)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| bytes.starts_with(signature))
+    {
+        return Some(mime);
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(SVG_SNIFF_LEN)]);
+    if head.contains("<svg") {
+        return Some("image/svg+xml");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_signatures() {
+        let cases: [(&[u8], &str); 11] = [
+            (b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR", "image/png"),
+            (b"RIFF\x24\0\0\0WEBPVP8 ", "image/webp"),
+            (b"\0\0\0\x1cftypavif\0\0\0\0", "image/avif"),
+            (b"\0\0\0\x1cftypavis\0\0\0\0", "image/avif"),
+            (b"\xFF\xD8\xFF\xE0\0\x10JFIF", "image/jpeg"),
+            (b"GIF89a\x01\0\x01\0", "image/gif"),
+            (b"BM\x3a\0\0\0", "image/bmp"),
+            (b"II*\0\x08\0\0\0", "image/tiff"),
+            (b"\0\0\x01\0\x01\0", "image/vnd.microsoft.icon"),
+            (b"%PDF-1.7\n", "application/pdf"),
+            (b"wOF2\0\x01\0\0", "font/woff2"),
+        ];
+        for (bytes, mime) in cases {
+            assert_eq!(sniff_mime_type(bytes), Some(mime), "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn sniffs_svg_near_the_start() {
+        let svg =
+            b"<?xml version=\"1.0\"?>\n<!DOCTYPE svg>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>";
+        assert_eq!(sniff_mime_type(svg), Some("image/svg+xml"));
+
+        let mut late = vec![b' '; SVG_SNIFF_LEN];
+        late.extend_from_slice(b"<svg/>");
+        assert_eq!(sniff_mime_type(&late), None);
+    }
+
+    #[test]
+    fn does_not_recognize_other_files() {
+        assert_eq!(sniff_mime_type(b""), None);
+        assert_eq!(sniff_mime_type(b"hello"), None);
+        assert_eq!(sniff_mime_type(b"\0\0\0\x1cftypmp42"), None);
+        assert_eq!(sniff_mime_type(b"RIFF\x24\0\0\0WAVE"), None);
+        assert_eq!(sniff_mime_type(b"\x89PNG"), None);
+    }
+}
@@ -0,0 +1,99 @@
+//! Reading of descriptions that are embedded in image files.
+
+use crate::png;
+
+/// Returns the description of an image, as stored in the `Description` text chunk of a PNG file
+/// or the `ImageDescription` EXIF tag of a JPEG file.
+///
+/// Returns `None` if the image has no (non-blank) description.
+pub(crate) fn description(mime: &str, bytes: &[u8]) -> Option<String> {
+    let description = match mime {
+        "image/png" => png::text(bytes, "Description"),
+        "image/jpeg" => exif_description(bytes),
+        _ => None,
+    }?;
+    let description = description.trim_end_matches('\0').trim();
+    if description.is_empty() {
+        None
+    } else {
+        Some(description.to_string())
+    }
+}
+
+#[cfg(feature = "exif")]
+fn exif_description(bytes: &[u8]) -> Option<String> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(bytes))
+        .ok()?;
+    let field = exif.get_field(exif::Tag::ImageDescription, exif::In::PRIMARY)?;
+    match &field.value {
+        exif::Value::Ascii(values) => values
+            .first()
+            .map(|value| String::from_utf8_lossy(value).into_owned()),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "exif"))]
+fn exif_description(_bytes: &[u8]) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a PNG file containing only a `tEXt` chunk with the given keyword and text.
+    fn png_with_text(keyword: &str, text: &str) -> Vec<u8> {
+        let data = [keyword.as_bytes(), b"\0", text.as_bytes()].concat();
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(b"tEXt");
+        bytes.extend_from_slice(&data);
+        bytes.extend_from_slice(&[0; 4]);
+        bytes
+    }
+
+    #[test]
+    fn reads_png_descriptions() {
+        let png = png_with_text("Description", "  Ferris waving\0");
+        assert_eq!(
+            description("image/png", &png).as_deref(),
+            Some("Ferris waving")
+        );
+    }
+
+    #[test]
+    fn ignores_blank_and_missing_descriptions() {
+        assert_eq!(
+            description("image/png", &png_with_text("Description", " \0")),
+            None
+        );
+        assert_eq!(
+            description("image/png", &png_with_text("Title", "Ferris")),
+            None
+        );
+        assert_eq!(description("image/png", b"not a png"), None);
+        assert_eq!(description("image/jpeg", b"\xFF\xD8\xFF\xD9"), None);
+        assert_eq!(description("image/gif", b"GIF89a"), None);
+    }
+
+    #[cfg(feature = "exif")]
+    #[test]
+    fn reads_jpeg_descriptions() {
+        // A big-endian TIFF structure with a single IFD entry, an `ImageDescription` in ASCII
+        // stored at offset 26, right after the IFD
+        let mut tiff = b"MM\0\x2a\0\0\0\x08\0\x01".to_vec();
+        tiff.extend_from_slice(b"\x01\x0e\0\x02\0\0\0\x0e\0\0\0\x1a\0\0\0\0");
+        tiff.extend_from_slice(b"Ferris waving\0");
+        let mut jpeg = b"\xFF\xD8\xFF\xE1".to_vec();
+        jpeg.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(b"\xFF\xD9");
+        assert_eq!(
+            description("image/jpeg", &jpeg).as_deref(),
+            Some("Ferris waving")
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Parsing of trailing `key = value` options passed to our macros.
+
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::parse;
+use syn::parse::{Parse, ParseStream};
+
+/// A list of `key = literal` options, e.g. `, alt = "Ferris", width = 200`.
+///
+/// Options are parsed up front and then consumed one at a time by the macro implementation,
+/// which lets each macro decide which options it accepts. Call [`Options::finish`] once all
+/// supported options have been consumed in order to report any remaining unknown options.
+#[derive(Default)]
+pub(crate) struct Options {
+    entries: Vec<(syn::Ident, syn::Lit)>,
+}
+
+impl Parse for Options {
+    /// Parses zero or more options, each preceded by a comma. A trailing comma is allowed.
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        Options::parse_entries(input, true)
+    }
+}
+
+impl Options {
+    /// Parses a comma-separated list of options without a leading comma, e.g. the arguments of
+    /// an attribute like `#[doc_image(label = "foo", path = "foo.png")]`.
+    pub(crate) fn parse_list(input: ParseStream) -> parse::Result<Self> {
+        Options::parse_entries(input, false)
+    }
+
+    /// Parses options like [`Options::parse`], except that the value of the option `key` need not
+    /// be a literal. It is parsed with `parse_value` instead, e.g. to allow lists such as
+    /// `data = [("a", 1.0), ("b", 2.0)]`, and returned separately.
+    pub(crate) fn parse_with<T>(
+        input: ParseStream,
+        key: &str,
+        parse_value: impl Fn(ParseStream) -> parse::Result<T>,
+    ) -> parse::Result<(Option<T>, Self)> {
+        let mut value = None;
+        let mut rest = proc_macro2::TokenStream::new();
+        while !input.is_empty() {
+            let comma = input.parse::<syn::Token![,]>()?;
+            let fork = input.fork();
+            let is_key = fork
+                .call(syn::Ident::parse_any)
+                .ok()
+                .filter(|ident| ident == key)
+                .is_some();
+            if is_key && fork.peek(syn::Token![=]) {
+                let ident = syn::Ident::parse_any(input)?;
+                if value.is_some() {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        format!("Option `{}` was given more than once.", key),
+                    ));
+                }
+                input.parse::<syn::Token![=]>()?;
+                value = Some(parse_value(input)?);
+            } else {
+                // Leave everything else to the regular option parsing
+                rest.extend(comma.into_token_stream());
+                while !input.is_empty() && !input.peek(syn::Token![,]) {
+                    rest.extend(std::iter::once(input.parse::<proc_macro2::TokenTree>()?));
+                }
+            }
+        }
+        Ok((value, syn::parse2(rest)?))
+    }
+
+    fn parse_entries(input: ParseStream, leading_comma: bool) -> parse::Result<Self> {
+        let mut entries: Vec<(syn::Ident, syn::Lit)> = Vec::new();
+        while !input.is_empty() {
+            if leading_comma || !entries.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+                if input.is_empty() {
+                    break;
+                }
+            }
+            // Allow keywords such as `crate` as option names
+            let key = syn::Ident::parse_any(input)?;
+            input.parse::<syn::Token![=]>()?;
+            let value = input.parse::<syn::Lit>()?;
+            if entries.iter().any(|(existing, _)| existing == &key) {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!("Option `{}` was given more than once.", key),
+                ));
+            }
+            entries.push((key, value));
+        }
+        Ok(Options { entries })
+    }
+
+    fn take(&mut self, key: &str) -> Option<syn::Lit> {
+        let index = self.entries.iter().position(|(ident, _)| ident == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Takes a string-valued option.
+    pub(crate) fn take_str(&mut self, key: &str) -> parse::Result<Option<syn::LitStr>> {
+        match self.take(key) {
+            Some(syn::Lit::Str(lit)) => Ok(Some(lit)),
+            Some(lit) => Err(syn::Error::new(
+                lit.span(),
+                format!("Expected a string literal for option `{}`.", key),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Takes a boolean-valued option.
+    pub(crate) fn take_bool(&mut self, key: &str) -> parse::Result<Option<bool>> {
+        match self.take(key) {
+            Some(syn::Lit::Bool(lit)) => Ok(Some(lit.value)),
+            Some(lit) => Err(syn::Error::new(
+                lit.span(),
+                format!("Expected `true` or `false` for option `{}`.", key),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Takes an integer-valued option.
+    pub(crate) fn take_int<N>(&mut self, key: &str) -> parse::Result<Option<N>>
+    where
+        N: std::str::FromStr,
+        N::Err: std::fmt::Display,
+    {
+        match self.take(key) {
+            Some(syn::Lit::Int(lit)) => lit.base10_parse().map(Some),
+            Some(lit) => Err(syn::Error::new(
+                lit.span(),
+                format!("Expected an integer for option `{}`.", key),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Takes a floating point option. Integer literals are accepted as well.
+    pub(crate) fn take_float(&mut self, key: &str) -> parse::Result<Option<f64>> {
+        match self.take(key) {
+            Some(syn::Lit::Float(lit)) => lit.base10_parse().map(Some),
+            Some(syn::Lit::Int(lit)) => lit.base10_parse().map(Some),
+            Some(lit) => Err(syn::Error::new(
+                lit.span(),
+                format!("Expected a number for option `{}`.", key),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Reports an error for the first option that has not been consumed.
+    pub(crate) fn finish(self) -> parse::Result<()> {
+        match self.entries.into_iter().next() {
+            Some((key, _)) => Err(syn::Error::new(
+                key.span(),
+                format!("Unknown option `{}`.", key),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse::Parser;
+
+    fn options(input: &str) -> Options {
+        syn::parse_str(input).unwrap()
+    }
+
+    fn error(result: parse::Result<impl Sized>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_options_with_leading_commas() {
+        let mut options = options(r#", alt = "Ferris", width = 200, scale = 0.5, crate = true,"#);
+        assert_eq!(options.take_str("alt").unwrap().unwrap().value(), "Ferris");
+        assert_eq!(options.take_int::<u32>("width").unwrap(), Some(200));
+        assert_eq!(options.take_float("scale").unwrap(), Some(0.5));
+        assert_eq!(options.take_bool("crate").unwrap(), Some(true));
+        assert!(options.finish().is_ok());
+    }
+
+    #[test]
+    fn parses_lists_without_leading_comma() {
+        let parse = |input| Options::parse_list.parse_str(input);
+        let mut options = parse(r#"label = "a", path = "a.png""#).unwrap();
+        assert_eq!(options.take_str("path").unwrap().unwrap().value(), "a.png");
+        assert_eq!(options.take_str("label").unwrap().unwrap().value(), "a");
+        assert!(parse("").unwrap().finish().is_ok());
+        assert!(parse(r#", label = "a""#).is_err());
+    }
+
+    #[test]
+    fn missing_options_are_none() {
+        let mut options = options("");
+        assert!(options.take_str("alt").unwrap().is_none());
+        assert!(options.take_bool("alt").unwrap().is_none());
+        assert!(options.take_int::<u8>("alt").unwrap().is_none());
+        assert!(options.take_float("alt").unwrap().is_none());
+    }
+
+    #[test]
+    fn floats_accept_integers() {
+        assert_eq!(
+            options(", scale = 2").take_float("scale").unwrap(),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn reports_options_of_the_wrong_type() {
+        assert_eq!(
+            error(options(", alt = 1").take_str("alt")),
+            "Expected a string literal for option `alt`."
+        );
+        assert_eq!(
+            error(options(r#", grid = "yes""#).take_bool("grid")),
+            "Expected `true` or `false` for option `grid`."
+        );
+        assert_eq!(
+            error(options(", width = 1.5").take_int::<u32>("width")),
+            "Expected an integer for option `width`."
+        );
+        assert!(options(", width = 300").take_int::<u8>("width").is_err());
+        assert_eq!(
+            error(options(", scale = true").take_float("scale")),
+            "Expected a number for option `scale`."
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_and_unknown_options() {
+        assert_eq!(
+            error(syn::parse_str::<Options>(", alt = \"a\", alt = \"b\"")),
+            "Option `alt` was given more than once."
+        );
+        let mut options = options(r#", alt = "a", size = 1"#);
+        options.take_str("alt").unwrap();
+        assert_eq!(error(options.finish()), "Unknown option `size`.");
+    }
+
+    #[test]
+    fn parses_custom_values() {
+        let parse_list = |input: ParseStream| {
+            let content;
+            syn::bracketed!(content in input);
+            content.parse_terminated::<_, syn::Token![,]>(|input| input.parse::<syn::LitInt>())
+        };
+        let parser = |input: ParseStream| Options::parse_with(input, "data", parse_list);
+        let (data, mut options) = parser
+            .parse_str(r#", kind = "bar", data = [1, 2]"#)
+            .unwrap();
+        assert_eq!(data.unwrap().len(), 2);
+        assert_eq!(options.take_str("kind").unwrap().unwrap().value(), "bar");
+        assert!(options.finish().is_ok());
+
+        let (data, options) = parser.parse_str(r#", kind = "bar""#).unwrap();
+        assert!(data.is_none());
+        assert_eq!(options.entries.len(), 1);
+        assert!(parser.parse_str(", data = [1], data = [2]").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! Locating the source directories of dependencies.
+//!
+//! Proc macros only know the manifest directory of the crate being compiled, so we have to
+//! reconstruct the location of dependencies ourselves. This covers path dependencies declared
+//! in the crate's own manifest, vendored dependencies and dependencies from the registry, but
+//! not e.g. git dependencies.
+
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+/// The dependency tables of a manifest that we consider.
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "build-dependencies", "dev-dependencies"];
+
+/// Compares crate names, treating `-` and `_` as equal.
+fn same_crate_name(a: &str, b: &str) -> bool {
+    a.replace('-', "_") == b.replace('-', "_")
+}
+
+fn read_toml(path: &Path) -> Option<Value> {
+    read_to_string(path).ok()?.parse().ok()
+}
+
+/// Finds the path of a path dependency declared in the manifest in `manifest_dir`.
+fn find_path_dependency(manifest_dir: &Path, name: &str) -> Option<PathBuf> {
+    let manifest = read_toml(&manifest_dir.join("Cargo.toml"))?;
+    DEPENDENCY_TABLES
+        .iter()
+        .filter_map(|table| manifest.get(table)?.as_table())
+        .flat_map(|table| table.iter())
+        .find_map(|(key, spec)| {
+            // Renamed dependencies are referred to by their actual package name
+            let package = spec.get("package").and_then(Value::as_str).unwrap_or(key);
+            if same_crate_name(key, name) || same_crate_name(package, name) {
+                let path = spec.get("path")?.as_str()?;
+                Some(manifest_dir.join(path))
+            } else {
+                None
+            }
+        })
+}
+
+/// Finds the directory containing `Cargo.lock`, starting at `dir` and moving upwards.
+fn find_lock_dir(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|dir| dir.join("Cargo.lock").is_file())
+}
+
+/// Returns the locked versions of the given package.
+fn locked_versions(lock_dir: &Path, name: &str) -> Vec<String> {
+    let lock = match read_toml(&lock_dir.join("Cargo.lock")) {
+        Some(lock) => lock,
+        None => return Vec::new(),
+    };
+    lock.get("package")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|package| {
+            package
+                .get("name")
+                .and_then(Value::as_str)
+                .filter(|package_name| same_crate_name(package_name, name))
+                .is_some()
+        })
+        .filter_map(|package| Some(package.get("version")?.as_str()?.to_string()))
+        .collect()
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")))
+        .or_else(|| std::env::var_os("USERPROFILE").map(|home| Path::new(&home).join(".cargo")))
+}
+
+/// Finds the source directories of the given package version in the vendor directory and the
+/// registry.
+fn candidate_dirs(lock_dir: &Path, name: &str, version: &str) -> Vec<PathBuf> {
+    let versioned_name = format!("{}-{}", name, version);
+    let mut candidates = vec![
+        lock_dir.join("vendor").join(name),
+        lock_dir.join("vendor").join(&versioned_name),
+    ];
+    let registries = cargo_home()
+        .and_then(|home| std::fs::read_dir(home.join("registry").join("src")).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok);
+    for registry in registries {
+        candidates.push(registry.path().join(&versioned_name));
+    }
+    candidates
+}
+
+/// Locates the source directory of the dependency with the given name.
+pub(crate) fn locate_package(manifest_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    if let Some(path) = find_path_dependency(manifest_dir, name) {
+        return Ok(path);
+    }
+
+    let lock_dir = find_lock_dir(manifest_dir)
+        .ok_or_else(|| format!("Unable to locate `{}`: no Cargo.lock found.", name))?;
+    let versions = locked_versions(lock_dir, name);
+    if versions.is_empty() {
+        return Err(format!(
+            "Unable to locate `{}`: it is not a path dependency and not in Cargo.lock.",
+            name
+        ));
+    }
+    versions
+        .iter()
+        .flat_map(|version| candidate_dirs(lock_dir, name, version))
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .ok_or_else(|| {
+            format!(
+                "Unable to locate the sources of `{}` (version {}) in the vendor directory or \
+                 the registry.",
+                name,
+                versions.join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a workspace for a test with the given files, unique to this process.
+    fn workspace(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = crate::tools::temp_path(name, "d");
+        let _ = std::fs::remove_dir_all(&dir);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    const LOCK: &str = r#"
+        [[package]]
+        name = "ferris_images"
+        version = "1.2.0"
+
+        [[package]]
+        name = "ferris-images"
+        version = "2.0.0"
+
+        [[package]]
+        name = "other"
+        version = "0.1.0"
+    "#;
+
+    #[test]
+    fn compares_crate_names() {
+        assert!(same_crate_name("ferris-images", "ferris_images"));
+        assert!(same_crate_name("ferris", "ferris"));
+        assert!(!same_crate_name("ferris", "ferris-images"));
+    }
+
+    #[test]
+    fn finds_path_dependencies() {
+        let manifest = r#"
+            [dependencies]
+            images = { path = "../images" }
+            renamed = { package = "ferris-images", path = "../ferris" }
+
+            [dev-dependencies]
+            test-images = { path = "../test" }
+
+            [build-dependencies]
+            registry = "1.0"
+        "#;
+        let dir = workspace("packages_path", &[("app/Cargo.toml", manifest)]);
+        let app = dir.join("app");
+        assert_eq!(
+            find_path_dependency(&app, "images"),
+            Some(app.join("../images"))
+        );
+        assert_eq!(
+            find_path_dependency(&app, "ferris_images"),
+            Some(app.join("../ferris"))
+        );
+        assert_eq!(
+            find_path_dependency(&app, "renamed"),
+            Some(app.join("../ferris"))
+        );
+        assert_eq!(
+            find_path_dependency(&app, "test_images"),
+            Some(app.join("../test"))
+        );
+        assert_eq!(find_path_dependency(&app, "registry"), None);
+        assert_eq!(find_path_dependency(&app, "missing"), None);
+        assert_eq!(locate_package(&app, "images"), Ok(app.join("../images")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reads_locked_versions() {
+        let dir = workspace("packages_lock", &[("Cargo.lock", LOCK)]);
+        assert_eq!(find_lock_dir(&dir.join("a/b")), Some(dir.as_path()));
+        assert_eq!(locked_versions(&dir, "ferris-images"), ["1.2.0", "2.0.0"]);
+        assert!(locked_versions(&dir, "missing").is_empty());
+        assert!(locked_versions(&dir.join("a"), "other").is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn locates_vendored_dependencies() {
+        let dir = workspace(
+            "packages_vendor",
+            &[
+                ("Cargo.lock", LOCK),
+                ("app/Cargo.toml", "[dependencies]\nferris-images = \"2\""),
+                ("vendor/ferris-images-2.0.0/Cargo.toml", ""),
+                ("vendor/other/Cargo.toml", ""),
+            ],
+        );
+        let app = dir.join("app");
+        assert_eq!(
+            locate_package(&app, "ferris-images"),
+            Ok(dir.join("vendor").join("ferris-images-2.0.0"))
+        );
+        assert_eq!(
+            locate_package(&app, "other"),
+            Ok(dir.join("vendor").join("other"))
+        );
+        let err = locate_package(&app, "missing").unwrap_err();
+        assert_eq!(
+            err,
+            "Unable to locate `missing`: it is not a path dependency and not in Cargo.lock."
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+//! Rendering of PlantUML diagrams, either with a local installation or with a PlantUML server.
+
+use crate::config::EmbedDocImageConfig;
+use crate::tools;
+use std::path::Path;
+
+/// Renders the PlantUML diagram in the file at `input` to SVG.
+///
+/// The diagram is rendered with the `plantuml` command if it is in `PATH`. Otherwise, if the
+/// `plantuml-server` feature is enabled, it is rendered by the configured PlantUML server, see
+/// [`render_on_server`]. Returns the SVG image along with a warning to print, if any.
+pub(crate) fn render(
+    manifest_dir: &Path,
+    input: &Path,
+) -> Result<(Vec<u8>, Option<String>), String> {
+    if let Some(plantuml) = tools::find_in_path("plantuml", manifest_dir) {
+        let args = ["-tsvg", "-pipe", "-charset", "UTF-8"];
+        return tools::run_with_input(plantuml.as_os_str(), args, input).map(|svg| (svg, None));
+    }
+    if !cfg!(feature = "plantuml-server") {
+        return Err(
+            "`plantuml` was not found. Make sure that it is installed and in `PATH`, or \
+                    enable the `plantuml-server` feature to render diagrams with a PlantUML \
+                    server."
+                .to_string(),
+        );
+    }
+    let source = std::fs::read(input)
+        .map_err(|err| format!("Failed to read {}: {}", input.display(), err))?;
+    render_on_server(manifest_dir, &source)
+}
+
+/// Returns the URL at which the PlantUML server renders the diagram to SVG.
+fn server_url(server: &str, source: &[u8]) -> String {
+    // The `~h` prefix tells the server that the diagram is hex encoded, which saves us from
+    // implementing the deflate-based encoding that PlantUML uses by default
+    let hex: String = source.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}/svg/~h{}", server, hex)
+}
+
+/// Renders a diagram with the PlantUML server given by `EMBED_DOC_IMAGE_PLANTUML_SERVER`, using
+/// `curl`.
+///
+/// Every rendered diagram is cached in the target directory. If the server cannot be reached,
+/// the cached image of the same diagram is used instead, so that documentation still builds
+/// offline, along with a warning.
+fn render_on_server(
+    manifest_dir: &Path,
+    source: &[u8],
+) -> Result<(Vec<u8>, Option<String>), String> {
+    let config = EmbedDocImageConfig::from_env();
+    let url = server_url(&config.plantuml_server, source);
+    let dir = tools::target_dir(manifest_dir)
+        .join("embed-doc-image")
+        .join("plantuml");
+    let cached = dir.join(format!("{}.svg", crate::sha256_hex(url.as_bytes())));
+
+    let timeout = format!("{:.3}", config.plantuml_timeout.as_secs_f64());
+    let args = [
+        "--silent",
+        "--show-error",
+        "--fail",
+        "--location",
+        "--max-time",
+        timeout.as_str(),
+        url.as_str(),
+    ];
+    match tools::run("curl", args) {
+        Ok(svg) => {
+            // Failing to cache the image only means that it is not available offline
+            let _ = std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(&cached, &svg));
+            Ok((svg, None))
+        }
+        Err(err) => match std::fs::read(&cached) {
+            Ok(svg) => {
+                let warning = format!(
+                    "Unable to reach the PlantUML server at {}, using a cached image instead. {}",
+                    config.plantuml_server, err
+                );
+                Ok((svg, Some(warning)))
+            }
+            Err(_) => Err(format!(
+                "Unable to render the diagram with the PlantUML server at {}: {}",
+                config.plantuml_server, err
+            )),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_diagrams_in_server_urls() {
+        assert_eq!(
+            server_url("https://www.plantuml.com/plantuml", b"A -> B\n"),
+            "https://www.plantuml.com/plantuml/svg/~h41202d3e20420a"
+        );
+        assert_eq!(
+            server_url("http://localhost:8080", "é".as_bytes()),
+            "http://localhost:8080/svg/~hc3a9"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Minimal byte-level handling of PNG files.
+//!
+//! We only ever need to drop or inspect individual chunks, so there is no need to pull in a
+//! full PNG decoder for this.
+
+/// The 8-byte signature that every PNG file starts with.
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// A single chunk in a PNG file.
+pub(crate) struct Chunk<'a> {
+    /// The chunk type, e.g. `b"IHDR"`.
+    pub(crate) kind: [u8; 4],
+    /// The raw bytes of the full chunk, including length, type and CRC.
+    pub(crate) raw: &'a [u8],
+}
+
+/// Returns `true` if `bytes` starts with the PNG signature.
+pub(crate) fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(&SIGNATURE)
+}
+
+/// Splits a PNG file into its chunks.
+///
+/// Returns `None` if the file is not a PNG file or if it is truncated.
+pub(crate) fn chunks(bytes: &[u8]) -> Option<Vec<Chunk<'_>>> {
+    if !is_png(bytes) {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut rest = &bytes[SIGNATURE.len()..];
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return None;
+        }
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let total_len = len.checked_add(12)?;
+        if rest.len() < total_len {
+            return None;
+        }
+        let (raw, remaining) = rest.split_at(total_len);
+        chunks.push(Chunk {
+            kind: [raw[4], raw[5], raw[6], raw[7]],
+            raw,
+        });
+        rest = remaining;
+    }
+    Some(chunks)
+}
+
+/// Rebuilds a PNG file, keeping only the chunks for which `keep` returns `true`.
+///
+/// Returns `None` if the file is not a valid PNG file.
+pub(crate) fn retain_chunks(bytes: &[u8], keep: impl Fn(&Chunk) -> bool) -> Option<Vec<u8>> {
+    let chunks = chunks(bytes)?;
+    let mut output = Vec::with_capacity(bytes.len());
+    output.extend_from_slice(&SIGNATURE);
+    for chunk in chunks.iter().filter(|chunk| keep(chunk)) {
+        output.extend_from_slice(chunk.raw);
+    }
+    Some(output)
+}
+
+/// The chunk types that carry textual or EXIF metadata.
+const METADATA_CHUNKS: [&[u8; 4]; 4] = [b"tEXt", b"iTXt", b"zTXt", b"eXIf"];
+
+/// Removes textual and EXIF metadata chunks (`tEXt`, `iTXt`, `zTXt` and `eXIf`) from a PNG file.
+///
+/// Editors such as Illustrator or Inkscape often store tens of kilobytes of application-specific
+/// metadata in these chunks, which do not affect how the image is displayed. All other chunks
+/// are kept, since ancillary chunks such as `sRGB`, `iCCP` or the frames of animated PNGs do
+/// affect how the image is displayed.
+///
+/// Returns `None` if the file is not a valid PNG file.
+pub(crate) fn strip_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    retain_chunks(bytes, |chunk| !METADATA_CHUNKS.contains(&&chunk.kind))
+}
+
+/// The chunk types that carry gamma and color space information.
+const COLOR_SPACE_CHUNKS: [&[u8; 4]; 4] = [b"gAMA", b"sRGB", b"iCCP", b"cHRM"];
+
+/// Removes gamma and color space chunks (`gAMA`, `sRGB`, `iCCP` and `cHRM`) from a PNG file.
+///
+/// Browsers interpret these chunks differently (Safari on macOS honors them, while other
+/// browsers partly ignore them), so the same image can look different across platforms. Without
+/// them, all browsers display the raw pixel values.
+///
+/// Returns `None` if the file is not a valid PNG file.
+pub(crate) fn strip_gamma(bytes: &[u8]) -> Option<Vec<u8>> {
+    retain_chunks(bytes, |chunk| !COLOR_SPACE_CHUNKS.contains(&&chunk.kind))
+}
+
+/// Returns the value of the uncompressed text chunk (`tEXt` or `iTXt`) with the given keyword.
+///
+/// Compressed `zTXt` chunks and compressed `iTXt` chunks are ignored, since we would need a
+/// decompressor for them. Returns `None` if there is no such chunk, or if the file is not a
+/// valid PNG file.
+pub(crate) fn text(bytes: &[u8], keyword: &str) -> Option<String> {
+    chunks(bytes)?.iter().find_map(|chunk| {
+        let data = &chunk.raw[8..chunk.raw.len() - 4];
+        let (key, value) = split_at_nul(data)?;
+        if key != keyword.as_bytes() {
+            return None;
+        }
+        match &chunk.kind {
+            // Latin-1 text, whose code points coincide with the first 256 Unicode code points
+            b"tEXt" => Some(value.iter().map(|&byte| char::from(byte)).collect()),
+            // Compression flag and method, followed by language tag and translated keyword
+            b"iTXt" if value.first() == Some(&0) => {
+                let (_language, rest) = split_at_nul(value.get(2..)?)?;
+                let (_translated_keyword, text) = split_at_nul(rest)?;
+                String::from_utf8(text.to_vec()).ok()
+            }
+            _ => None,
+        }
+    })
+}
+
+fn split_at_nul(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let index = bytes.iter().position(|&byte| byte == 0)?;
+    Some((&bytes[..index], &bytes[index + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a chunk with the given type and data. The CRC is not checked when reading chunks,
+    /// so it is left as zero.
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    /// Builds a PNG file from the given chunks.
+    fn png(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = SIGNATURE.to_vec();
+        chunks
+            .iter()
+            .for_each(|chunk| bytes.extend_from_slice(chunk));
+        bytes
+    }
+
+    fn kinds(bytes: &[u8]) -> Vec<[u8; 4]> {
+        chunks(bytes)
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk.kind)
+            .collect()
+    }
+
+    #[test]
+    fn splits_files_into_chunks() {
+        let bytes = png(&[
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"IDAT", b"data"),
+            chunk(b"IEND", &[]),
+        ]);
+        let chunks = chunks(&bytes).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(&chunks[1].kind, b"IDAT");
+        assert_eq!(chunks[1].raw, &chunk(b"IDAT", b"data")[..]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(!is_png(b"GIF89a"));
+        assert!(!is_png(&SIGNATURE[..7]));
+        assert!(chunks(b"GIF89a").is_none());
+        assert!(strip_metadata(b"").is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = png(&[chunk(b"IHDR", &[0; 13]), chunk(b"IEND", &[])]);
+        // Cutting the file between chunks leaves a shorter, but well-formed file
+        let boundary = SIGNATURE.len() + 25;
+        for len in (SIGNATURE.len() + 1..bytes.len()).filter(|&len| len != boundary) {
+            assert!(chunks(&bytes[..len]).is_none(), "accepted {} bytes", len);
+        }
+        assert_eq!(chunks(&bytes[..boundary]).unwrap().len(), 1);
+        assert!(chunks(&SIGNATURE).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_chunks_longer_than_the_file() {
+        let mut bytes = png(&[chunk(b"IEND", &[])]);
+        bytes[SIGNATURE.len()..SIGNATURE.len() + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(chunks(&bytes).is_none());
+    }
+
+    #[test]
+    fn strips_metadata_chunks() {
+        let bytes = png(&[
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"tEXt", b"Software\0Inkscape"),
+            chunk(b"sRGB", &[0]),
+            chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x/>"),
+            chunk(b"zTXt", b"Comment\0\0xyz"),
+            chunk(b"eXIf", b"MM\0*"),
+            chunk(b"IDAT", b"data"),
+            chunk(b"IEND", &[]),
+        ]);
+        let stripped = strip_metadata(&bytes).unwrap();
+        assert_eq!(kinds(&stripped), [*b"IHDR", *b"sRGB", *b"IDAT", *b"IEND"]);
+        assert_eq!(
+            stripped,
+            png(&[
+                chunk(b"IHDR", &[0; 13]),
+                chunk(b"sRGB", &[0]),
+                chunk(b"IDAT", b"data"),
+                chunk(b"IEND", &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn strips_color_space_chunks() {
+        let bytes = png(&[
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"gAMA", &[0, 0, 0xb1, 0x8f]),
+            chunk(b"cHRM", &[0; 32]),
+            chunk(b"iCCP", b"icc\0\0data"),
+            chunk(b"sRGB", &[0]),
+            chunk(b"tEXt", b"Title\0Ferris"),
+            chunk(b"IEND", &[]),
+        ]);
+        let stripped = strip_gamma(&bytes).unwrap();
+        assert_eq!(kinds(&stripped), [*b"IHDR", *b"tEXt", *b"IEND"]);
+    }
+
+    #[test]
+    fn reads_uncompressed_text() {
+        let bytes = png(&[
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"tEXt", b"Title\0Caf\xe9"),
+            chunk(
+                b"iTXt",
+                "Description\0\0\0en\0Beschreibung\0Ferris 🦀".as_bytes(),
+            ),
+            chunk(b"iTXt", b"Comment\0\x01\0en\0\0compressed"),
+            chunk(b"IEND", &[]),
+        ]);
+        assert_eq!(text(&bytes, "Title").as_deref(), Some("Café"));
+        assert_eq!(text(&bytes, "Description").as_deref(), Some("Ferris 🦀"));
+        assert_eq!(text(&bytes, "Comment"), None);
+        assert_eq!(text(&bytes, "Author"), None);
+    }
+
+    #[test]
+    fn ignores_malformed_text_chunks() {
+        let bytes = png(&[
+            chunk(b"tEXt", b"no separator"),
+            chunk(b"iTXt", b"Description\0\0"),
+            chunk(b"iTXt", b"Comment\0\0\0en"),
+        ]);
+        assert_eq!(text(&bytes, "no separator"), None);
+        assert_eq!(text(&bytes, "Description"), None);
+        assert_eq!(text(&bytes, "Comment"), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Generation of QR code images.
+
+#[cfg(feature = "qrcode")]
+use qrcode::render::{Canvas, Pixel};
+
+#[cfg(not(feature = "qrcode"))]
+const FEATURE_DISABLED: &str =
+    "Generating QR codes requires the `qrcode` feature of `embed-doc-image`.";
+
+/// A pixel of a black and white image, which is `true` if it is dark.
+#[cfg(feature = "qrcode")]
+#[derive(Clone, Copy)]
+struct Dark(bool);
+
+/// A black and white image, stored row by row.
+#[cfg(feature = "qrcode")]
+struct Bitmap {
+    width: u32,
+    height: u32,
+    dark: Vec<bool>,
+    dark_pixel: bool,
+}
+
+#[cfg(feature = "qrcode")]
+impl Pixel for Dark {
+    type Image = Bitmap;
+    type Canvas = Bitmap;
+
+    fn default_color(color: qrcode::Color) -> Self {
+        Dark(color == qrcode::Color::Dark)
+    }
+}
+
+#[cfg(feature = "qrcode")]
+impl Canvas for Bitmap {
+    type Pixel = Dark;
+    type Image = Bitmap;
+
+    fn new(width: u32, height: u32, dark_pixel: Dark, light_pixel: Dark) -> Self {
+        Bitmap {
+            width,
+            height,
+            dark: vec![light_pixel.0; width as usize * height as usize],
+            dark_pixel: dark_pixel.0,
+        }
+    }
+
+    fn draw_dark_pixel(&mut self, x: u32, y: u32) {
+        self.dark[y as usize * self.width as usize + x as usize] = self.dark_pixel;
+    }
+
+    fn into_image(self) -> Bitmap {
+        self
+    }
+}
+
+/// Encodes `data` as a QR code, choosing the smallest version that fits.
+#[cfg(feature = "qrcode")]
+fn encode(data: &str) -> Result<qrcode::QrCode, String> {
+    qrcode::QrCode::new(data.as_bytes()).map_err(|err| err.to_string())
+}
+
+/// Generates a QR code for `data` as a PNG image that is at most `size` pixels wide.
+///
+/// Each module is at least one pixel wide, so the image can be larger than `size` for long
+/// data.
+#[cfg(feature = "qrcode")]
+pub(crate) fn png(data: &str, size: u32) -> Result<Vec<u8>, String> {
+    let bitmap = encode(data)?
+        .render::<Dark>()
+        .max_dimensions(size, size)
+        .build();
+    let pixels: Vec<u8> = bitmap
+        .dark
+        .iter()
+        .map(|&dark| if dark { 0 } else { 255 })
+        .collect();
+    crate::raster::encode_png_pixels(&pixels, bitmap.width, bitmap.height, image::ColorType::L8)
+}
+
+#[cfg(not(feature = "qrcode"))]
+pub(crate) fn png(_data: &str, _size: u32) -> Result<Vec<u8>, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+/// Generates a QR code for `data` as an SVG image that is at most `size` pixels wide.
+#[cfg(feature = "qrcode")]
+pub(crate) fn svg(data: &str, size: u32) -> Result<String, String> {
+    Ok(encode(data)?
+        .render::<qrcode::render::svg::Color>()
+        .max_dimensions(size, size)
+        .build())
+}
+
+#[cfg(not(feature = "qrcode"))]
+pub(crate) fn svg(_data: &str, _size: u32) -> Result<String, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+#[cfg(all(test, feature = "qrcode"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_matches_the_qr_code() {
+        let url = "https://crates.io/crates/embed-doc-image";
+        let png = png(url, 200).unwrap();
+        let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+            .unwrap()
+            .into_luma8();
+        assert!(image.width() <= 200 && image.width() == image.height());
+
+        // Compare every pixel to the modules of the QR code, with the default quiet zone of
+        // four modules around it
+        let code = encode(url).unwrap();
+        let modules = code.width() as u32 + 8;
+        let scale = image.width() / modules;
+        assert!(scale >= 1);
+        let colors = code.to_colors();
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let (mx, my) = ((x / scale) as i64 - 4, (y / scale) as i64 - 4);
+            let in_code =
+                (0..code.width() as i64).contains(&mx) && (0..code.width() as i64).contains(&my);
+            let dark =
+                in_code && colors[my as usize * code.width() + mx as usize] == qrcode::Color::Dark;
+            assert_eq!(pixel.0[0], if dark { 0 } else { 255 }, "at ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn png_has_at_least_one_pixel_per_module() {
+        let png = png("https://crates.io", 1).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+        let modules = encode("https://crates.io").unwrap().width() as u32 + 8;
+        assert_eq!(image.width(), modules);
+    }
+
+    #[test]
+    fn svg_is_an_svg_image() {
+        let svg = svg("https://crates.io", 200).unwrap();
+        assert_eq!(
+            crate::magic::sniff_mime_type(svg.as_bytes()),
+            Some("image/svg+xml")
+        );
+    }
+}
+
+#[cfg(all(test, not(feature = "qrcode")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generating_requires_the_qrcode_feature() {
+        assert_eq!(png("a", 10), Err(FEATURE_DISABLED.to_string()));
+        assert_eq!(svg("a", 10), Err(FEATURE_DISABLED.to_string()));
+    }
+}
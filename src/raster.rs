@@ -0,0 +1,725 @@
+//! Pixel-level processing of raster images.
+//!
+//! Decoding images requires the `image` crate, which is only available with the `image` feature.
+//! Without it, every function here fails with an error explaining how to enable the feature.
+
+#[cfg(feature = "image")]
+use crate::config::EmbedDocImageConfig;
+
+#[cfg(not(feature = "image"))]
+const FEATURE_DISABLED: &str = "Decoding images requires the `image` feature of `embed-doc-image`.";
+
+/// Characters used for ASCII art, from darkest to lightest.
+#[cfg(feature = "image")]
+const ASCII_SHADES: [char; 5] = ['█', '▓', '▒', '░', ' '];
+
+/// Renders an image as block-character art that is `width` characters wide.
+///
+/// Characters are roughly twice as tall as they are wide, so the image is squashed vertically
+/// by a factor of two to keep its proportions. Transparent pixels are treated as white.
+#[cfg(feature = "image")]
+pub(crate) fn ascii_art(bytes: &[u8], width: u32) -> Result<String, String> {
+    let image = image::load_from_memory(bytes).map_err(|err| err.to_string())?;
+    let aspect_ratio = f64::from(image.height()) / f64::from(image.width().max(1));
+    let height = ((f64::from(width) * aspect_ratio / 2.0).round() as u32).max(1);
+    let pixels = image
+        .resize_exact(width, height, image::imageops::FilterType::Triangle)
+        .into_luma_alpha8();
+    let mut art = String::new();
+    for row in pixels.rows() {
+        for pixel in row {
+            let [luma, alpha] = pixel.0;
+            // Blend with a white background
+            let darkness = u32::from(255 - luma) * u32::from(alpha) / 255;
+            let brightness = 255 - darkness;
+            let shade = brightness as usize * ASCII_SHADES.len() / 256;
+            art.push(ASCII_SHADES[shade]);
+        }
+        art.push('\n');
+    }
+    Ok(art)
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn ascii_art(_bytes: &[u8], _width: u32) -> Result<String, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+/// A rectangular region of an image, as `[x, y, width, height]` in pixels.
+pub(crate) type Region = [u32; 4];
+
+/// A color as red, green and blue components.
+pub(crate) type Rgb = [u8; 3];
+
+/// Several images tiled into a single PNG image.
+pub(crate) struct SpriteSheet {
+    pub png: Vec<u8>,
+    /// The position of the top-left corner of each image.
+    pub positions: Vec<(u32, u32)>,
+}
+
+#[cfg(feature = "image")]
+fn load(bytes: &[u8]) -> Result<image::RgbaImage, String> {
+    image::load_from_memory(bytes)
+        .map(|image| image.into_rgba8())
+        .map_err(|err| err.to_string())
+}
+
+/// Encodes an image as PNG, with the compression level given by `EMBED_DOC_IMAGE_PNG_LEVEL`.
+#[cfg(feature = "image")]
+fn encode_png(image: &image::RgbaImage) -> Result<Vec<u8>, String> {
+    encode_png_pixels(
+        image.as_raw(),
+        image.width(),
+        image.height(),
+        image::ColorType::Rgba8,
+    )
+}
+
+/// Encodes raw pixels of the given color type as PNG, like [`encode_png`].
+#[cfg(feature = "image")]
+pub(crate) fn encode_png_pixels(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: image::ColorType,
+) -> Result<Vec<u8>, String> {
+    use image::ImageEncoder;
+    use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+
+    let compression = match EmbedDocImageConfig::from_env().png_level {
+        0..=3 => CompressionType::Fast,
+        4..=6 => CompressionType::Default,
+        _ => CompressionType::Best,
+    };
+    let mut png = Vec::new();
+    PngEncoder::new_with_quality(&mut png, compression, FilterType::Adaptive)
+        .write_image(pixels, width, height, color_type)
+        .map_err(|err| err.to_string())?;
+    Ok(png)
+}
+
+/// Scales an image down to fit within the given width and height, keeping its proportions.
+///
+/// Returns `None` if the image already fits. Otherwise, the result is encoded as JPEG if
+/// `as_jpeg` is set, and as PNG otherwise. Only the first frame of animated images is kept.
+#[cfg(feature = "image")]
+pub(crate) fn shrink_to_fit(
+    bytes: &[u8],
+    max_width: u32,
+    max_height: u32,
+    as_jpeg: bool,
+) -> Result<Option<Vec<u8>>, String> {
+    let image = image::load_from_memory(bytes).map_err(|err| err.to_string())?;
+    if image.width() <= max_width && image.height() <= max_height {
+        return Ok(None);
+    }
+    let resized = image.resize(max_width, max_height, image::imageops::FilterType::Lanczos3);
+    if !as_jpeg {
+        return encode_png(&resized.into_rgba8()).map(Some);
+    }
+    let mut jpeg = Vec::new();
+    resized
+        .into_rgb8()
+        .write_to(
+            &mut std::io::Cursor::new(&mut jpeg),
+            image::ImageOutputFormat::Jpeg(EmbedDocImageConfig::from_env().jpeg_quality),
+        )
+        .map_err(|err| err.to_string())?;
+    Ok(Some(jpeg))
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn shrink_to_fit(
+    _bytes: &[u8],
+    _max_width: u32,
+    _max_height: u32,
+    _as_jpeg: bool,
+) -> Result<Option<Vec<u8>>, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+/// Makes all pixels whose color differs from `background` by at most `tolerance` in each of the
+/// red, green and blue components fully transparent. Returns the result as PNG.
+#[cfg(feature = "image")]
+pub(crate) fn make_transparent(
+    bytes: &[u8],
+    background: Rgb,
+    tolerance: u8,
+) -> Result<Vec<u8>, String> {
+    let mut image = load(bytes)?;
+    for pixel in image.pixels_mut() {
+        let is_background = pixel.0[..3]
+            .iter()
+            .zip(background)
+            .all(|(component, bg)| component.abs_diff(bg) <= tolerance);
+        if is_background {
+            pixel.0[3] = 0;
+        }
+    }
+    encode_png(&image)
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn make_transparent(
+    _bytes: &[u8],
+    _background: Rgb,
+    _tolerance: u8,
+) -> Result<Vec<u8>, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+/// Returns the smallest region, with even `x` and `y`, that contains all pixels that differ
+/// between two images of the same size, or `None` if the images are identical.
+/// Composites an image onto an opaque background of the given color, removing its alpha
+/// channel. Returns the result as PNG without an alpha channel.
+#[cfg(feature = "image")]
+pub(crate) fn flatten(bytes: &[u8], background: Rgb) -> Result<Vec<u8>, String> {
+    let image = load(bytes)?;
+    let mut pixels = Vec::with_capacity(image.as_raw().len() / 4 * 3);
+    for pixel in image.pixels() {
+        let alpha = u32::from(pixel.0[3]);
+        for (component, bg) in pixel.0[..3].iter().zip(background) {
+            // Rounded integer blending, so that opaque pixels keep their exact color
+            let blended =
+                (u32::from(*component) * alpha + u32::from(bg) * (255 - alpha) + 127) / 255;
+            pixels.push(blended as u8);
+        }
+    }
+    encode_png_pixels(
+        &pixels,
+        image.width(),
+        image.height(),
+        image::ColorType::Rgb8,
+    )
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn flatten(_bytes: &[u8], _background: Rgb) -> Result<Vec<u8>, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+#[cfg(feature = "image")]
+fn changed_region(previous: &image::RgbaImage, next: &image::RgbaImage) -> Option<Region> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in next.enumerate_pixels() {
+        if previous.get_pixel(x, y) != pixel {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    if min_x == u32::MAX {
+        return None;
+    }
+    let (x, y) = (min_x & !1, min_y & !1);
+    Some([x, y, max_x + 1 - x, max_y + 1 - y])
+}
+
+/// Converts an animated GIF image to a lossless animated WebP image.
+///
+/// Each frame only stores the region that changed since the previous frame, and identical
+/// consecutive frames are merged. GIF files do not expose their loop count through the `image`
+/// crate, so the animation always loops forever.
+#[cfg(feature = "image")]
+pub(crate) fn gif_to_webp(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    use image::codecs::gif::GifDecoder;
+    use image::codecs::webp::WebPEncoder;
+    use image::{AnimationDecoder, ImageEncoder};
+
+    let decoder = GifDecoder::new(std::io::Cursor::new(bytes)).map_err(|err| err.to_string())?;
+    let frames = decoder
+        .into_frames()
+        .collect_frames()
+        .map_err(|err| err.to_string())?;
+    let (width, height) = match frames.first() {
+        Some(frame) => frame.buffer().dimensions(),
+        None => return Err("the GIF image has no frames".to_string()),
+    };
+
+    let mut webp_frames: Vec<crate::webp::Frame> = Vec::new();
+    let mut previous: Option<&image::RgbaImage> = None;
+    for frame in &frames {
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let duration_ms = numerator / denominator.max(1);
+        let buffer = frame.buffer();
+        let region = match previous {
+            Some(previous) => match changed_region(previous, buffer) {
+                Some(region) => region,
+                None => {
+                    if let Some(last) = webp_frames.last_mut() {
+                        last.duration_ms += duration_ms;
+                    }
+                    continue;
+                }
+            },
+            None => [0, 0, width, height],
+        };
+        let [x, y, w, h] = region;
+        let pixels = image::imageops::crop_imm(buffer, x, y, w, h).to_image();
+        let mut webp = Vec::new();
+        WebPEncoder::new_lossless(&mut webp)
+            .write_image(pixels.as_raw(), w, h, image::ColorType::Rgba8)
+            .map_err(|err| err.to_string())?;
+        webp_frames.push(crate::webp::Frame {
+            webp,
+            x,
+            y,
+            width: w,
+            height: h,
+            duration_ms,
+        });
+        previous = Some(buffer);
+    }
+    Ok(crate::webp::assemble_animation(width, height, &webp_frames))
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn gif_to_webp(_bytes: &[u8]) -> Result<Vec<u8>, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+/// A format that [`convert`] can encode images in.
+#[derive(Clone, Copy)]
+pub(crate) enum TargetFormat {
+    Png,
+    WebP,
+    Jpeg,
+}
+
+impl TargetFormat {
+    /// The formats by name, as given to `embed_image_format_convert!`.
+    pub(crate) const NAMES: [(&'static str, TargetFormat); 4] = [
+        ("png", TargetFormat::Png),
+        ("webp", TargetFormat::WebP),
+        ("jpeg", TargetFormat::Jpeg),
+        ("jpg", TargetFormat::Jpeg),
+    ];
+
+    pub(crate) fn mime(self) -> &'static str {
+        match self {
+            TargetFormat::Png => "image/png",
+            TargetFormat::WebP => "image/webp",
+            TargetFormat::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+/// Re-encodes an image in the given format.
+///
+/// WebP images are encoded losslessly, and JPEG images with the quality given by
+/// `EMBED_DOC_IMAGE_JPEG_QUALITY`. For ICO files, the largest image is used. Animated GIF images
+/// are rejected, since only their first frame would be kept.
+#[cfg(feature = "image")]
+pub(crate) fn convert(bytes: &[u8], target: TargetFormat) -> Result<Vec<u8>, String> {
+    use image::codecs::gif::GifDecoder;
+    use image::codecs::webp::WebPEncoder;
+    use image::{AnimationDecoder, ImageEncoder};
+
+    if image::guess_format(bytes).ok() == Some(image::ImageFormat::Gif) {
+        let decoder =
+            GifDecoder::new(std::io::Cursor::new(bytes)).map_err(|err| err.to_string())?;
+        if decoder.into_frames().take(2).count() > 1 {
+            return Err(
+                "animated GIF images cannot be converted, since only their first frame \
+                        would be kept. Use `embed_image_gif_to_webp!` instead."
+                    .to_string(),
+            );
+        }
+    }
+    let image = image::load_from_memory(bytes).map_err(|err| err.to_string())?;
+    match target {
+        TargetFormat::Png => encode_png(&image.into_rgba8()),
+        TargetFormat::WebP => {
+            let image = image.into_rgba8();
+            let mut webp = Vec::new();
+            WebPEncoder::new_lossless(&mut webp)
+                .write_image(
+                    image.as_raw(),
+                    image.width(),
+                    image.height(),
+                    image::ColorType::Rgba8,
+                )
+                .map_err(|err| err.to_string())?;
+            Ok(webp)
+        }
+        TargetFormat::Jpeg => {
+            let mut jpeg = Vec::new();
+            image
+                .into_rgb8()
+                .write_to(
+                    &mut std::io::Cursor::new(&mut jpeg),
+                    image::ImageOutputFormat::Jpeg(EmbedDocImageConfig::from_env().jpeg_quality),
+                )
+                .map_err(|err| err.to_string())?;
+            Ok(jpeg)
+        }
+    }
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn convert(_bytes: &[u8], _target: TargetFormat) -> Result<Vec<u8>, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+/// Draws the outline of a rectangle with the given line thickness onto an image, clipping it
+/// to the image bounds.
+#[cfg(feature = "image")]
+fn draw_outline(image: &mut image::RgbaImage, region: Region, thickness: u32, color: Rgb) {
+    let [x, y, w, h] = region;
+    let color = image::Rgba([color[0], color[1], color[2], 255]);
+    let (right, bottom) = (x.saturating_add(w), y.saturating_add(h));
+    for py in y..bottom.min(image.height()) {
+        for px in x..right.min(image.width()) {
+            let on_border = px < x + thickness
+                || py < y + thickness
+                || px + thickness >= right
+                || py + thickness >= bottom;
+            if on_border {
+                image.put_pixel(px, py, color);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+fn check_region(image: &image::RgbaImage, region: Region) -> Result<(), String> {
+    let [x, y, w, h] = region;
+    let fits = u64::from(x) + u64::from(w) <= u64::from(image.width())
+        && u64::from(y) + u64::from(h) <= u64::from(image.height());
+    if w == 0 || h == 0 || !fits {
+        return Err(format!(
+            "region {}x{} at ({}, {}) does not fit within the image, which is {}x{}",
+            w,
+            h,
+            x,
+            y,
+            image.width(),
+            image.height()
+        ));
+    }
+    Ok(())
+}
+
+/// Crops the region from an image and scales it by the given factor, optionally drawing a
+/// border around the result. Returns the result as PNG.
+#[cfg(feature = "image")]
+pub(crate) fn crop_and_scale(
+    bytes: &[u8],
+    region: Region,
+    scale: f64,
+    border: Option<Rgb>,
+) -> Result<Vec<u8>, String> {
+    let mut image = load(bytes)?;
+    check_region(&image, region)?;
+    let [x, y, w, h] = region;
+    let cropped = image::imageops::crop(&mut image, x, y, w, h).to_image();
+    let (width, height) = (
+        ((f64::from(w) * scale).round() as u32).max(1),
+        ((f64::from(h) * scale).round() as u32).max(1),
+    );
+    let mut scaled = image::imageops::resize(
+        &cropped,
+        width,
+        height,
+        image::imageops::FilterType::CatmullRom,
+    );
+    if let Some(color) = border {
+        draw_outline(&mut scaled, [0, 0, width, height], 2, color);
+    }
+    encode_png(&scaled)
+}
+
+/// Draws the outline of the region onto an image. Returns the result as PNG.
+#[cfg(feature = "image")]
+pub(crate) fn outline_region(bytes: &[u8], region: Region, color: Rgb) -> Result<Vec<u8>, String> {
+    let mut image = load(bytes)?;
+    check_region(&image, region)?;
+    draw_outline(&mut image, region, 2, color);
+    encode_png(&image)
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn crop_and_scale(
+    _bytes: &[u8],
+    _region: Region,
+    _scale: f64,
+    _border: Option<Rgb>,
+) -> Result<Vec<u8>, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn outline_region(
+    _bytes: &[u8],
+    _region: Region,
+    _color: Rgb,
+) -> Result<Vec<u8>, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+/// Tiles images into a sprite sheet of square cells of `size` pixels, in a grid that is as
+/// close to square as possible.
+///
+/// Each image is scaled to fit its cell while keeping its proportions, and centered in it.
+#[cfg(feature = "image")]
+pub(crate) fn sprite_sheet(images: &[Vec<u8>], size: u32) -> Result<SpriteSheet, String> {
+    let count = images.len() as u32;
+    let cols = ((f64::from(count)).sqrt().ceil() as u32).max(1);
+    let rows = count.div_ceil(cols).max(1);
+    let mut sheet = image::RgbaImage::new(cols * size, rows * size);
+    let mut positions = Vec::with_capacity(images.len());
+    for (index, bytes) in images.iter().enumerate() {
+        let index = index as u32;
+        let (x, y) = ((index % cols) * size, (index / cols) * size);
+        let icon = image::load_from_memory(bytes)
+            .map_err(|err| err.to_string())?
+            .resize(size, size, image::imageops::FilterType::CatmullRom)
+            .into_rgba8();
+        image::imageops::overlay(
+            &mut sheet,
+            &icon,
+            i64::from(x + (size - icon.width()) / 2),
+            i64::from(y + (size - icon.height()) / 2),
+        );
+        positions.push((x, y));
+    }
+    Ok(SpriteSheet {
+        png: encode_png(&sheet)?,
+        positions,
+    })
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn sprite_sheet(_images: &[Vec<u8>], _size: u32) -> Result<SpriteSheet, String> {
+    Err(FEATURE_DISABLED.to_string())
+}
+
+#[cfg(all(test, feature = "image"))]
+mod tests {
+    use super::*;
+
+    /// Encodes an image as PNG with the `image` crate directly, independent of [`encode_png`].
+    fn png(image: &image::RgbaImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        png
+    }
+
+    /// A `width` by `height` image that is white, except for a black left half.
+    fn half_black(width: u32, height: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(width, height, |x, _| {
+            if x < width / 2 {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn renders_ascii_art() {
+        let art = ascii_art(&png(&half_black(8, 8)), 4).unwrap();
+        assert_eq!(art, "██  \n██  \n");
+    }
+
+    #[test]
+    fn ascii_art_treats_transparency_as_white() {
+        let transparent = image::RgbaImage::from_pixel(4, 2, image::Rgba([0, 0, 0, 0]));
+        assert_eq!(ascii_art(&png(&transparent), 2).unwrap(), "  \n");
+    }
+
+    #[test]
+    fn rejects_undecodable_images() {
+        assert!(ascii_art(b"not an image", 4).is_err());
+        assert!(shrink_to_fit(b"not an image", 4, 4, false).is_err());
+        assert!(convert(b"not an image", TargetFormat::Png).is_err());
+        assert!(gif_to_webp(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn shrinks_large_images_only() {
+        let image = png(&half_black(40, 20));
+        assert!(shrink_to_fit(&image, 40, 20, false).unwrap().is_none());
+        let shrunk = shrink_to_fit(&image, 10, 10, false).unwrap().unwrap();
+        let shrunk = image::load_from_memory(&shrunk).unwrap();
+        assert_eq!((shrunk.width(), shrunk.height()), (10, 5));
+        let jpeg = shrink_to_fit(&image, 10, 10, true).unwrap().unwrap();
+        assert_eq!(
+            image::guess_format(&jpeg).unwrap(),
+            image::ImageFormat::Jpeg
+        );
+    }
+
+    #[test]
+    fn makes_background_transparent() {
+        let mut image = image::RgbaImage::from_pixel(2, 1, image::Rgba([250, 255, 255, 255]));
+        image.put_pixel(1, 0, image::Rgba([200, 0, 0, 255]));
+        let result = load(&make_transparent(&png(&image), [255, 255, 255], 5).unwrap()).unwrap();
+        assert_eq!(result.get_pixel(0, 0).0[3], 0);
+        assert_eq!(result.get_pixel(1, 0).0, [200, 0, 0, 255]);
+    }
+
+    #[test]
+    fn flattens_onto_background() {
+        let mut image = image::RgbaImage::from_pixel(3, 1, image::Rgba([0, 0, 0, 0]));
+        image.put_pixel(1, 0, image::Rgba([10, 20, 30, 255]));
+        image.put_pixel(2, 0, image::Rgba([0, 0, 0, 128]));
+        let flat =
+            image::load_from_memory(&flatten(&png(&image), [255, 255, 255]).unwrap()).unwrap();
+        assert_eq!(flat.color(), image::ColorType::Rgb8);
+        let flat = flat.into_rgb8();
+        assert_eq!(flat.get_pixel(0, 0).0, [255, 255, 255]);
+        assert_eq!(flat.get_pixel(1, 0).0, [10, 20, 30]);
+        assert_eq!(flat.get_pixel(2, 0).0, [127, 127, 127]);
+    }
+
+    #[test]
+    fn finds_changed_regions() {
+        let previous = image::RgbaImage::new(8, 8);
+        assert_eq!(changed_region(&previous, &previous), None);
+        let mut next = previous.clone();
+        next.put_pixel(3, 5, image::Rgba([1, 2, 3, 4]));
+        next.put_pixel(6, 6, image::Rgba([1, 2, 3, 4]));
+        // The region starts at even coordinates
+        assert_eq!(changed_region(&previous, &next), Some([2, 4, 5, 3]));
+    }
+
+    #[test]
+    fn converts_between_formats() {
+        let image = png(&half_black(4, 4));
+        for (target, format) in [
+            (TargetFormat::Png, image::ImageFormat::Png),
+            (TargetFormat::WebP, image::ImageFormat::WebP),
+            (TargetFormat::Jpeg, image::ImageFormat::Jpeg),
+        ] {
+            let converted = convert(&image, target).unwrap();
+            assert_eq!(image::guess_format(&converted).unwrap(), format);
+            assert_eq!(
+                crate::magic::sniff_mime_type(&converted),
+                Some(target.mime())
+            );
+        }
+        // WebP images are lossless
+        let webp = convert(&image, TargetFormat::WebP).unwrap();
+        assert_eq!(load(&webp).unwrap(), half_black(4, 4));
+    }
+
+    /// Encodes the frames as an animated GIF with a delay of 100 ms each.
+    fn gif(frames: Vec<image::RgbaImage>) -> Vec<u8> {
+        let mut gif = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut gif);
+            let delay = image::Delay::from_numer_denom_ms(100, 1);
+            encoder
+                .encode_frames(
+                    frames
+                        .into_iter()
+                        .map(|frame| image::Frame::from_parts(frame, 0, 0, delay)),
+                )
+                .unwrap();
+        }
+        gif
+    }
+
+    #[test]
+    fn rejects_converting_animated_gifs() {
+        let animated = gif(vec![half_black(4, 4), image::RgbaImage::new(4, 4)]);
+        let err = convert(&animated, TargetFormat::Png).unwrap_err();
+        assert!(err.contains("embed_image_gif_to_webp!"), "{}", err);
+        let still = gif(vec![half_black(4, 4)]);
+        assert!(convert(&still, TargetFormat::Png).is_ok());
+    }
+
+    #[test]
+    fn converts_gifs_to_animated_webp() {
+        let frames = vec![
+            half_black(4, 4),
+            half_black(4, 4),
+            image::RgbaImage::new(4, 4),
+        ];
+        let webp = gif_to_webp(&gif(frames)).unwrap();
+        assert!(crate::webp::is_webp(&webp));
+        assert!(crate::webp::is_animated(&webp));
+        // The identical second frame is merged into the first
+        assert_eq!(webp.windows(4).filter(|kind| kind == b"ANMF").count(), 2);
+    }
+
+    #[test]
+    fn crops_and_scales_regions() {
+        let image = png(&half_black(8, 8));
+        let zoomed = load(&crop_and_scale(&image, [0, 0, 2, 2], 2.0, None).unwrap()).unwrap();
+        assert_eq!(zoomed.dimensions(), (4, 4));
+        assert!(zoomed.pixels().all(|pixel| pixel.0 == [0, 0, 0, 255]));
+
+        let bordered = crop_and_scale(&image, [0, 0, 8, 8], 1.0, Some([255, 0, 0])).unwrap();
+        let bordered = load(&bordered).unwrap();
+        assert_eq!(bordered.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(bordered.get_pixel(6, 6).0, [255, 0, 0, 255]);
+        assert_eq!(bordered.get_pixel(2, 2).0, [0, 0, 0, 255]);
+        assert_eq!(bordered.get_pixel(5, 5).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn outlines_regions() {
+        let image = png(&half_black(8, 8));
+        let outlined = load(&outline_region(&image, [4, 4, 4, 4], [0, 255, 0]).unwrap()).unwrap();
+        assert_eq!(outlined.get_pixel(4, 4).0, [0, 255, 0, 255]);
+        assert_eq!(outlined.get_pixel(7, 7).0, [0, 255, 0, 255]);
+        assert_eq!(outlined.get_pixel(3, 3).0, [0, 0, 0, 255]);
+        assert_eq!(outlined.get_pixel(6, 6).0, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn rejects_regions_outside_the_image() {
+        let image = png(&half_black(8, 8));
+        for region in [
+            [0, 0, 9, 1],
+            [8, 0, 1, 1],
+            [0, 0, 0, 4],
+            [u32::MAX, 0, 2, 2],
+        ] {
+            assert!(outline_region(&image, region, [0, 0, 0]).is_err());
+            assert!(crop_and_scale(&image, region, 1.0, None).is_err());
+        }
+    }
+
+    #[test]
+    fn tiles_sprite_sheets() {
+        let images = vec![
+            png(&half_black(8, 8)),
+            png(&half_black(8, 4)),
+            png(&half_black(2, 2)),
+        ];
+        let sheet = sprite_sheet(&images, 4).unwrap();
+        assert_eq!(sheet.positions, [(0, 0), (4, 0), (0, 4)]);
+        let png = load(&sheet.png).unwrap();
+        assert_eq!(png.dimensions(), (8, 8));
+        // The wide image is centered vertically in its cell
+        assert_eq!(png.get_pixel(4, 0).0[3], 0);
+        assert_eq!(png.get_pixel(4, 1).0, [0, 0, 0, 255]);
+        // The last cell is empty
+        assert_eq!(png.get_pixel(6, 6).0[3], 0);
+    }
+}
+
+#[cfg(all(test, not(feature = "image")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoding_requires_the_image_feature() {
+        assert_eq!(ascii_art(b"", 1), Err(FEATURE_DISABLED.to_string()));
+        assert_eq!(
+            convert(b"", TargetFormat::Png),
+            Err(FEATURE_DISABLED.to_string())
+        );
+        assert!(sprite_sheet(&[], 1).is_err());
+    }
+}
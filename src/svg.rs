@@ -0,0 +1,828 @@
+//! Minimal textual handling of SVG files.
+//!
+//! SVG files are XML, but most transformations we need are simple enough that we can get away
+//! with operating directly on the text. Validation and optimization use a proper XML parser,
+//! which is only available with the `svg` feature.
+
+/// Checks that an SVG file is well-formed XML with a root element.
+///
+/// This catches e.g. truncated files and unclosed or mismatched tags, which would otherwise
+/// result in a broken image that browsers silently refuse to display.
+#[cfg(feature = "svg")]
+pub(crate) fn validate(svg: &[u8]) -> Result<(), String> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_reader(svg);
+    let mut buffer = Vec::new();
+    let mut depth = 0usize;
+    let mut seen_root = false;
+    loop {
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event_into(&mut buffer)
+            .map_err(|err| format!("{} (at byte {})", err, reader.error_position()))?;
+        match event {
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                if depth == 0 && seen_root {
+                    return Err(format!("multiple root elements (at byte {})", position));
+                }
+                // Iterating the attributes checks them for e.g. duplicates and missing quotes
+                for attribute in element.attributes() {
+                    attribute
+                        .map_err(|err| format!("{} in the element at byte {}", err, position))?;
+                }
+                if let Event::Start(_) = event {
+                    depth += 1;
+                }
+                seen_root = true;
+            }
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::Eof => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+    if depth > 0 {
+        Err("unexpected end of file, some elements are not closed".to_string())
+    } else if !seen_root {
+        Err("no root element".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "svg"))]
+pub(crate) fn validate(_svg: &[u8]) -> Result<(), String> {
+    Ok(())
+}
+
+/// Namespace prefixes of editor-specific elements and attributes, which do not affect rendering.
+#[cfg(feature = "svg")]
+const EDITOR_PREFIXES: [&[u8]; 2] = [b"inkscape", b"sodipodi"];
+
+/// Whether an element or attribute name is in an editor-specific namespace, or declares one.
+#[cfg(feature = "svg")]
+fn is_editor_specific(name: &[u8]) -> bool {
+    let name = name.strip_prefix(b"xmlns:").unwrap_or(name);
+    EDITOR_PREFIXES.iter().any(|prefix| {
+        name == *prefix || (name.starts_with(prefix) && name.get(prefix.len()) == Some(&b':'))
+    })
+}
+
+/// Collapses runs of whitespace into single spaces and trims the ends.
+#[cfg(feature = "svg")]
+fn collapse_whitespace(value: &[u8]) -> Vec<u8> {
+    value
+        .split(|byte| byte.is_ascii_whitespace())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(&b' ')
+}
+
+/// Returns a copy of the start tag without editor-specific attributes, and with whitespace in
+/// path data collapsed.
+#[cfg(feature = "svg")]
+fn remove_editor_attributes(
+    element: &quick_xml::events::BytesStart,
+) -> Result<quick_xml::events::BytesStart<'static>, String> {
+    use quick_xml::events::attributes::Attribute;
+
+    let mut cleaned = element.to_owned();
+    cleaned.clear_attributes();
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|err| err.to_string())?;
+        let key = attribute.key.as_ref();
+        if is_editor_specific(key) {
+            continue;
+        }
+        let value = if key == b"d" {
+            collapse_whitespace(&attribute.value)
+        } else {
+            attribute.value.into_owned()
+        };
+        cleaned.push_attribute(Attribute {
+            key: attribute.key,
+            value: value.into(),
+        });
+    }
+    Ok(cleaned)
+}
+
+/// Applies a set of safe size optimizations to an SVG file, similar to those of SVGO.
+///
+/// This removes the XML declaration, comments, elements and attributes in the Inkscape and
+/// Sodipodi namespaces along with their namespace declarations, and groups (`<g>`) without
+/// content. Whitespace in path data is collapsed. Everything else is passed through unchanged.
+#[cfg(feature = "svg")]
+pub(crate) fn optimize(svg: &[u8]) -> Result<Vec<u8>, String> {
+    use quick_xml::events::{BytesStart, Event};
+
+    let mut reader = quick_xml::Reader::from_reader(svg);
+    let mut writer = quick_xml::Writer::new(Vec::with_capacity(svg.len()));
+    let mut buffer = Vec::new();
+    // Depth within an editor-specific element that is being skipped
+    let mut skip_depth = 0usize;
+    // Groups that have been opened, but not written because they have no content so far
+    let mut pending_groups: Vec<BytesStart<'static>> = Vec::new();
+    loop {
+        let event = reader
+            .read_event_into(&mut buffer)
+            .map_err(|err| format!("{} (at byte {})", err, reader.error_position()))?;
+        if skip_depth > 0 {
+            match event {
+                Event::Start(_) => skip_depth += 1,
+                Event::End(_) => skip_depth -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            buffer.clear();
+            continue;
+        }
+        let event = match event {
+            Event::Decl(_) | Event::Comment(_) => None,
+            Event::Eof => break,
+            Event::Start(ref element) | Event::Empty(ref element)
+                if is_editor_specific(element.name().as_ref()) =>
+            {
+                if let Event::Start(_) = event {
+                    skip_depth = 1;
+                }
+                None
+            }
+            Event::Start(element) if element.name().as_ref() == b"g" => {
+                pending_groups.push(remove_editor_attributes(&element)?);
+                None
+            }
+            Event::Empty(element) if element.name().as_ref() == b"g" => None,
+            Event::Start(element) => Some(Event::Start(remove_editor_attributes(&element)?)),
+            Event::Empty(element) => Some(Event::Empty(remove_editor_attributes(&element)?)),
+            Event::End(ref element)
+                if element.name().as_ref() == b"g" && !pending_groups.is_empty() =>
+            {
+                pending_groups.pop();
+                None
+            }
+            // Whitespace between elements is insignificant outside of text elements, which
+            // cannot contain groups, and before the root element
+            Event::Text(ref text)
+                if (!pending_groups.is_empty() || writer.get_ref().is_empty())
+                    && text.iter().all(u8::is_ascii_whitespace) =>
+            {
+                None
+            }
+            event => Some(event),
+        };
+        if let Some(event) = event {
+            for group in pending_groups.drain(..) {
+                writer
+                    .write_event(Event::Start(group))
+                    .map_err(|err| err.to_string())?;
+            }
+            writer.write_event(event).map_err(|err| err.to_string())?;
+        }
+        buffer.clear();
+    }
+    Ok(writer.into_inner())
+}
+
+#[cfg(not(feature = "svg"))]
+pub(crate) fn optimize(_svg: &[u8]) -> Result<Vec<u8>, String> {
+    Err("Optimizing SVG images requires the `svg` feature of `embed-doc-image`.".to_string())
+}
+
+/// Attributes whose values consist of numbers, possibly interspersed with commands, units or
+/// function names as in `d="M 1.5 2"` or `transform="rotate(45 10 10)"`.
+#[cfg(feature = "svg")]
+const NUMERIC_ATTRIBUTES: [&[u8]; 23] = [
+    b"d",
+    b"points",
+    b"transform",
+    b"gradientTransform",
+    b"patternTransform",
+    b"viewBox",
+    b"x",
+    b"y",
+    b"x1",
+    b"y1",
+    b"x2",
+    b"y2",
+    b"cx",
+    b"cy",
+    b"fx",
+    b"fy",
+    b"r",
+    b"rx",
+    b"ry",
+    b"width",
+    b"height",
+    b"stroke-width",
+    b"offset",
+];
+
+/// Returns the length of the number at the start of `bytes`, if any.
+#[cfg(feature = "svg")]
+fn number_len(bytes: &[u8]) -> Option<usize> {
+    let digits = |start: usize| {
+        bytes[start.min(bytes.len())..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count()
+    };
+    let mut len = usize::from(matches!(bytes.first(), Some(b'-' | b'+')));
+    let integer_digits = digits(len);
+    len += integer_digits;
+    let mut fraction_digits = 0;
+    // In path data, `1.5.5` is the two numbers `1.5` and `.5`
+    if bytes.get(len) == Some(&b'.') {
+        fraction_digits = digits(len + 1);
+        len += 1 + fraction_digits;
+    }
+    if integer_digits + fraction_digits == 0 {
+        return None;
+    }
+    if let Some(b'e' | b'E') = bytes.get(len) {
+        let sign = usize::from(matches!(bytes.get(len + 1), Some(b'-' | b'+')));
+        let exponent_digits = digits(len + 1 + sign);
+        if exponent_digits > 0 {
+            len += 1 + sign + exponent_digits;
+        }
+    }
+    Some(len)
+}
+
+/// Rounds all non-integer numbers in an attribute value to the given number of decimal places,
+/// leaving everything else as it is.
+#[cfg(feature = "svg")]
+fn round_numbers_in(value: &[u8], digits: u8) -> Vec<u8> {
+    let mut output = Vec::with_capacity(value.len());
+    let mut rest = value;
+    while !rest.is_empty() {
+        let len = match number_len(rest) {
+            Some(len) => len,
+            None => {
+                output.push(rest[0]);
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let (number, remaining) = rest.split_at(len);
+        rest = remaining;
+        let is_integer = number
+            .iter()
+            .all(|byte| byte.is_ascii_digit() || *byte == b'-' || *byte == b'+');
+        let parsed = std::str::from_utf8(number)
+            .ok()
+            .and_then(|number| number.parse::<f64>().ok());
+        let rounded = match parsed {
+            Some(parsed) if !is_integer => {
+                let mut rounded = format!("{:.*}", usize::from(digits), parsed);
+                if rounded.contains('.') {
+                    rounded.truncate(rounded.trim_end_matches('0').trim_end_matches('.').len());
+                }
+                if rounded == "-0" {
+                    rounded.remove(0);
+                }
+                rounded.into_bytes()
+            }
+            _ => number.to_vec(),
+        };
+        // A number that started with `.` may have been separated from the previous one by it
+        let needs_separator = rounded.first().is_some_and(u8::is_ascii_digit)
+            && output
+                .last()
+                .is_some_and(|byte| byte.is_ascii_digit() || *byte == b'.');
+        if needs_separator {
+            output.push(b' ');
+        }
+        output.extend_from_slice(&rounded);
+    }
+    output
+}
+
+/// Rounds the numbers in path data, transforms and geometric attributes of an SVG image to the
+/// given number of decimal places.
+///
+/// Vector editors often write coordinates with ten or more decimal places, which make up a large
+/// part of the file but are invisible at the sizes at which documentation is displayed. Numbers
+/// in all other attributes and in text are left as they are.
+#[cfg(feature = "svg")]
+pub(crate) fn round_numbers(svg: &[u8], digits: u8) -> Result<Vec<u8>, String> {
+    use quick_xml::events::Event;
+    use quick_xml::events::attributes::Attribute;
+
+    let mut reader = quick_xml::Reader::from_reader(svg);
+    let mut writer = quick_xml::Writer::new(Vec::with_capacity(svg.len()));
+    let mut buffer = Vec::new();
+    loop {
+        let event = reader
+            .read_event_into(&mut buffer)
+            .map_err(|err| format!("{} (at byte {})", err, reader.error_position()))?;
+        let event = match event {
+            Event::Eof => break,
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                let mut rounded = element.to_owned();
+                rounded.clear_attributes();
+                for attribute in element.attributes() {
+                    let attribute = attribute.map_err(|err| err.to_string())?;
+                    // Numbers contain no characters that need escaping, so the raw value is used
+                    let value = if NUMERIC_ATTRIBUTES.contains(&attribute.key.as_ref()) {
+                        round_numbers_in(&attribute.value, digits)
+                    } else {
+                        attribute.value.into_owned()
+                    };
+                    rounded.push_attribute(Attribute {
+                        key: attribute.key,
+                        value: value.into(),
+                    });
+                }
+                match event {
+                    Event::Start(_) => Event::Start(rounded),
+                    _ => Event::Empty(rounded),
+                }
+            }
+            event => event,
+        };
+        writer.write_event(event).map_err(|err| err.to_string())?;
+        buffer.clear();
+    }
+    Ok(writer.into_inner())
+}
+
+#[cfg(not(feature = "svg"))]
+pub(crate) fn round_numbers(_svg: &[u8], _digits: u8) -> Result<Vec<u8>, String> {
+    Err(
+        "Rounding numbers in SVG images requires the `svg` feature of `embed-doc-image`."
+            .to_string(),
+    )
+}
+
+/// An external resource referenced by an SVG image or stylesheet.
+pub(crate) struct Resource {
+    pub mime: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Resolves a reference to an external resource. Returns `None` for references that should be
+/// left as they are, e.g. fragments or absolute URLs.
+pub(crate) type Resolver<'a> = dyn FnMut(&str) -> Result<Option<Resource>, String> + 'a;
+
+/// Replaces every `url(...)` in a stylesheet that refers to an external resource with a data URI.
+pub(crate) fn inline_css_urls(css: &str, resolve: &mut Resolver) -> Result<String, String> {
+    let mut output = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("url(") {
+        let (before, after) = rest.split_at(start + "url(".len());
+        output.push_str(before);
+        let end = match after.find(')') {
+            Some(end) => end,
+            None => {
+                rest = after;
+                break;
+            }
+        };
+        let reference = after[..end].trim().trim_matches(|c| c == '"' || c == '\'');
+        match resolve(reference)? {
+            Some(resource) => output.push_str(&format!(
+                "\"{}\"",
+                crate::data_uri(&resource.mime, &resource.bytes)
+            )),
+            None => output.push_str(&after[..end]),
+        }
+        rest = &after[end..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Makes an SVG image self-contained by replacing references to external resources with their
+/// contents.
+///
+/// References in `href` and `xlink:href` attributes are replaced by data URIs, as are `url(...)`
+/// references in `style` attributes and `<style>` elements. Stylesheets included with
+/// `<link rel="stylesheet">` are replaced by `<style>` elements.
+#[cfg(feature = "svg")]
+pub(crate) fn inline_resources(svg: &[u8], resolve: &mut Resolver) -> Result<Vec<u8>, String> {
+    use quick_xml::events::{BytesCData, BytesEnd, BytesStart, BytesText, Event};
+
+    let xml_error = |err: quick_xml::Error| err.to_string();
+    let mut reader = quick_xml::Reader::from_reader(svg);
+    let mut writer = quick_xml::Writer::new(Vec::with_capacity(svg.len()));
+    let mut buffer = Vec::new();
+    let mut in_style = false;
+    // Depth within a `<link>` element that has been replaced and whose content is skipped
+    let mut skip_depth = 0usize;
+    loop {
+        let event = reader
+            .read_event_into(&mut buffer)
+            .map_err(|err| format!("{} (at byte {})", err, reader.error_position()))?;
+        if skip_depth > 0 {
+            match event {
+                Event::Start(_) => skip_depth += 1,
+                Event::End(_) => skip_depth -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            buffer.clear();
+            continue;
+        }
+        match event {
+            Event::Start(ref element) | Event::Empty(ref element)
+                if element.local_name().as_ref() == b"link" =>
+            {
+                let href = element
+                    .try_get_attribute("href")
+                    .map_err(|err| err.to_string())?
+                    .map(|href| href.unescape_value().map(|href| href.into_owned()))
+                    .transpose()
+                    .map_err(xml_error)?;
+                let stylesheet = match &href {
+                    Some(href) => resolve(href)?.filter(|resource| resource.mime == "text/css"),
+                    None => None,
+                };
+                match stylesheet {
+                    Some(stylesheet) => {
+                        let css = String::from_utf8(stylesheet.bytes)
+                            .map_err(|_| "stylesheet is not UTF-8".to_string())?;
+                        let css = inline_css_urls(&css, resolve)?;
+                        writer
+                            .write_event(Event::Start(BytesStart::new("style")))
+                            .map_err(|err| err.to_string())?;
+                        writer
+                            .write_event(Event::CData(BytesCData::new(css)))
+                            .map_err(|err| err.to_string())?;
+                        writer
+                            .write_event(Event::End(BytesEnd::new("style")))
+                            .map_err(|err| err.to_string())?;
+                        if let Event::Start(_) = event {
+                            skip_depth = 1;
+                        }
+                    }
+                    None => writer.write_event(event).map_err(|err| err.to_string())?,
+                }
+            }
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                let mut rewritten = element.to_owned();
+                rewritten.clear_attributes();
+                for attribute in element.attributes() {
+                    let attribute = attribute.map_err(|err| err.to_string())?;
+                    let key = std::str::from_utf8(attribute.key.as_ref())
+                        .map_err(|err| err.to_string())?;
+                    let value = attribute.unescape_value().map_err(xml_error)?;
+                    let value = if key == "href" || key.ends_with(":href") {
+                        match resolve(&value)? {
+                            Some(resource) => crate::data_uri(&resource.mime, &resource.bytes),
+                            None => value.into_owned(),
+                        }
+                    } else if key == "style" {
+                        inline_css_urls(&value, resolve)?
+                    } else {
+                        value.into_owned()
+                    };
+                    // Pushing `&str` pairs escapes the value again
+                    rewritten.push_attribute((key, value.as_str()));
+                }
+                in_style =
+                    element.local_name().as_ref() == b"style" && matches!(event, Event::Start(_));
+                let event = match event {
+                    Event::Start(_) => Event::Start(rewritten),
+                    _ => Event::Empty(rewritten),
+                };
+                writer.write_event(event).map_err(|err| err.to_string())?;
+            }
+            Event::Text(ref text) if in_style => {
+                let css = text.unescape().map_err(xml_error)?;
+                let css = inline_css_urls(&css, resolve)?;
+                writer
+                    .write_event(Event::Text(BytesText::new(&css)))
+                    .map_err(|err| err.to_string())?;
+            }
+            Event::CData(ref data) if in_style => {
+                let css = String::from_utf8_lossy(data).into_owned();
+                let css = inline_css_urls(&css, resolve)?;
+                writer
+                    .write_event(Event::CData(BytesCData::new(css)))
+                    .map_err(|err| err.to_string())?;
+            }
+            Event::End(_) => {
+                in_style = false;
+                writer.write_event(event).map_err(|err| err.to_string())?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event).map_err(|err| err.to_string())?,
+        }
+        buffer.clear();
+    }
+    Ok(writer.into_inner())
+}
+
+#[cfg(not(feature = "svg"))]
+pub(crate) fn inline_resources(_svg: &[u8], _resolve: &mut Resolver) -> Result<Vec<u8>, String> {
+    Err("Inlining SVG resources requires the `svg` feature of `embed-doc-image`.".to_string())
+}
+
+/// Removes all elements with the given (unprefixed) tag name, including their contents.
+pub(crate) fn strip_elements(svg: &str, tag: &str) -> String {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut output = String::with_capacity(svg.len());
+    let mut rest = svg;
+    while let Some(start) = find_start_tag(rest, &open) {
+        output.push_str(&rest[..start]);
+        let element = &rest[start..];
+        let start_tag_end = match element.find('>') {
+            Some(end) => end,
+            None => {
+                // Malformed, leave the remainder untouched
+                rest = element;
+                break;
+            }
+        };
+        rest = if element[..start_tag_end].ends_with('/') {
+            &element[start_tag_end + 1..]
+        } else {
+            match element.find(&close) {
+                Some(end) => &element[end + close.len()..],
+                None => "",
+            }
+        };
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Returns `true` if the SVG contains an `<a>` element with an `href` or `xlink:href` attribute.
+pub(crate) fn has_links(svg: &str) -> bool {
+    let mut rest = svg;
+    while let Some(start) = find_start_tag(rest, "<a") {
+        let element = &rest[start..];
+        let start_tag = &element[..element.find('>').unwrap_or(element.len())];
+        if start_tag.contains("href=") {
+            return true;
+        }
+        rest = &element[2..];
+    }
+    false
+}
+
+/// Finds the start of a start tag `open` (e.g. `<metadata`), making sure that we do not match
+/// on tags that merely share a prefix (e.g. `<metadataextra`).
+fn find_start_tag(svg: &str, open: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(pos) = svg[offset..].find(open) {
+        let start = offset + pos;
+        let next = svg[start + open.len()..].chars().next();
+        match next {
+            Some(c) if c.is_whitespace() || c == '>' || c == '/' => return Some(start),
+            _ => offset = start + open.len(),
+        }
+    }
+    None
+}
+
+/// Escapes text for inclusion in SVG (XML) text content and attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Wraps an image of the given dimensions in an SVG document, so that SVG content can be drawn
+/// on top of it. `overlay` is inserted as-is after the image.
+pub(crate) fn wrap_image(width: u32, height: u32, data_uri: &str, overlay: &str) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+         xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+         width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+         <image width=\"{w}\" height=\"{h}\" xlink:href=\"{uri}\"/>{overlay}</svg>",
+        w = width,
+        h = height,
+        uri = data_uri,
+        overlay = overlay
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "svg")]
+    fn optimized(svg: &str) -> String {
+        String::from_utf8(optimize(svg.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[cfg(feature = "svg")]
+    fn rounded(svg: &str, digits: u8) -> String {
+        String::from_utf8(round_numbers(svg.as_bytes(), digits).unwrap()).unwrap()
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn accepts_well_formed_svg() {
+        let svg = "<?xml version=\"1.0\"?><!-- comment --><svg><g><rect/></g></svg>";
+        assert_eq!(validate(svg.as_bytes()), Ok(()));
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn rejects_malformed_svg() {
+        let invalid = [
+            "",
+            "<!-- only a comment -->",
+            "<svg><g></svg>",
+            "<svg><rect></svg>",
+            "<svg>",
+            "<svg></svg><svg></svg>",
+            "<svg width=\"1\" width=\"2\"></svg>",
+            "<svg width=1></svg>",
+        ];
+        for svg in invalid {
+            assert!(validate(svg.as_bytes()).is_err(), "accepted {:?}", svg);
+        }
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn optimize_removes_editor_data() {
+        let svg = "<?xml version=\"1.0\"?>\n<!-- Created with Inkscape -->\n\
+                   <svg xmlns:inkscape=\"http://www.inkscape.org/namespaces/inkscape\" \
+                   xmlns:sodipodi=\"http://sodipodi.sourceforge.net/DTD/sodipodi-0.dtd\" \
+                   inkscape:version=\"1.0\" width=\"10\">\
+                   <sodipodi:namedview><inkscape:grid/></sodipodi:namedview>\
+                   <inkscape:perspective/>\
+                   <path inkscape:label=\"Path\" d=\"M 0  0\n L 1 1\"/></svg>";
+        assert_eq!(
+            optimized(svg),
+            "<svg width=\"10\"><path d=\"M 0 0 L 1 1\"/></svg>"
+        );
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn optimize_removes_empty_groups() {
+        let svg = "<svg><g id=\"a\">\n  <g/>\n  <g>\n  </g>\n</g><g><rect/></g></svg>";
+        assert_eq!(optimized(svg), "<svg><g><rect/></g></svg>");
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn optimize_keeps_text() {
+        let svg = "<svg><text x=\"1\"> Hello  <tspan>world</tspan> </text></svg>";
+        assert_eq!(optimized(svg), svg);
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn finds_numbers() {
+        assert_eq!(number_len(b"1.5 2"), Some(3));
+        assert_eq!(number_len(b"-.5,1"), Some(3));
+        assert_eq!(number_len(b"1.5.5"), Some(3));
+        assert_eq!(number_len(b"1e-3x"), Some(4));
+        assert_eq!(number_len(b"2em"), Some(1));
+        assert_eq!(number_len(b"+7"), Some(2));
+        assert_eq!(number_len(b"-"), None);
+        assert_eq!(number_len(b"."), None);
+        assert_eq!(number_len(b"M1"), None);
+        assert_eq!(number_len(b""), None);
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn rounds_numbers_in_attribute_values() {
+        let round = |value: &str, digits| {
+            String::from_utf8(round_numbers_in(value.as_bytes(), digits)).unwrap()
+        };
+        assert_eq!(round("M 1.23456 2.5", 2), "M 1.23 2.5");
+        assert_eq!(round("rotate(45.678 10 10)", 1), "rotate(45.7 10 10)");
+        assert_eq!(round("1.999,-0.0001", 2), "2,0");
+        assert_eq!(round("10.50px", 1), "10.5px");
+        assert_eq!(round("100 200", 0), "100 200");
+        // Numbers starting with `.` may be separated from the previous number by it alone
+        assert_eq!(round("M1.5.27", 1), "M1.5 0.3");
+        assert_eq!(round("M1.5-.27", 1), "M1.5-0.3");
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn rounds_numbers_in_geometric_attributes_only() {
+        let svg = "<svg viewBox=\"0 0 10.123 10.123\">\
+                   <path id=\"p1.2345\" d=\"M1.2345,2.3456\"/>\
+                   <text x=\"1.2345\">3.14159</text></svg>";
+        assert_eq!(
+            rounded(svg, 2),
+            "<svg viewBox=\"0 0 10.12 10.12\">\
+             <path id=\"p1.2345\" d=\"M1.23,2.35\"/>\
+             <text x=\"1.23\">3.14159</text></svg>"
+        );
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn rejects_malformed_svg_when_rounding() {
+        assert!(round_numbers(b"<svg width=1></svg>", 2).is_err());
+    }
+
+    #[cfg(not(feature = "svg"))]
+    #[test]
+    fn processing_requires_the_svg_feature() {
+        assert_eq!(validate(b"<svg"), Ok(()));
+        assert!(optimize(b"<svg/>").is_err());
+        assert!(round_numbers(b"<svg/>", 2).is_err());
+    }
+
+    /// Resolves `a.png` and `style.css`, leaving all other references alone.
+    fn resolve(reference: &str) -> Result<Option<Resource>, String> {
+        Ok(match reference {
+            "a.png" => Some(Resource {
+                mime: "image/png".to_string(),
+                bytes: b"png".to_vec(),
+            }),
+            "style.css" => Some(Resource {
+                mime: "text/css".to_string(),
+                bytes: b"rect { fill: url('a.png') }".to_vec(),
+            }),
+            "missing.png" => return Err("missing.png not found".to_string()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn inlines_css_urls() {
+        let css = "a { background: url(a.png) } b { background: url(\"#gradient\") }";
+        assert_eq!(
+            inline_css_urls(css, &mut resolve).unwrap(),
+            "a { background: url(\"data:image/png;base64,cG5n\") } \
+             b { background: url(\"#gradient\") }"
+        );
+        assert_eq!(
+            inline_css_urls("a { b: url(a.png", &mut resolve).unwrap(),
+            "a { b: url(a.png"
+        );
+        assert!(inline_css_urls("url(missing.png)", &mut resolve).is_err());
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn inlines_resources() {
+        let svg = "<svg xmlns:xlink=\"http://www.w3.org/1999/xlink\">\
+                   <link rel=\"stylesheet\" href=\"style.css\"/>\
+                   <image xlink:href=\"a.png\"/><use href=\"#shape\"/>\
+                   <rect style=\"fill: url(a.png)\"/>\
+                   <style>g { fill: url(a.png) }</style></svg>";
+        let inlined = inline_resources(svg.as_bytes(), &mut resolve).unwrap();
+        assert_eq!(
+            String::from_utf8(inlined).unwrap(),
+            "<svg xmlns:xlink=\"http://www.w3.org/1999/xlink\">\
+             <style><![CDATA[rect { fill: url(\"data:image/png;base64,cG5n\") }]]></style>\
+             <image xlink:href=\"data:image/png;base64,cG5n\"/><use href=\"#shape\"/>\
+             <rect style=\"fill: url(&quot;data:image/png;base64,cG5n&quot;)\"/>\
+             <style>g { fill: url(&quot;data:image/png;base64,cG5n&quot;) }</style></svg>"
+        );
+        assert!(
+            inline_resources(b"<svg><image href=\"missing.png\"/></svg>", &mut resolve).is_err()
+        );
+    }
+
+    #[test]
+    fn strips_elements() {
+        let svg = "<svg><script>alert(1)</script><rect/><script src=\"a.js\"/>\
+                   <scripts/><script\n>x</script></svg>";
+        assert_eq!(
+            strip_elements(svg, "script"),
+            "<svg><rect/><scripts/></svg>"
+        );
+        assert_eq!(strip_elements("<svg><script", "script"), "<svg><script");
+        assert_eq!(strip_elements("<svg><script>x", "script"), "<svg>");
+    }
+
+    #[test]
+    fn finds_links() {
+        assert!(has_links(
+            "<svg><a href=\"https://example.com\"><rect/></a></svg>"
+        ));
+        assert!(has_links("<svg><a xlink:href=\"#top\">top</a></svg>"));
+        assert!(!has_links(
+            "<svg><a><rect/></a><animate href=\"#a\"/></svg>"
+        ));
+        assert!(!has_links("<svg><abbr href=\"x\"/></svg>"));
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(
+            escape("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn wraps_images() {
+        let svg = wrap_image(3, 2, "data:image/png;base64,cG5n", "<circle r=\"1\"/>");
+        assert!(svg.contains("viewBox=\"0 0 3 2\""));
+        assert!(svg.contains(
+            "<image width=\"3\" height=\"2\" xlink:href=\"data:image/png;base64,cG5n\"/>\
+             <circle r=\"1\"/></svg>"
+        ));
+    }
+}
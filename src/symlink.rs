@@ -0,0 +1,143 @@
+//! Diagnosing symbolic links that cannot be resolved.
+//!
+//! Reading through a cycle of symbolic links fails with an OS error such as "Too many levels of
+//! symbolic links", which does not say which links are involved. When a path cannot be
+//! canonicalized, we therefore follow its links ourselves to describe what went wrong.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// The maximum number of symbolic links followed when resolving a path, matching Linux.
+const MAX_HOPS: usize = 40;
+
+/// Formats a chain of links as `a -> b -> c`.
+fn describe(chain: &[PathBuf]) -> String {
+    chain
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// Follows the symbolic links of every component of an absolute path.
+///
+/// Returns an error describing the chain of links if they form a cycle, exceed the maximum
+/// number of hops or end in a link whose target does not exist. Paths that merely do not exist
+/// are not considered an error here.
+pub(crate) fn check(path: &Path) -> Result<(), String> {
+    let mut hops = 0;
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+                continue;
+            }
+            Component::CurDir => continue,
+            _ => resolved.push(component),
+        }
+        let mut chain = vec![resolved.clone()];
+        let mut visited = HashSet::new();
+        while let Ok(target) = std::fs::read_link(&resolved) {
+            if !visited.insert(resolved.clone()) {
+                return Err(format!("symbolic links form a cycle: {}", describe(&chain)));
+            }
+            hops += 1;
+            if hops > MAX_HOPS {
+                return Err(format!(
+                    "more than {} symbolic links must be followed: {}",
+                    MAX_HOPS,
+                    describe(&chain)
+                ));
+            }
+            // Relative targets are relative to the directory containing the link
+            resolved.pop();
+            resolved.push(target);
+            chain.push(resolved.clone());
+        }
+        if chain.len() > 1 && std::fs::symlink_metadata(&resolved).is_err() {
+            return Err(format!(
+                "symbolic link points to a path that does not exist: {}",
+                describe(&chain)
+            ));
+        }
+        if !resolved.exists() {
+            // The path simply does not exist, which is reported elsewhere
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// Creates an empty directory for a test, unique to this process.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = crate::tools::temp_path(name, "d");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn accepts_resolvable_and_missing_paths() {
+        let dir = test_dir("symlink_resolvable");
+        std::fs::write(dir.join("image.png"), "").unwrap();
+        symlink("image.png", dir.join("link.png")).unwrap();
+        symlink(dir.join("link.png"), dir.join("link2.png")).unwrap();
+        assert_eq!(check(&dir.join("link2.png")), Ok(()));
+        assert_eq!(check(&dir.join("sub/../link.png")), Ok(()));
+        assert_eq!(check(&dir.join("missing/image.png")), Ok(()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let dir = test_dir("symlink_cycle");
+        symlink("b.png", dir.join("a.png")).unwrap();
+        symlink("a.png", dir.join("b.png")).unwrap();
+        let err = check(&dir.join("a.png")).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            err,
+            format!(
+                "symbolic links form a cycle: {0}/a.png -> {0}/b.png -> {0}/a.png",
+                dir.display()
+            )
+        );
+    }
+
+    #[test]
+    fn reports_broken_links() {
+        let dir = test_dir("symlink_broken");
+        symlink("missing.png", dir.join("a.png")).unwrap();
+        let err = check(&dir.join("a.png")).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            err,
+            format!(
+                "symbolic link points to a path that does not exist: {0}/a.png -> {0}/missing.png",
+                dir.display()
+            )
+        );
+    }
+
+    #[test]
+    fn reports_long_chains() {
+        let dir = test_dir("symlink_chain");
+        for i in 0..=MAX_HOPS {
+            symlink(format!("{}.png", i + 1), dir.join(format!("{}.png", i))).unwrap();
+        }
+        std::fs::write(dir.join(format!("{}.png", MAX_HOPS + 1)), "").unwrap();
+        let err = check(&dir.join("0.png")).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            err.starts_with("more than 40 symbolic links must be followed"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Helpers for invoking external tools at build time.
+
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Returns the target directory of the build.
+///
+/// Proc macros are not told where the target directory is, so we use `CARGO_TARGET_DIR` if it
+/// is set and otherwise assume the default `target` directory next to `Cargo.lock`.
+pub(crate) fn target_dir(manifest_dir: &Path) -> PathBuf {
+    match std::env::var_os("CARGO_TARGET_DIR") {
+        Some(dir) => manifest_dir.join(dir),
+        None => manifest_dir
+            .ancestors()
+            .find(|dir| dir.join("Cargo.lock").is_file())
+            .unwrap_or(manifest_dir)
+            .join("target"),
+    }
+}
+
+/// Returns a path in the temporary directory that is unique to this process and `name`.
+pub(crate) fn temp_path(name: &str, extension: &str) -> PathBuf {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    std::env::temp_dir().join(format!(
+        "embed-doc-image-{}-{}.{}",
+        std::process::id(),
+        sanitized,
+        extension
+    ))
+}
+
+/// Searches `PATH` for an executable called `program`, like the `which` command does.
+///
+/// Programs given as a path rather than a bare name are resolved relative to `dir` instead.
+pub(crate) fn find_in_path(program: &str, dir: &Path) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return Some(dir.join(path)).filter(|path| path.is_file());
+    }
+    let file_name = format!("{}{}", program, std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| [dir.join(program), dir.join(&file_name)])
+        .find(|candidate| candidate.is_file())
+}
+
+/// Runs `program` with the given arguments and returns its stdout.
+///
+/// Fails with a human-readable message if the program could not be started (typically because
+/// it is not installed) or exited unsuccessfully.
+pub(crate) fn run<I, S>(program: &str, args: I) -> Result<Vec<u8>, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new(program);
+    command.args(args);
+    output(program, &mut command)
+}
+
+/// Runs `program` like [`run`], but in the working directory `dir`.
+pub(crate) fn run_in<I, S>(dir: &Path, program: &OsStr, args: I) -> Result<Vec<u8>, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new(program);
+    command.args(args).current_dir(dir);
+    output(&program.to_string_lossy(), &mut command)
+}
+
+/// Runs `program` like [`run`], but with the contents of the file at `input` on stdin.
+pub(crate) fn run_with_input<I, S>(
+    program: &OsStr,
+    args: I,
+    input: &Path,
+) -> Result<Vec<u8>, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let input = std::fs::File::open(input)
+        .map_err(|err| format!("Failed to read {}: {}", input.display(), err))?;
+    let mut command = Command::new(program);
+    command.args(args).stdin(input);
+    output(&program.to_string_lossy(), &mut command)
+}
+
+fn output(program: &str, command: &mut Command) -> Result<Vec<u8>, String> {
+    let output = command.output().map_err(|err| match err.kind() {
+        ErrorKind::NotFound => format!(
+            "`{}` was not found. Make sure that it is installed and in `PATH`.",
+            program
+        ),
+        _ => format!("Failed to run `{}`: {}", program, err),
+    })?;
+    if !output.status.success() {
+        return Err(format!(
+            "`{}` failed with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_paths_are_sanitized() {
+        let path = temp_path("a/b c", "svg");
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(
+            name,
+            format!("embed-doc-image-{}-a_b_c.svg", std::process::id())
+        );
+        assert_eq!(path.parent(), Some(std::env::temp_dir().as_path()));
+    }
+
+    #[test]
+    fn target_dir_is_next_to_the_lock_file() {
+        if std::env::var_os("CARGO_TARGET_DIR").is_some() {
+            return;
+        }
+        let dir = temp_path("target_dir", "d");
+        let crate_dir = dir.join("crates").join("a");
+        std::fs::create_dir_all(&crate_dir).unwrap();
+        assert_eq!(target_dir(&crate_dir), crate_dir.join("target"));
+        std::fs::write(dir.join("Cargo.lock"), "").unwrap();
+        assert_eq!(target_dir(&crate_dir), dir.join("target"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_programs_given_as_paths() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(
+            find_in_path("./Cargo.toml", dir),
+            Some(dir.join("./Cargo.toml"))
+        );
+        assert_eq!(find_in_path("./missing", dir), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_programs_in_path() {
+        let sh = find_in_path("sh", Path::new("/")).unwrap();
+        assert!(sh.is_absolute());
+        assert_eq!(
+            find_in_path("embed-doc-image-missing-tool", Path::new("/")),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn returns_the_output_of_programs() {
+        assert_eq!(run("sh", ["-c", "printf out"]).unwrap(), b"out");
+        let dir = std::env::temp_dir();
+        let pwd = run_in(&dir, OsStr::new("sh"), ["-c", "pwd -P"]).unwrap();
+        assert_eq!(
+            Path::new(String::from_utf8(pwd).unwrap().trim()),
+            dir.canonicalize().unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn passes_files_on_stdin() {
+        let input = temp_path("passes_files_on_stdin", "txt");
+        std::fs::write(&input, "input").unwrap();
+        let output = run_with_input(OsStr::new("cat"), [] as [&str; 0], &input);
+        std::fs::remove_file(&input).unwrap();
+        assert_eq!(output.unwrap(), b"input");
+        let missing = run_with_input(OsStr::new("cat"), [] as [&str; 0], &input).unwrap_err();
+        assert!(missing.starts_with("Failed to read"), "{}", missing);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_failing_programs() {
+        assert_eq!(
+            run("sh", ["-c", "echo broken >&2; exit 3"]).unwrap_err(),
+            "`sh` failed with exit status: 3: broken"
+        );
+        assert_eq!(
+            run("embed-doc-image-missing-tool", [] as [&str; 0]).unwrap_err(),
+            "`embed-doc-image-missing-tool` was not found. Make sure that it is installed and \
+             in `PATH`."
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Minimal byte-level handling of WebP files.
+//!
+//! WebP files are RIFF containers, consisting of a 12-byte header followed by chunks that each
+//! start with a 4-byte type and a 4-byte little-endian size.
+
+/// Flag in the `VP8X` chunk that marks a file as animated.
+const ANIMATION_FLAG: u8 = 0x02;
+
+/// Returns `true` if `bytes` starts with a WebP header.
+pub(crate) fn is_webp(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP"
+}
+
+/// Returns `true` if `bytes` is an animated WebP file.
+///
+/// Static and animated WebP files share the same header and MIME type, but animated files use
+/// the extended format, whose `VP8X` chunk has the animation flag set and is followed by an
+/// `ANIM` chunk. Both appear at the start of the file, so it suffices to pass the first few
+/// hundred bytes.
+pub(crate) fn is_animated(bytes: &[u8]) -> bool {
+    if !is_webp(bytes) {
+        return false;
+    }
+    let mut rest = &bytes[12..];
+    while rest.len() >= 8 {
+        let kind = &rest[0..4];
+        let size = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        match kind {
+            b"ANIM" | b"ANMF" => return true,
+            b"VP8X"
+                if rest
+                    .get(8)
+                    .filter(|flags| *flags & ANIMATION_FLAG != 0)
+                    .is_some() =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size. Sizes beyond the end of the (possibly truncated)
+        // input end the search, also if they would overflow `usize`.
+        match size
+            .checked_add(8 + (size & 1))
+            .and_then(|total_len| rest.get(total_len..))
+        {
+            Some(remaining) => rest = remaining,
+            None => break,
+        }
+    }
+    false
+}
+
+/// Flag in the `VP8X` chunk that marks a file as containing transparency.
+#[cfg(feature = "image")]
+const ALPHA_FLAG: u8 = 0x10;
+
+/// A frame of an animation, given as a still WebP file.
+#[cfg(feature = "image")]
+pub(crate) struct Frame {
+    pub webp: Vec<u8>,
+    /// The position of the frame on the canvas, which must be even.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub duration_ms: u32,
+}
+
+#[cfg(feature = "image")]
+fn push_chunk(output: &mut Vec<u8>, kind: &[u8; 4], payload: &[u8]) {
+    output.extend_from_slice(kind);
+    output.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    output.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+#[cfg(feature = "image")]
+fn push_u24(output: &mut Vec<u8>, value: u32) {
+    output.extend_from_slice(&value.to_le_bytes()[..3]);
+}
+
+/// Assembles still WebP files into an animated WebP file of the given canvas size that loops
+/// forever.
+///
+/// Each frame replaces the pixels of the canvas that it covers, i.e. without blending, and
+/// leaves the rest of the canvas untouched.
+#[cfg(feature = "image")]
+pub(crate) fn assemble_animation(width: u32, height: u32, frames: &[Frame]) -> Vec<u8> {
+    let mut chunks = Vec::new();
+    let mut header = vec![ALPHA_FLAG | ANIMATION_FLAG, 0, 0, 0];
+    push_u24(&mut header, width - 1);
+    push_u24(&mut header, height - 1);
+    push_chunk(&mut chunks, b"VP8X", &header);
+    // Transparent background, and a loop count of 0 for looping forever
+    push_chunk(&mut chunks, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+    for frame in frames {
+        let mut payload = Vec::with_capacity(16 + frame.webp.len());
+        // The frame offset is given in units of two pixels
+        push_u24(&mut payload, frame.x / 2);
+        push_u24(&mut payload, frame.y / 2);
+        push_u24(&mut payload, frame.width - 1);
+        push_u24(&mut payload, frame.height - 1);
+        push_u24(&mut payload, frame.duration_ms.min(0xff_ffff));
+        // Do not blend with the previous frame, and do not dispose of the frame
+        payload.push(0x02);
+        // The image data of a still file consists of all chunks after the RIFF header
+        payload.extend_from_slice(&frame.webp[12..]);
+        push_chunk(&mut chunks, b"ANMF", &payload);
+    }
+
+    let mut output = Vec::with_capacity(12 + chunks.len());
+    output.extend_from_slice(b"RIFF");
+    output.extend_from_slice(&(4 + chunks.len() as u32).to_le_bytes());
+    output.extend_from_slice(b"WEBP");
+    output.extend_from_slice(&chunks);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a WebP file from the given chunks, each given as type and payload.
+    fn webp(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut body = b"WEBP".to_vec();
+        for (kind, payload) in chunks {
+            body.extend_from_slice(*kind);
+            body.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            body.extend_from_slice(payload);
+            if payload.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    fn animated() -> Vec<u8> {
+        webp(&[
+            (b"VP8X", &[0; 10]),
+            (b"ICCP", b"odd"),
+            (b"ANIM", &[0; 6]),
+            (b"ANMF", &[0; 16]),
+        ])
+    }
+
+    #[test]
+    fn recognizes_webp_headers() {
+        assert!(is_webp(&webp(&[])));
+        assert!(!is_webp(b"RIFF\0\0\0\0WAVE"));
+        assert!(!is_webp(b"RIFF\0\0\0\0WEB"));
+        assert!(!is_webp(b""));
+    }
+
+    #[test]
+    fn detects_animations() {
+        assert!(is_animated(&animated()));
+        let mut flagged = webp(&[(b"VP8X", &[0; 10])]);
+        flagged[20] = ANIMATION_FLAG;
+        assert!(is_animated(&flagged));
+        assert!(!is_animated(&webp(&[(b"VP8X", &[0; 10])])));
+        assert!(!is_animated(&webp(&[(b"VP8L", &[0x2f, 0, 0, 0, 0])])));
+    }
+
+    #[test]
+    fn handles_truncated_files() {
+        let bytes = animated();
+        for len in 0..bytes.len() {
+            // Must not panic, and cannot find the animation before the `ANIM` chunk
+            let found = is_animated(&bytes[..len]);
+            assert_eq!(found, len >= 12 + 18 + 12 + 8, "for {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn handles_chunks_larger_than_the_file() {
+        for size in [u32::MAX, u32::MAX - 1, 1 << 31, 100] {
+            let mut bytes = webp(&[(b"ICCP", &[0; 4]), (b"ANIM", &[0; 6])]);
+            bytes[16..20].copy_from_slice(&size.to_le_bytes());
+            assert!(!is_animated(&bytes), "for size {}", size);
+        }
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn assembles_animations() {
+        use image::{AnimationDecoder, ImageEncoder};
+
+        let frame = |color: [u8; 4]| {
+            let pixels = image::RgbaImage::from_pixel(2, 2, image::Rgba(color));
+            let mut webp = Vec::new();
+            image::codecs::webp::WebPEncoder::new_lossless(&mut webp)
+                .write_image(pixels.as_raw(), 2, 2, image::ColorType::Rgba8)
+                .unwrap();
+            Frame {
+                webp,
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 2,
+                duration_ms: 100,
+            }
+        };
+        let frames = [frame([255, 0, 0, 255]), frame([0, 0, 255, 255])];
+        let animation = assemble_animation(2, 2, &frames);
+        assert!(is_webp(&animation));
+        assert!(is_animated(&animation));
+        assert_eq!(
+            crate::dimensions::image_dimensions("image/webp", &animation),
+            Some((2, 2))
+        );
+
+        let decoder =
+            image::codecs::webp::WebPDecoder::new(std::io::Cursor::new(&animation)).unwrap();
+        let decoded = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].buffer().get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(decoded[1].buffer().get_pixel(1, 1).0, [0, 0, 255, 255]);
+        assert_eq!(decoded[1].delay().numer_denom_ms(), (100, 1));
+    }
+}